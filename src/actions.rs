@@ -0,0 +1,89 @@
+use crate::google_api::{self, ApiToken};
+use crate::watch::WatchedThreads;
+use anyhow::Result;
+
+pub enum Action {
+    MarkedRead { message_id: String, subject: String },
+    Watched { thread_id: String, subject: String },
+    Unwatched { thread_id: String, subject: String },
+    Labeled { message_id: String, label_id: String, label_name: String, subject: String },
+    // Conversation rows archive the whole thread
+    Archived { message_id: String, thread_id: Option<String>, subject: String },
+    // As with archiving, conversation rows trash and restore the whole thread
+    Trashed { message_id: String, thread_id: Option<String>, subject: String },
+    Untrashed { message_id: String, thread_id: Option<String>, subject: String },
+}
+
+impl Action {
+    pub fn describe(&self) -> String {
+        match self {
+            Action::MarkedRead { subject, .. } => format!("Marked read: {}", subject),
+            Action::Watched { subject, .. } => format!("Watched: {}", subject),
+            Action::Unwatched { subject, .. } => format!("Unwatched: {}", subject),
+            Action::Labeled { label_name, subject, .. } => format!("Labeled {}: {}", label_name, subject),
+            Action::Archived { subject, .. } => format!("Archived: {}", subject),
+            Action::Trashed { subject, .. } => format!("Trashed: {}", subject),
+            Action::Untrashed { subject, .. } => format!("Restored from Trash: {}", subject),
+        }
+    }
+
+    pub async fn undo(&self, token: &ApiToken, watched: &mut WatchedThreads) -> Result<()> {
+        match self {
+            Action::MarkedRead { message_id, .. } => google_api::mark_as_unread(token, message_id).await,
+            Action::Watched { thread_id, subject } | Action::Unwatched { thread_id, subject } => {
+                watched.toggle(token, thread_id, subject).await.map(|_| ())
+            }
+            Action::Labeled { message_id, label_id, .. } => {
+                google_api::modify_labels(token, message_id, &[], &[label_id]).await
+            }
+            Action::Archived { thread_id: Some(thread_id), .. } => {
+                google_api::modify_thread_labels(token, thread_id, &["INBOX"], &[]).await
+            }
+            Action::Archived { message_id, thread_id: None, .. } => {
+                google_api::modify_labels(token, message_id, &["INBOX"], &[]).await
+            }
+            Action::Trashed { thread_id: Some(thread_id), .. } => google_api::untrash_thread(token, thread_id).await,
+            Action::Trashed { message_id, thread_id: None, .. } => google_api::untrash_message(token, message_id).await,
+            Action::Untrashed { thread_id: Some(thread_id), .. } => google_api::trash_thread(token, thread_id).await,
+            Action::Untrashed { message_id, thread_id: None, .. } => google_api::trash_message(token, message_id).await,
+        }
+    }
+}
+
+pub struct LoggedAction {
+    pub action: Action,
+    pub time: String,
+    pub undone: bool,
+}
+
+#[derive(Default)]
+pub struct ActionLog {
+    pub entries: Vec<LoggedAction>,
+    pub selected: usize,
+}
+
+impl ActionLog {
+    pub fn record(&mut self, action: Action) {
+        self.entries.push(LoggedAction {
+            action,
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            undone: false,
+        });
+        // Keep the newest entry selected so "undo" defaults to the last thing done
+        self.selected = self.entries.len() - 1;
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut LoggedAction> {
+        self.entries.get_mut(self.selected)
+    }
+}
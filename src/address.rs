@@ -0,0 +1,44 @@
+// "Jane Doe <jane@example.com>" -> "jane@example.com"; bare addresses pass through
+pub fn extract_address(header_value: &str) -> String {
+    let value = header_value.trim();
+    match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => value[start + 1..end].trim().to_ascii_lowercase(),
+        _ => value.trim_matches('"').to_ascii_lowercase(),
+    }
+}
+
+// "Jane Doe <jane@example.com>" -> "Jane Doe"; falls back to the address without a name
+pub fn display_name(header_value: &str) -> String {
+    let value = header_value.trim();
+    match value.rfind('<') {
+        Some(start) if !value[..start].trim().is_empty() => value[..start].trim().trim_matches('"').to_string(),
+        _ => extract_address(value),
+    }
+}
+
+// Splits an address header on the commas between addresses, not those inside quoted names
+pub fn split_list(header_value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in header_value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => addresses.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    addresses.push(current);
+    addresses.into_iter().map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect()
+}
+
+// FNV-1a: stable across runs and Rust versions, unlike the std hasher, so a sender keeps the
+// same marker from one session to the next
+pub fn stable_hash(address: &str) -> u64 {
+    address.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
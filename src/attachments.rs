@@ -0,0 +1,239 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LIST_PAGE_SIZE: u32 = 100;
+// Keeps names well under the 255-byte limit of common filesystems, leaving room for a suffix
+const MAX_NAME_BYTES: usize = 200;
+
+/// Turns a sender-supplied filename into a plain name safe to create inside the download
+/// directory: no directory components, no control or reserved characters, no leading dots.
+pub fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').trim_end_matches(['.', ' ']);
+
+    let mut end = cleaned.len().min(MAX_NAME_BYTES);
+    while !cleaned.is_char_boundary(end) {
+        end -= 1;
+    }
+    match &cleaned[..end] {
+        "" => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// `dir/name`, then `dir/stem (2).ext` and so on
+fn candidates<'a>(dir: &'a Path, name: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let extension = path.extension().and_then(|e| e.to_str());
+    std::iter::once(dir.join(name)).chain((2..).map(move |n| match extension {
+        Some(ext) => dir.join(format!("{} ({}).{}", stem, n, ext)),
+        None => dir.join(format!("{} ({})", stem, n)),
+    }))
+}
+
+/// `dir/name`, or `dir/stem (2).ext` and so on when that is taken.
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    candidates(dir, name).find(|candidate| !candidate.exists()).unwrap_or_else(|| dir.join(name))
+}
+
+/// Creates a file, or link, at the first of `unique_path`'s names that is free. `create` must
+/// fail with `AlreadyExists` rather than replace anything, so a file that appears in between,
+/// e.g. from a second download into the same directory, is never overwritten.
+fn create_unique(dir: &Path, name: &str, mut create: impl FnMut(&Path) -> io::Result<()>) -> Result<PathBuf> {
+    for candidate in candidates(dir, name).take(10_000) {
+        match create(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to write {}", candidate.display())),
+        }
+    }
+    bail!("No free name for {} in {}", name, dir.display())
+}
+
+fn write_new(path: &Path, data: &[u8]) -> io::Result<()> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(data)
+}
+
+fn digest(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub saved: usize,
+    pub duplicates: usize,
+}
+
+/// Saves attachments into a directory, storing each distinct content only once. Files already
+/// in the directory count, so re-running a download doesn't create copies.
+pub struct Downloader {
+    dir: PathBuf,
+    // Hard-link duplicates under their own name instead of skipping them
+    link_duplicates: bool,
+    seen: HashMap<[u8; 32], PathBuf>,
+    // Files that were already in the directory, by size; read and hashed only once content of
+    // the same size comes along
+    unhashed: HashMap<u64, Vec<PathBuf>>,
+    pub summary: Summary,
+}
+
+impl Downloader {
+    pub fn new(dir: &Path, link_duplicates: bool) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let mut unhashed: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for entry in fs::read_dir(dir)?.flatten() {
+            if let Ok(metadata) = entry.metadata()
+                && metadata.is_file()
+            {
+                unhashed.entry(metadata.len()).or_default().push(entry.path());
+            }
+        }
+        Ok(Downloader {
+            dir: dir.to_path_buf(),
+            link_duplicates,
+            seen: HashMap::new(),
+            unhashed,
+            summary: Summary::default(),
+        })
+    }
+
+    /// Returns where the content now lives, whether newly written, linked or already present.
+    pub fn save(&mut self, filename: &str, data: &[u8]) -> Result<PathBuf> {
+        for path in self.unhashed.remove(&(data.len() as u64)).unwrap_or_default() {
+            if let Ok(existing) = fs::read(&path) {
+                self.seen.entry(digest(&existing)).or_insert(path);
+            }
+        }
+        let hash = digest(data);
+        if let Some(existing) = self.seen.get(&hash) {
+            self.summary.duplicates += 1;
+            let name = sanitize_filename(filename);
+            let already_named = existing.file_name().is_some_and(|n| n.to_string_lossy() == name);
+            if !self.link_duplicates || already_named {
+                return Ok(existing.clone());
+            }
+            return create_unique(&self.dir, &name, |target| fs::hard_link(existing, target))
+                .with_context(|| format!("Failed to link {}", existing.display()));
+        }
+
+        let target = create_unique(&self.dir, &sanitize_filename(filename), |target| write_new(target, data))?;
+        self.seen.insert(hash, target.clone());
+        self.summary.saved += 1;
+        Ok(target)
+    }
+}
+
+// Small parts come inline with the message; larger ones are fetched by id
+async fn part_data(token: &ApiToken, message_id: &str, part: &google_api::MessagePayload) -> Result<Vec<u8>> {
+    match part.body.as_ref().and_then(|b| b.attachment_id.as_deref()) {
+        Some(attachment_id) => google_api::get_attachment(token, message_id, attachment_id).await,
+        None => Ok(google_api::decode_part_data(part).unwrap_or_default()),
+    }
+}
+
+/// An attachment of the message open in the viewer, saved on demand with `a`.
+#[derive(Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub size: usize,
+    attachment_id: Option<String>,
+    inline: Option<Vec<u8>>,
+}
+
+impl Attachment {
+    pub fn list(detail: &google_api::MessageDetail) -> Vec<Attachment> {
+        let parts = detail.payload.as_ref().map(google_api::attachment_parts).unwrap_or_default();
+        parts
+            .into_iter()
+            .map(|part| {
+                let attachment_id = part.body.as_ref().and_then(|b| b.attachment_id.clone());
+                let inline = if attachment_id.is_none() { google_api::decode_part_data(part) } else { None };
+                Attachment {
+                    filename: part.filename.clone(),
+                    size: part.body.as_ref().map_or(0, |b| b.size),
+                    attachment_id,
+                    inline,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the attachment into `dir` under its sanitized name, never over an existing file.
+    pub async fn save(&self, token: &ApiToken, message_id: &str, dir: &Path) -> Result<PathBuf> {
+        let data = match &self.attachment_id {
+            Some(attachment_id) => google_api::get_attachment(token, message_id, attachment_id).await?,
+            None => self.inline.clone().unwrap_or_default(),
+        };
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        create_unique(dir, &sanitize_filename(&self.filename), |target| write_new(target, &data))
+    }
+}
+
+pub fn describe_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.0} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Downloads the attachments of every message matching `query`.
+pub async fn download(token: &ApiToken, query: &str, dir: &Path, link_duplicates: bool) -> Result<Summary> {
+    let mut downloader = Downloader::new(dir, link_duplicates)?;
+    let mut params = google_api::ListParams {
+        query: Some(format!("{} has:attachment", query)),
+        max_results: Some(LIST_PAGE_SIZE),
+        ..Default::default()
+    };
+    loop {
+        let page = google_api::list_messages_page(token, &params).await?;
+        for message in page.messages.unwrap_or_default() {
+            let detail = google_api::get_full_message(token, &message.id).await?;
+            let Some(payload) = &detail.payload else { continue };
+            for part in google_api::attachment_parts(payload) {
+                let data = part_data(token, &detail.id, part).await?;
+                let path = downloader.save(&part.filename, &data)?;
+                println!("{}  {}", part.filename, path.display());
+            }
+        }
+        params.page_token = page.next_page_token;
+        if params.page_token.is_none() {
+            return Ok(downloader.summary);
+        }
+    }
+}
+
+/// A message's attachments copied to a private temporary directory so they can be attached
+/// again, when forwarding it or resuming it as a draft. The copies are removed on drop.
+pub struct Copies {
+    // Created fresh and owner-only, under a random name nobody else can claim first
+    dir: tempfile::TempDir,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Copies {
+    pub async fn fetch(token: &ApiToken, detail: &google_api::MessageDetail) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("gmail-cli-attachments-")
+            .tempdir()
+            .context("Failed to create a directory for the attachments")?;
+        let mut copies = Copies { dir, paths: Vec::new() };
+        for part in detail.payload.as_ref().map(google_api::attachment_parts).unwrap_or_default() {
+            let data = part_data(token, &detail.id, part).await?;
+            let path = unique_path(copies.dir.path(), &sanitize_filename(&part.filename));
+            crate::secure::write_private(&path, data)?;
+            copies.paths.push(path);
+        }
+        Ok(copies)
+    }
+}
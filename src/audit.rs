@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+
+const AUDIT_FILE: &str = "audit.log";
+
+/// One line of the audit log, stored as JSON so it stays greppable and parseable.
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    timestamp: DateTime<Local>,
+    operation: String,
+    message_id: Option<String>,
+    details: String,
+    // None on success
+    error: Option<String>,
+}
+
+/// Appends a mutating API call and its outcome. Failing to write the log never fails the call
+/// itself.
+pub fn record<T>(operation: &str, message_id: Option<&str>, details: &str, result: &Result<T>) {
+    let entry = Entry {
+        timestamp: Local::now(),
+        operation: operation.to_string(),
+        message_id: message_id.map(str::to_string),
+        details: details.to_string(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    let Some(path) = crate::store::log_path(AUDIT_FILE) else { return };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    let Ok(line) = serde_json::to_string(&entry) else { return };
+    if let Ok(mut file) = File::options().create(true).append(true).open(&path) {
+        writeln!(file, "{}", line).ok();
+    }
+}
+
+/// Prints the last `count` entries, oldest first.
+pub fn print_tail(count: usize) -> Result<()> {
+    let path = crate::store::log_path(AUDIT_FILE).context("No log directory")?;
+    if !path.exists() {
+        println!("No mutating operations recorded yet.");
+        return Ok(());
+    }
+    let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let lines: Vec<&str> = content.lines().collect();
+    for line in &lines[lines.len().saturating_sub(count)..] {
+        let Ok(entry) = serde_json::from_str::<Entry>(line) else {
+            println!("(unreadable) {}", line);
+            continue;
+        };
+        let outcome = match &entry.error {
+            Some(error) => format!("FAILED: {}", error),
+            None => "ok".to_string(),
+        };
+        println!(
+            "{}  {:<8} {:<18} {}  [{}]",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.operation,
+            entry.message_id.as_deref().unwrap_or("-"),
+            entry.details,
+            outcome
+        );
+    }
+    Ok(())
+}
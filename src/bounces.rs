@@ -0,0 +1,148 @@
+use crate::google_api::{self, ApiToken, MessageDetail};
+use anyhow::Result;
+use std::collections::HashMap;
+
+const MAX_BOUNCES: u32 = 100;
+const MAX_SENT: u32 = 500;
+
+pub struct Bounce {
+    pub bounce_id: String,
+    pub date: String,
+    pub failed_recipients: Vec<String>,
+    pub status: Option<String>,
+    pub diagnostic: Option<String>,
+    pub original_id: Option<String>,
+    pub original_subject: Option<String>,
+}
+
+fn strip_address_type(value: &str) -> String {
+    // "rfc822; user@example.com" -> "user@example.com"
+    value.rsplit(';').next().unwrap_or(value).trim().to_string()
+}
+
+fn parse_delivery_status(detail: &MessageDetail) -> (Vec<String>, Option<String>, Option<String>) {
+    let mut recipients = Vec::new();
+    let mut status = None;
+    let mut diagnostic = None;
+
+    if let Some(report) = google_api::find_part_text(detail, "message/delivery-status") {
+        for line in report.lines() {
+            let Some((name, value)) = line.split_once(':') else { continue };
+            match name.trim().to_ascii_lowercase().as_str() {
+                "final-recipient" | "original-recipient" => {
+                    let address = strip_address_type(value);
+                    if !recipients.contains(&address) {
+                        recipients.push(address);
+                    }
+                }
+                "status" if status.is_none() => status = Some(value.trim().to_string()),
+                "diagnostic-code" if diagnostic.is_none() => {
+                    diagnostic = Some(strip_address_type(value))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Gmail's own bounces also carry the failed addresses in a header
+    if recipients.is_empty() {
+        recipients = detail
+            .get_header("X-Failed-Recipients")
+            .split(',')
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty())
+            .collect();
+    }
+
+    (recipients, status, diagnostic)
+}
+
+/// Finds delivery-failure reports from the last `days` days and links each one to the sent
+/// message it refers to. Gmail files a bounce into the original's thread, so the thread id is
+/// the primary link, with the failed recipient as a fallback.
+pub async fn detect(token: &ApiToken, days: u32) -> Result<Vec<Bounce>> {
+    let bounce_query = format!("from:(mailer-daemon OR postmaster) newer_than:{}d", days);
+    let bounce_ids = google_api::list_messages(token, &bounce_query, MAX_BOUNCES)
+        .await?
+        .messages
+        .unwrap_or_default();
+    if bounce_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let sent_query = format!("in:sent newer_than:{}d", days);
+    let sent = google_api::list_messages(token, &sent_query, MAX_SENT)
+        .await?
+        .messages
+        .unwrap_or_default();
+    // Results are newest first; keep the most recent sent message per thread
+    let mut sent_by_thread: HashMap<&str, &str> = HashMap::new();
+    for message in &sent {
+        sent_by_thread.entry(&message.thread_id).or_insert(&message.id);
+    }
+
+    let details = futures::future::join_all(
+        bounce_ids.iter().map(|m| google_api::get_full_message(token, &m.id)),
+    )
+    .await;
+
+    let mut bounces = Vec::new();
+    for detail in details.into_iter().flatten() {
+        let (failed_recipients, status, diagnostic) = parse_delivery_status(&detail);
+        let mut original_id = sent_by_thread.get(detail.thread_id.as_str()).map(|id| id.to_string());
+
+        if original_id.is_none()
+            && let Some(recipient) = failed_recipients.first()
+        {
+            let query = format!("in:sent to:{} newer_than:{}d", recipient, days);
+            original_id = google_api::list_messages(token, &query, 1)
+                .await
+                .ok()
+                .and_then(|list| list.messages)
+                .and_then(|messages| messages.into_iter().next())
+                .map(|m| m.id);
+        }
+
+        let mut original_subject = None;
+        if let Some(id) = &original_id
+            && let Ok(original) = google_api::get_message_headers(token, id, &["Subject"]).await
+        {
+            original_subject = Some(original.get_header("Subject"));
+        }
+
+        bounces.push(Bounce {
+            bounce_id: detail.id.clone(),
+            date: detail.get_header("Date"),
+            failed_recipients,
+            status,
+            diagnostic,
+            original_id,
+            original_subject,
+        });
+    }
+
+    Ok(bounces)
+}
+
+pub fn print_report(bounces: &[Bounce]) {
+    if bounces.is_empty() {
+        println!("No delivery failures found.");
+        return;
+    }
+    for bounce in bounces {
+        println!("{}", bounce.date);
+        println!("  Failed:   {}", bounce.failed_recipients.join(", "));
+        if let Some(status) = &bounce.status {
+            println!("  Status:   {}", status);
+        }
+        if let Some(diagnostic) = &bounce.diagnostic {
+            println!("  Reason:   {}", diagnostic);
+        }
+        match (&bounce.original_id, &bounce.original_subject) {
+            (Some(id), Some(subject)) => println!("  Original: {} ({})", subject, id),
+            (Some(id), None) => println!("  Original: {}", id),
+            _ => println!("  Original: not found"),
+        }
+        println!("  Report:   {}", bounce.bounce_id);
+    }
+}
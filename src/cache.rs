@@ -0,0 +1,73 @@
+use crate::google_api::MessageDetail;
+use anyhow::Result;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::PathBuf;
+
+const MESSAGES_DIR: &str = "messages";
+const LISTS_FILE: &str = "lists.json";
+
+pub fn messages_dir() -> Option<PathBuf> {
+    crate::paths::cache_dir().map(|dir| crate::store::app_dir(dir).join(MESSAGES_DIR))
+}
+
+pub fn store_message(detail: &MessageDetail) -> Result<()> {
+    if let Some(dir) = messages_dir() {
+        // Cached mail is as private as the mailbox itself
+        crate::secure::write_private(&dir.join(format!("{}.json", detail.id)), serde_json::to_string(detail)?)?;
+    }
+    Ok(())
+}
+
+// The first page of recently loaded lists, shown while the real one loads at startup
+pub fn store_lists<T: Serialize>(lists: &T) -> Result<()> {
+    if let Some(dir) = crate::paths::cache_dir().map(crate::store::app_dir) {
+        crate::secure::write_private(&dir.join(LISTS_FILE), serde_json::to_string(lists)?)?;
+    }
+    Ok(())
+}
+
+pub fn cached_lists<T: DeserializeOwned + Default>() -> T {
+    crate::paths::cache_dir()
+        .map(|dir| crate::store::app_dir(dir).join(LISTS_FILE))
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Entries that fail to parse (e.g. written by an older version) are skipped
+pub fn cached_messages() -> Vec<MessageDetail> {
+    let Some(entries) = messages_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect()
+}
+
+/// Returns the number of cached entries and the paths of those that no longer parse.
+pub fn check() -> (usize, Vec<PathBuf>) {
+    let Some(entries) = messages_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return (0, Vec::new());
+    };
+    let paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    let corrupt = paths
+        .iter()
+        .filter(|path| {
+            fs::read_to_string(path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<MessageDetail>(&content).ok())
+                .is_none()
+        })
+        .cloned()
+        .collect();
+    (paths.len(), corrupt)
+}
@@ -0,0 +1,104 @@
+use crate::address;
+use crate::cache;
+use crate::config::CautionConfig;
+use crate::google_api::MessageDetail;
+use std::collections::{HashMap, HashSet};
+
+/// Addresses seen in the local message cache: senders with the ids of their messages, and
+/// everyone I have written to.
+#[derive(Default)]
+pub struct KnownSenders {
+    senders: HashMap<String, HashSet<String>>,
+    recipients: HashSet<String>,
+}
+
+impl KnownSenders {
+    pub fn from_cache() -> Self {
+        Self::from_messages(&cache::cached_messages())
+    }
+
+    fn from_messages(messages: &[MessageDetail]) -> Self {
+        let mut known = KnownSenders::default();
+        for message in messages {
+            let sent = message.label_ids.iter().flatten().any(|l| l == "SENT");
+            if sent {
+                for header in ["To", "Cc"] {
+                    known.recipients.extend(
+                        message.get_header(header).split(',').map(address::extract_address).filter(|a| !a.is_empty()),
+                    );
+                }
+            } else {
+                let from = address::extract_address(&message.get_header("From"));
+                known.senders.entry(from).or_default().insert(message.id.clone());
+            }
+        }
+        known
+    }
+
+    /// Whether `from` has never written before, apart from message `id` itself, and isn't
+    /// someone I have written to.
+    pub fn is_first_time(&self, from: &str, id: &str) -> bool {
+        let from = address::extract_address(from);
+        if self.recipients.contains(&from) {
+            return false;
+        }
+        !self.senders.get(&from).is_some_and(|ids| ids.iter().any(|other| other != id))
+    }
+}
+
+/// Local signals that a message deserves a second look, independent of Gmail's spam verdict.
+#[derive(Debug, Default, Clone)]
+pub struct Verdict {
+    pub score: u32,
+    pub reasons: Vec<&'static str>,
+}
+
+impl Verdict {
+    pub fn is_flagged(&self, config: &CautionConfig) -> bool {
+        config.threshold > 0 && self.score >= config.threshold
+    }
+}
+
+fn domain(address: &str) -> &str {
+    address.rsplit_once('@').map_or("", |(_, domain)| domain)
+}
+
+// Compares the last two labels, so replies to a subdomain of the sender ("mail.example.com")
+// aren't a mismatch. Under two-part suffixes like co.uk this errs towards not flagging.
+fn base_domain(domain: &str) -> String {
+    let labels: Vec<&str> = domain.rsplit('.').take(2).collect();
+    labels.into_iter().rev().collect::<Vec<_>>().join(".")
+}
+
+/// Scores a message from its From and Reply-To headers and, once the body has been
+/// analyzed, its tracker count.
+pub fn assess(
+    id: &str,
+    from: &str,
+    reply_to: &str,
+    trackers: Option<usize>,
+    known: &KnownSenders,
+    config: &CautionConfig,
+) -> Verdict {
+    let mut verdict = Verdict::default();
+    let mut flag = |weight: u32, reason: &'static str| {
+        verdict.score += weight;
+        verdict.reasons.push(reason);
+    };
+    let sender = address::extract_address(from);
+    let sender_domain = domain(&sender);
+    if config.suspicious_tlds.iter().any(|tld| sender_domain.ends_with(&format!(".{}", tld.trim_start_matches('.')))) {
+        flag(2, "suspicious domain");
+    }
+    let reply_to = address::extract_address(reply_to);
+    if !reply_to.is_empty() && base_domain(domain(&reply_to)) != base_domain(sender_domain) {
+        flag(2, "reply-to mismatch");
+    }
+    if trackers.is_some_and(|count| count > config.max_trackers) {
+        flag(1, "many trackers");
+    }
+    if known.is_first_time(from, id) {
+        flag(1, "first-time sender");
+    }
+    verdict
+}
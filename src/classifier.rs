@@ -0,0 +1,106 @@
+use crate::address;
+use crate::google_api::MessageDetail;
+use std::collections::{HashMap, HashSet};
+
+// Messages without any user label train this class, so ordinary mail isn't forced into one
+const NO_LABEL: &str = "";
+// A label needs a few examples before it is worth suggesting
+const MIN_EXAMPLES: usize = 3;
+// Posterior probability the best label needs over everything else, including no label
+const MIN_CONFIDENCE: f64 = 0.7;
+const MIN_WORD_LEN: usize = 3;
+
+#[derive(Default)]
+struct ClassStats {
+    messages: usize,
+    words: HashMap<String, usize>,
+    total_words: usize,
+}
+
+/// Multinomial naive Bayes over sender, subject and snippet words, trained from how user
+/// labels are already applied in the local message cache. Runs entirely offline.
+#[derive(Default)]
+pub struct Classifier {
+    classes: HashMap<String, ClassStats>,
+    vocabulary: HashSet<String>,
+    messages: usize,
+}
+
+// Sender and domain are their own features so they aren't drowned out by subject words
+fn features(from: &str, subject: &str, snippet: &str) -> Vec<String> {
+    let sender = address::extract_address(from);
+    let mut features = Vec::new();
+    if let Some((_, domain)) = sender.split_once('@') {
+        features.push(format!("domain:{}", domain));
+    }
+    features.push(format!("from:{}", sender));
+    let words = |text: &str, prefix: &str| -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= MIN_WORD_LEN)
+            .map(|w| format!("{}{}", prefix, w.to_lowercase()))
+            .collect()
+    };
+    features.extend(words(subject, "subject:"));
+    features.extend(words(snippet, ""));
+    features
+}
+
+fn user_labels(message: &MessageDetail) -> Vec<&str> {
+    message
+        .label_ids
+        .iter()
+        .flatten()
+        .filter(|l| l.starts_with("Label_"))
+        .map(String::as_str)
+        .collect()
+}
+
+impl Classifier {
+    pub fn train(messages: &[MessageDetail]) -> Self {
+        let mut classifier = Classifier::default();
+        for message in messages.iter().filter(|m| !m.label_ids.iter().flatten().any(|l| l == "SENT" || l == "DRAFT")) {
+            let words = features(&message.get_header("From"), &message.get_header("Subject"), &message.snippet);
+            let labels = user_labels(message);
+            let labels = if labels.is_empty() { vec![NO_LABEL] } else { labels };
+            for label in labels {
+                let stats = classifier.classes.entry(label.to_string()).or_default();
+                stats.messages += 1;
+                stats.total_words += words.len();
+                for word in &words {
+                    *stats.words.entry(word.clone()).or_default() += 1;
+                }
+            }
+            classifier.vocabulary.extend(words);
+            classifier.messages += 1;
+        }
+        classifier
+    }
+
+    /// The most likely user label for a message, if the model is confident enough and the
+    /// message doesn't have it already.
+    pub fn suggest(&self, from: &str, subject: &str, snippet: &str, existing: &[String]) -> Option<String> {
+        let words = features(from, subject, snippet);
+        let vocabulary = self.vocabulary.len() as f64;
+        let scores: Vec<(&str, f64)> = self
+            .classes
+            .iter()
+            .filter(|(label, stats)| label.as_str() == NO_LABEL || stats.messages >= MIN_EXAMPLES)
+            .map(|(label, stats)| {
+                let prior = (stats.messages as f64 / self.messages as f64).ln();
+                let denominator = stats.total_words as f64 + vocabulary;
+                // Laplace smoothing keeps unseen words from zeroing a class out
+                let likelihood: f64 = words
+                    .iter()
+                    .map(|w| ((*stats.words.get(w).unwrap_or(&0) + 1) as f64 / denominator).ln())
+                    .sum();
+                (label.as_str(), prior + likelihood)
+            })
+            .collect();
+        let (best, best_score) = scores.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        // Normalize in log space: exp(best) / sum(exp(all))
+        let total: f64 = scores.iter().map(|(_, score)| (score - best_score).exp()).sum();
+        let confidence = 1.0 / total;
+        (best != NO_LABEL && confidence >= MIN_CONFIDENCE && !existing.iter().any(|l| l == best))
+            .then(|| best.to_string())
+    }
+}
@@ -0,0 +1,374 @@
+use crate::address;
+use crate::config::{ComposeConfig, ReplyStyle};
+use crate::mime::OutgoingMessage;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Recipients {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Recipients {
+    fn into_vec(self) -> Vec<String> {
+        let list = match self {
+            Recipients::One(s) => vec![s],
+            Recipients::Many(v) => v,
+        };
+        list.iter()
+            .flat_map(|s| s.split(','))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct FrontMatter {
+    to: Option<Recipients>,
+    cc: Option<Recipients>,
+    bcc: Option<Recipients>,
+    subject: String,
+    attachments: Vec<String>,
+    // Overrides `compose.markdown_html` for this message
+    html: Option<bool>,
+    // Save to Gmail Drafts instead of sending
+    draft: bool,
+    // One of the account's send-as aliases; checked before sending
+    from: Option<String>,
+}
+
+// Splits "---\n<yaml>\n---\n<body>" or "{<json>}\n<body>" into the header block and the body
+fn split_front_matter(content: &str) -> Result<(FrontMatter, String)> {
+    let trimmed = content.trim_start_matches('\u{feff}');
+
+    if trimmed.starts_with('{') {
+        let mut stream = serde_json::Deserializer::from_str(trimmed).into_iter::<FrontMatter>();
+        let front_matter = stream
+            .next()
+            .context("Missing JSON front-matter")?
+            .context("Invalid JSON front-matter")?;
+        let body = trimmed[stream.byte_offset()..].trim_start_matches(['\r', '\n']);
+        return Ok((front_matter, body.to_string()));
+    }
+
+    let mut lines = trimmed.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        bail!("Message file must start with a '---' YAML or '{{' JSON front-matter block");
+    }
+    let mut yaml = String::new();
+    let mut closed = false;
+    for line in lines.by_ref() {
+        if line.trim_end() == "---" {
+            closed = true;
+            break;
+        }
+        yaml.push_str(line);
+        yaml.push('\n');
+    }
+    if !closed {
+        bail!("Unterminated front-matter block (missing closing '---')");
+    }
+    let front_matter: FrontMatter = if yaml.trim().is_empty() {
+        FrontMatter::default()
+    } else {
+        serde_yaml::from_str(&yaml).context("Invalid YAML front-matter")?
+    };
+    let body = lines.collect::<Vec<_>>().join("\n");
+    Ok((front_matter, body))
+}
+
+// Headers the composer owns; configured defaults may not replace them
+const RESERVED_HEADERS: [&str; 8] = [
+    "mime-version",
+    "to",
+    "cc",
+    "bcc",
+    "subject",
+    "content-type",
+    "content-transfer-encoding",
+    "content-disposition",
+];
+
+fn push_unique(list: &mut Vec<String>, address: &str) {
+    if !list.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+        list.push(address.to_string());
+    }
+}
+
+/// Adds the configured auto-cc/bcc recipients, default headers and wrapping to a message.
+pub fn apply_defaults(message: &mut OutgoingMessage, config: &ComposeConfig) {
+    for address in &config.auto_cc {
+        push_unique(&mut message.cc, address);
+    }
+    for address in &config.auto_bcc {
+        push_unique(&mut message.bcc, address);
+    }
+    for (name, value) in &config.headers {
+        let reserved = RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str());
+        let already_set = message.extra_headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name));
+        if !reserved && !already_set {
+            message.extra_headers.push((name.clone(), value.clone()));
+        }
+    }
+    if config.wrap_column > 0 {
+        message.wrap_column = Some(config.wrap_column);
+    }
+    message.html_alternative |= config.markdown_html;
+}
+
+pub fn parse_message(content: &str, base_dir: &Path, config: &ComposeConfig) -> Result<OutgoingMessage> {
+    let (front_matter, body) = split_front_matter(content)?;
+    let to = front_matter.to.map(Recipients::into_vec).unwrap_or_default();
+    // A draft can be saved before its recipients are known
+    if to.is_empty() && !front_matter.draft {
+        bail!("Front-matter must specify at least one 'to' recipient");
+    }
+
+    let mut message = OutgoingMessage {
+        to,
+        cc: front_matter.cc.map(Recipients::into_vec).unwrap_or_default(),
+        bcc: front_matter.bcc.map(Recipients::into_vec).unwrap_or_default(),
+        subject: front_matter.subject,
+        body,
+        // Attachment paths are relative to the message file, not the working directory
+        attachments: front_matter
+            .attachments
+            .iter()
+            .map(|a| base_dir.join(a))
+            .collect(),
+        save_as_draft: front_matter.draft,
+        ..Default::default()
+    };
+    if let Some(from) = front_matter.from {
+        message.extra_headers.push(("From".to_string(), from));
+    }
+    apply_defaults(&mut message, config);
+    if let Some(html) = front_matter.html {
+        message.html_alternative = html;
+    }
+    Ok(message)
+}
+
+pub fn load_message_file(path: &Path, config: &ComposeConfig) -> Result<OutgoingMessage> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read message file {}", path.display()))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    parse_message(&content, base_dir, config)
+}
+
+pub struct ReplyDraft {
+    pub body: String,
+    // Line of the body (from 0) where the reply text goes
+    pub cursor_line: usize,
+}
+
+/// The "On <date>, <name> wrote:" line from `compose.attribution`. `received_at` is used for
+/// {date} when known, otherwise the raw Date header is shown as is.
+pub fn attribution(from: &str, received_at: Option<i64>, date_header: &str, config: &ComposeConfig) -> Result<Option<String>> {
+    if config.attribution.trim().is_empty() {
+        return Ok(None);
+    }
+    let locale = match &config.locale {
+        Some(name) => name.parse::<chrono::Locale>().map_err(|_| anyhow!("Unknown locale {:?} in compose.locale", name))?,
+        None => chrono::Locale::POSIX,
+    };
+    // An invalid format from the config fails the write, where to_string would panic; the
+    // raw header is shown instead
+    let mut date = String::new();
+    let formatted = received_at.and_then(|secs| chrono::DateTime::from_timestamp(secs, 0)).is_some_and(|at| {
+        write!(date, "{}", at.with_timezone(&chrono::Local).format_localized(&config.attribution_date_format, locale)).is_ok()
+    });
+    if !formatted {
+        date = date_header.to_string();
+    }
+    Ok(Some(
+        config
+            .attribution
+            .replace("{date}", &date)
+            .replace("{name}", &address::display_name(from))
+            .replace("{email}", &address::extract_address(from))
+            .replace("{from}", from.trim()),
+    ))
+}
+
+/// Quotes `original` under `attribution` and leaves room for the reply according to `style`.
+pub fn reply_draft(attribution: Option<String>, original: &str, style: ReplyStyle) -> ReplyDraft {
+    let quote = |line: &str| {
+        if line.is_empty() || line.starts_with('>') { format!(">{}", line) } else { format!("> {}", line) }
+    };
+    let original = original.trim_end();
+
+    let mut lines = Vec::new();
+    let cursor_line = match style {
+        ReplyStyle::Top => {
+            lines.extend([String::new(), String::new()]);
+            lines.extend(attribution);
+            lines.extend(original.lines().map(quote));
+            0
+        }
+        ReplyStyle::Bottom => {
+            lines.extend(attribution);
+            lines.extend(original.lines().map(quote));
+            lines.extend([String::new(), String::new(), String::new()]);
+            lines.len() - 2
+        }
+        ReplyStyle::Interleaved => {
+            lines.extend(attribution);
+            let mut first_gap = None;
+            for paragraph in original.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                lines.extend(paragraph.lines().map(quote));
+                lines.extend([String::new(), String::new(), String::new()]);
+                first_gap.get_or_insert(lines.len() - 2);
+            }
+            first_gap.unwrap_or(lines.len())
+        }
+    };
+    ReplyDraft { body: lines.join("\n"), cursor_line }
+}
+
+/// Who a reply goes to: the Reply-To address (or the sender), and for reply-all everyone
+/// else on To and Cc as Cc. My own addresses are left out, and replying to my own message
+/// goes to its original recipients instead.
+pub fn reply_recipients(
+    from: &str,
+    reply_to: &str,
+    to: &str,
+    cc: &str,
+    all: bool,
+    me: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let is_me = |a: &str| me.iter().any(|m| m.eq_ignore_ascii_case(&address::extract_address(a)));
+    let mut recipients = Vec::new();
+    let mut copied = Vec::new();
+    if is_me(from) {
+        recipients.extend(address::split_list(to));
+        if all {
+            copied.extend(address::split_list(cc));
+        }
+    } else {
+        recipients.push(if reply_to.trim().is_empty() { from.trim().to_string() } else { reply_to.trim().to_string() });
+        if all {
+            copied.extend(address::split_list(to).into_iter().chain(address::split_list(cc)));
+        }
+    }
+    let mut seen: Vec<String> = Vec::new();
+    let mut keep = |a: &String| {
+        let key = address::extract_address(a);
+        let new = !seen.contains(&key);
+        seen.push(key);
+        new
+    };
+    recipients.retain(|a| keep(a));
+    copied.retain(|a| keep(a) && !is_me(a));
+    (recipients, copied)
+}
+
+/// In-Reply-To and References for a reply to the message with these headers, so every mail
+/// client, not only Gmail, files the reply in the right conversation.
+pub fn threading_headers(message_id: &str, references: &str) -> Vec<(String, String)> {
+    let message_id = message_id.trim();
+    if message_id.is_empty() {
+        return Vec::new();
+    }
+    let references = match references.trim() {
+        "" => message_id.to_string(),
+        earlier => format!("{} {}", earlier, message_id),
+    };
+    vec![
+        ("In-Reply-To".to_string(), message_id.to_string()),
+        ("References".to_string(), references),
+    ]
+}
+
+/// The body of a forward: a blank line to write in, then the original under a
+/// "Forwarded message" block with its main headers.
+pub fn forward_body(headers: &[(&str, String)], original: &str) -> String {
+    let mut lines = vec![String::new(), String::new(), "---------- Forwarded message ---------".to_string()];
+    for (name, value) in headers.iter().filter(|(_, value)| !value.is_empty()) {
+        lines.push(format!("{}: {}", name, value));
+    }
+    lines.push(String::new());
+    lines.push(original.trim_end().to_string());
+    lines.join("\n")
+}
+
+// Editors known to accept `+<line>` to open with the cursor on that line
+const LINE_ARG_EDITORS: [&str; 9] = ["vi", "vim", "nvim", "nano", "emacs", "micro", "kak", "joe", "mg"];
+
+/// Opens the recipients, subject, attachments and body of `prefill` in $EDITOR (falling back
+/// to vi), with the cursor on `cursor_line` of the body where the editor supports it. Returns
+/// None if the user emptied the file to cancel.
+pub fn edit_in_editor(
+    prefill: &OutgoingMessage,
+    cursor_line: Option<usize>,
+    config: &ComposeConfig,
+) -> Result<Option<OutgoingMessage>> {
+    // Owner-only and created exclusively, so the draft can't be read or redirected through a
+    // symlink by other users; removed on drop
+    let mut file = tempfile::Builder::new()
+        .prefix("gmail-cli-compose-")
+        .suffix(".md")
+        .tempfile()
+        .context("Failed to create a file to edit the message in")?;
+    let path = file.path().to_path_buf();
+    // JSON strings are valid YAML scalars, which takes care of quoting
+    let mut front_matter = format!("---\nto: {}\n", serde_json::to_string(&prefill.to.join(", "))?);
+    for (name, list) in [("cc", &prefill.cc), ("bcc", &prefill.bcc)] {
+        if !list.is_empty() {
+            front_matter.push_str(&format!("{}: {}\n", name, serde_json::to_string(&list.join(", "))?));
+        }
+    }
+    front_matter.push_str(&format!("subject: {}\n", serde_json::to_string(&prefill.subject)?));
+    // Listed so unwanted ones can be deleted before sending
+    if !prefill.attachments.is_empty() {
+        front_matter.push_str("attachments:\n");
+        for path in &prefill.attachments {
+            front_matter.push_str(&format!("  - {}\n", serde_json::to_string(&path.to_string_lossy())?));
+        }
+    }
+    if prefill.save_as_draft {
+        front_matter.push_str("# Saved back to Gmail Drafts; delete the next line to send it instead\ndraft: true\n---\n");
+    } else {
+        front_matter.push_str("# draft: true saves this to Gmail Drafts instead of sending it\n---\n");
+    }
+    let draft = format!("{}{}", front_matter, prefill.body);
+    file.write_all(draft.as_bytes())?;
+    file.flush()?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let program = editor.split_whitespace().next().unwrap_or("");
+    let program = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or("");
+    // The body starts after the front matter and editors count from 1
+    let position = match cursor_line {
+        Some(line) if LINE_ARG_EDITORS.contains(&program) => format!(" +{}", line + front_matter.lines().count() + 1),
+        _ => String::new(),
+    };
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{}{} \"$1\"", editor, position))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor));
+    let content = fs::read_to_string(&path);
+    drop(file);
+    if !status?.success() {
+        bail!("Editor exited with an error; message not sent");
+    }
+
+    let content = content?;
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    parse_message(&content, Path::new("."), config).map(Some)
+}
@@ -0,0 +1,386 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const CONFIG_FILE: &str = "config.toml";
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ComposeConfig {
+    pub auto_cc: Vec<String>,
+    pub auto_bcc: Vec<String>,
+    // Extra headers added to every outgoing message, e.g. X-Mailer or Reply-To
+    pub headers: BTreeMap<String, String>,
+    // Column for format=flowed wrapping of plain text; 0 sends lines as written
+    pub wrap_column: usize,
+    // Render the (Markdown) body to an HTML alternative part
+    pub markdown_html: bool,
+    // Where replies go relative to the quoted original
+    pub reply_style: ReplyStyle,
+    // Line above the quote; {date}, {name}, {email} and {from} are filled in, and an empty
+    // template leaves it out
+    pub attribution: String,
+    // strftime format for {date}, in local time
+    pub attribution_date_format: String,
+    // Locale for day and month names in {date}, e.g. "de_DE"; English when unset
+    pub locale: Option<String>,
+}
+
+impl Default for ComposeConfig {
+    fn default() -> Self {
+        ComposeConfig {
+            auto_cc: Vec::new(),
+            auto_bcc: Vec::new(),
+            headers: BTreeMap::new(),
+            wrap_column: 72,
+            markdown_html: false,
+            reply_style: ReplyStyle::default(),
+            attribution: "On {date}, {name} wrote:".to_string(),
+            attribution_date_format: "%a, %-d %b %Y at %H:%M".to_string(),
+            locale: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyStyle {
+    // Reply above the quote, as most webmail does
+    #[default]
+    Top,
+    // Reply below the quote
+    Bottom,
+    // A gap after each quoted paragraph to answer it in place
+    Interleaved,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    // One line per message
+    #[default]
+    Compact,
+    // The snippet is shown on a second line under the subject
+    Comfortable,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    pub density: Density,
+    // Inbox messages older than these many days get an amber / red age badge; 0 disables
+    pub age_warn_days: u32,
+    pub age_alert_days: u32,
+    // View opened on launch: inbox, unread, sent, outbox, archive, all, trash, needs-reply,
+    // unified, drafts, awaiting-me, awaiting-others, last or search:<name>
+    pub startup: String,
+    // Open messages in reader mode, toggled with `v`
+    pub reader_mode: bool,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            density: Density::default(),
+            age_warn_days: 7,
+            age_alert_days: 30,
+            startup: "inbox".to_string(),
+            reader_mode: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    // Key -> Rhai script, relative to the scripts directory unless absolute. A binding replaces
+    // the built-in action on its key in the message list, except q, which always quits.
+    pub bindings: BTreeMap<char, String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sensitivity {
+    // Only direct questions to me alone
+    Low,
+    #[default]
+    Medium,
+    // Anything addressed to me or asking a question
+    High,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct NeedsReplyConfig {
+    pub sensitivity: Sensitivity,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct TrashConfig {
+    // The daemon permanently deletes mail that has been in Trash this many days; unset disables
+    pub purge_after_days: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct RedactConfig {
+    // Regexes removed from redacted exports on top of email addresses and phone numbers, e.g.
+    // customer or ticket ids
+    pub patterns: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ServiceAccountConfig {
+    // JSON key of a Workspace service account with domain-wide delegation; defaults to
+    // $GOOGLE_APPLICATION_CREDENTIALS
+    pub key_file: Option<PathBuf>,
+    // Mailbox to act as when --impersonate isn't given
+    pub subject: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CautionConfig {
+    // Score at which a message gets the caution badge; 0 disables the badge
+    pub threshold: u32,
+    // Sender domains ending in one of these add to the score
+    pub suspicious_tlds: Vec<String>,
+    // More tracking pixels and tracked links than this add to the score
+    pub max_trackers: usize,
+}
+
+impl Default for CautionConfig {
+    fn default() -> Self {
+        CautionConfig {
+            threshold: 3,
+            suspicious_tlds: ["zip", "mov", "xyz", "top", "click", "country", "gq", "tk", "ml", "cf", "work", "rest"]
+                .map(String::from)
+                .to_vec(),
+            max_trackers: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct PathsConfig {
+    // Where tokens and state files go instead of the config directory
+    pub state_dir: Option<PathBuf>,
+    // $GMAIL_CLI_CACHE_DIR takes precedence
+    pub cache_dir: Option<PathBuf>,
+    // Where audit.log goes instead of the state directory
+    pub log_dir: Option<PathBuf>,
+    // Where `a` in the viewer saves attachments instead of the Downloads folder
+    pub download_dir: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HtmlEngine {
+    #[default]
+    Html2text,
+    // Keeps the main content and drops navigation, footers and table layout
+    Readability,
+    // `w3m -dump` and `lynx -dump`, which must be installed
+    W3m,
+    Lynx,
+    // `html.command`, given the HTML on stdin
+    Command,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct HtmlConfig {
+    // How HTML-only mail is turned into text
+    pub engine: HtmlEngine,
+    // Shell command for engine = "command", e.g. "pandoc -f html -t plain"
+    pub command: Option<String>,
+    // Engine per sender address or "@domain", e.g. `"@substack.com" = "readability"`
+    pub senders: BTreeMap<String, HtmlEngine>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct SpeechConfig {
+    // Shell command that reads text aloud from stdin; defaults to `say` on macOS and
+    // `espeak --stdin` elsewhere
+    pub command: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TranslateConfig {
+    // Shell command given the text on stdin, e.g. "trans -b :{lang}"; {lang} is target_lang
+    pub command: Option<String>,
+    // Used when no command is set; $DEEPL_AUTH_KEY works too
+    pub deepl_api_key: Option<String>,
+    // The free-tier endpoint; paid plans use https://api.deepl.com/v2/translate
+    pub deepl_url: String,
+    pub target_lang: String,
+    // Show only the translation instead of adding it under the original
+    pub replace: bool,
+}
+
+impl Default for TranslateConfig {
+    fn default() -> Self {
+        TranslateConfig {
+            command: None,
+            deepl_api_key: None,
+            deepl_url: "https://api-free.deepl.com/v2/translate".to_string(),
+            target_lang: "EN".to_string(),
+            replace: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NetworkConfig {
+    // Proxy for every request, e.g. "http://proxy.example.com:3128"; HTTPS_PROXY is
+    // honoured when unset
+    pub proxy: Option<String>,
+    // Extra PEM certificates to trust, for proxies that inspect TLS
+    pub ca_bundle: Option<PathBuf>,
+    // Base URLs, for gateways in front of Google; default to www.googleapis.com,
+    // oauth2.googleapis.com and accounts.google.com
+    pub api_url: Option<String>,
+    pub oauth_url: Option<String>,
+    pub accounts_url: Option<String>,
+    // Whole-request and connection timeouts; 0 waits forever
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    // Extra attempts after a failed request, with exponential backoff
+    pub retries: u32,
+    // Overrides by subcommand name, e.g. `[network.commands.daemon]` with `retries = 5`;
+    // the TUI is `list`
+    pub commands: BTreeMap<String, RequestPolicy>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            proxy: None,
+            ca_bundle: None,
+            api_url: None,
+            oauth_url: None,
+            accounts_url: None,
+            timeout_secs: 60,
+            connect_timeout_secs: 10,
+            retries: 2,
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// These settings with the overrides for `command` applied.
+    pub fn for_command(&self, command: Option<&str>) -> NetworkConfig {
+        let mut config = self.clone();
+        if let Some(policy) = command.and_then(|c| self.commands.get(c)) {
+            config.timeout_secs = policy.timeout_secs.unwrap_or(config.timeout_secs);
+            config.connect_timeout_secs = policy.connect_timeout_secs.unwrap_or(config.connect_timeout_secs);
+            config.retries = policy.retries.unwrap_or(config.retries);
+        }
+        config
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct RequestPolicy {
+    pub timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    pub retries: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct SecurityConfig {
+    // Keep token.json encrypted under a passphrase (or $GMAIL_CLI_PASSPHRASE)
+    pub encrypt_token: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub compose: ComposeConfig,
+    pub scripts: ScriptsConfig,
+    pub ui: UiConfig,
+    pub trash: TrashConfig,
+    pub needs_reply: NeedsReplyConfig,
+    pub security: SecurityConfig,
+    pub redact: RedactConfig,
+    pub service_account: ServiceAccountConfig,
+    pub paths: PathsConfig,
+    pub caution: CautionConfig,
+    pub network: NetworkConfig,
+    pub html: HtmlConfig,
+    pub translate: TranslateConfig,
+    pub speech: SpeechConfig,
+    // Gmail query behind the inbox, e.g. "in:inbox newer_than:90d"; blank keeps the built-in one
+    pub inbox_query: String,
+    // Saved searches by name, e.g. `work = "from:@example.com is:unread"`
+    pub searches: BTreeMap<String, String>,
+}
+
+impl Config {
+    // A missing config file is fine; a malformed one is reported rather than silently ignored
+    pub fn load() -> Result<Self> {
+        let Some(path) = crate::store::data_path(CONFIG_FILE) else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    fn modified() -> Option<SystemTime> {
+        let path = crate::store::data_path(CONFIG_FILE)?;
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    pub fn inbox_query(&self) -> &str {
+        match self.inbox_query.trim() {
+            "" => crate::google_api::DEFAULT_INBOX_QUERY,
+            query => query,
+        }
+    }
+}
+
+/// Notices edits to the config file while the TUI runs by comparing its modification time,
+/// at most once a second. One `stat` a second from the loop that already redraws is cheaper
+/// than a file-notification dependency and its thread, and works the same on every platform.
+pub struct ConfigWatcher {
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        ConfigWatcher { modified: Config::modified(), checked_at: Instant::now() }
+    }
+
+    /// The config as it is now on disk, if the file changed since the last call. An invalid
+    /// file is reported once and then ignored until it changes again.
+    pub fn poll(&mut self) -> Option<Result<Config>> {
+        if self.checked_at.elapsed() < RELOAD_CHECK_INTERVAL {
+            return None;
+        }
+        self.checked_at = Instant::now();
+        let modified = Config::modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load())
+    }
+}
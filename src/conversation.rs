@@ -0,0 +1,126 @@
+use crate::google_api::{self, ApiToken, MessageDetail};
+use crate::senders::SenderLists;
+use crate::text;
+use crate::threading::ThreadOverrides;
+use anyhow::Result;
+
+// Shown under each expanded message's sender and date
+const HEADERS: [&str; 2] = ["To", "Cc"];
+
+struct Entry {
+    // Sender and date
+    title: String,
+    snippet: String,
+    headers: String,
+    body: String,
+    expanded: bool,
+}
+
+/// A whole thread in the viewer, oldest message first. Older messages start collapsed to one
+/// line each; the open message, the newest and any unread ones are expanded.
+pub struct Conversation {
+    entries: Vec<Entry>,
+    // The message ]/[ move between and Space expands or collapses
+    cursor: usize,
+    text: String,
+    // Line each message starts on in `text`
+    starts: Vec<u16>,
+}
+
+impl Conversation {
+    /// Follows local merge and split overrides like the timeline; a message split off its
+    /// thread is a conversation of its own. Drafts are left out.
+    pub async fn load(
+        token: &ApiToken,
+        thread_id: &str,
+        open_id: &str,
+        overrides: &ThreadOverrides,
+        senders: &SenderLists,
+        reader: bool,
+    ) -> Result<Self> {
+        let mut messages: Vec<MessageDetail> = if overrides.is_split(open_id) {
+            vec![google_api::get_full_message(token, open_id).await?]
+        } else {
+            let members = overrides.members(thread_id);
+            let threads = futures::future::join_all(members.iter().map(|t| google_api::get_full_thread(token, t))).await;
+            threads
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flat_map(|t| t.messages.unwrap_or_default())
+                .filter(|m| !m.label_ids.as_ref().is_some_and(|l| l.iter().any(|l| l == "DRAFT")))
+                .filter(|m| !overrides.is_split(&m.id))
+                .collect()
+        };
+        messages.sort_by_key(|m| m.received_at().unwrap_or_default());
+
+        let last = messages.len().saturating_sub(1);
+        let entries = messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let from = m.get_header("From");
+                let body = google_api::render_body(m, &senders.render_prefs(&from), reader);
+                Entry {
+                    title: text::single_line(&format!("{} — {}", from, m.get_header("Date"))),
+                    snippet: text::single_line(&m.snippet),
+                    headers: HEADERS
+                        .iter()
+                        .map(|name| (name, m.get_header(name)))
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(name, value)| format!("{}: {}\n", name, text::single_line(&value)))
+                        .collect(),
+                    body: text::sanitize_multiline(&body),
+                    expanded: i == last || m.id == open_id || m.is_unread(),
+                }
+            })
+            .collect();
+        let cursor = messages.iter().position(|m| m.id == open_id).unwrap_or(last);
+        let mut conversation = Conversation { entries, cursor, text: String::new(), starts: Vec::new() };
+        conversation.render();
+        Ok(conversation)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The line the current message starts on, to scroll the viewer to. Long lines wrapped by
+    /// the viewer aren't counted, so this can fall a little short.
+    pub fn offset(&self) -> u16 {
+        self.starts.get(self.cursor).copied().unwrap_or_default()
+    }
+
+    pub fn move_cursor(&mut self, forward: bool) {
+        self.cursor = if forward { (self.cursor + 1).min(self.entries.len().saturating_sub(1)) } else { self.cursor.saturating_sub(1) };
+        self.render();
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(entry) = self.entries.get_mut(self.cursor) {
+            entry.expanded = !entry.expanded;
+        }
+        self.render();
+    }
+
+    fn render(&mut self) {
+        self.text.clear();
+        self.starts.clear();
+        let mut line = 0u16;
+        for (i, entry) in self.entries.iter().enumerate() {
+            self.starts.push(line);
+            let pointer = if i == self.cursor { "»" } else { " " };
+            let chunk = if entry.expanded {
+                format!("{}▾ {}\n{}\n{}\n\n", pointer, entry.title, entry.headers, entry.body.trim_end())
+            } else {
+                format!("{}▸ {} — {}\n", pointer, entry.title, entry.snippet)
+            };
+            line = line.saturating_add(chunk.matches('\n').count() as u16);
+            self.text.push_str(&chunk);
+        }
+    }
+}
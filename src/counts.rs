@@ -0,0 +1,65 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Every badge is refreshed once per cycle, one request at a time
+const REFRESH_CYCLE: Duration = Duration::from_secs(120);
+// The first round is quicker so badges appear soon after startup
+const FIRST_ROUND_GAP: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub enum CountSource {
+    Label(&'static str),
+    // A saved search; Gmail only estimates how many unread messages match
+    Search(String),
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Counts {
+    pub unread: u32,
+    pub total: Option<u32>,
+}
+
+async fn fetch(token: &ApiToken, source: &CountSource) -> Result<Counts> {
+    Ok(match source {
+        CountSource::Label(id) => {
+            let label = google_api::get_label(token, id).await?;
+            Counts { unread: label.messages_unread.unwrap_or(0), total: label.messages_total }
+        }
+        CountSource::Search(query) => {
+            let list = google_api::MessagesList::new()
+                .q(format!("{} is:unread", query))
+                .max_results(1)
+                .send(token)
+                .await?;
+            Counts { unread: list.result_size_estimate.unwrap_or(0).max(0) as u32, total: None }
+        }
+    })
+}
+
+/// Keeps sending fresh counts, keyed like `sources`, until the receiver is dropped. Requests
+/// are spread evenly over the cycle rather than sent together.
+pub fn spawn_refresher(token: ApiToken, sources: BTreeMap<String, CountSource>) -> mpsc::Receiver<(String, Counts)> {
+    let (tx, rx) = mpsc::channel(sources.len().max(1));
+    tokio::spawn(async move {
+        if sources.is_empty() {
+            return;
+        }
+        let mut gap = FIRST_ROUND_GAP;
+        loop {
+            for (key, source) in &sources {
+                // A failed refresh keeps the last known badge
+                if let Ok(counts) = fetch(&token, source).await
+                    && tx.send((key.clone(), counts)).await.is_err()
+                {
+                    return;
+                }
+                tokio::time::sleep(gap).await;
+            }
+            gap = REFRESH_CYCLE / sources.len() as u32;
+        }
+    });
+    rx
+}
@@ -0,0 +1,158 @@
+use crate::google_api::{self, ApiToken};
+use crate::notify::{self, Urgency};
+use crate::outbox;
+use crate::reminders::Deadlines;
+use crate::trash;
+use crate::watch::WatchedThreads;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+// Trash only needs checking occasionally; the purge threshold is measured in days
+const PURGE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+struct Poller {
+    inbox_query: String,
+    general_notifications: bool,
+    seen_unread: HashSet<String>,
+    seeded: bool,
+}
+
+impl Poller {
+    async fn check_inbox(&mut self, token: &ApiToken, watched: &WatchedThreads) -> Result<()> {
+        let messages = google_api::MessagesList::new()
+            .q(&self.inbox_query)
+            .label_ids(&["UNREAD"])
+            .max_results(25)
+            .send(token)
+            .await?
+            .messages
+            .unwrap_or_default();
+
+        for message in messages {
+            if !self.seen_unread.insert(message.id.clone()) || !self.seeded {
+                continue;
+            }
+            // Watched threads get their own (critical) alert below
+            if !self.general_notifications || watched.contains(&message.thread_id) {
+                continue;
+            }
+            let detail = google_api::get_message_headers(token, &message.id, &["From", "Subject"]).await?;
+            notify::send(
+                &format!("New mail from {}", detail.get_header("From")),
+                &detail.get_header("Subject"),
+                Urgency::Normal,
+            );
+        }
+        self.seeded = true;
+        Ok(())
+    }
+
+    async fn check_watched(&self, token: &ApiToken, watched: &mut WatchedThreads) -> Result<()> {
+        let mut counts = HashMap::new();
+        for (thread_id, entry) in watched.threads.iter() {
+            let thread = match google_api::get_thread(token, thread_id, &["From"]).await {
+                Ok(thread) => thread,
+                Err(e) => {
+                    eprintln!("Failed to check watched thread {}: {:?}", thread_id, e);
+                    continue;
+                }
+            };
+            let messages = thread.messages.unwrap_or_default();
+            if messages.len() > entry.message_count
+                && let Some(latest) = messages.last()
+            {
+                notify::send(
+                    &format!("Watched thread: {}", entry.subject),
+                    &format!("New message from {}", latest.get_header("From")),
+                    Urgency::Critical,
+                );
+            }
+            if messages.len() != entry.message_count {
+                counts.insert(thread_id.clone(), messages.len());
+            }
+        }
+        if !counts.is_empty() {
+            watched.record_counts(&counts).await?;
+        }
+        Ok(())
+    }
+}
+
+// Re-read each cycle like the watch list, since deadlines are set from the TUI
+fn check_deadlines() -> Result<()> {
+    let now = chrono::Local::now();
+    let mut deadlines = Deadlines::load();
+    // Most cycles have nothing to send; only then is the file changed
+    if deadlines.take_reminders(now).is_empty() {
+        return Ok(());
+    }
+    let reminders = deadlines.update(|deadlines| deadlines.take_reminders(now))?;
+    for (deadline, overdue) in reminders {
+        let (title, urgency) = if overdue {
+            ("Reply overdue", Urgency::Critical)
+        } else {
+            ("Reply due soon", Urgency::Normal)
+        };
+        notify::send(
+            &format!("{}: {}", title, deadline.subject),
+            &format!("From {}, respond by {}", deadline.from, deadline.due.format("%a %H:%M")),
+            urgency,
+        );
+    }
+    Ok(())
+}
+
+pub async fn run(
+    token: &ApiToken,
+    interval: Duration,
+    inbox_query: &str,
+    general_notifications: bool,
+    purge_after_days: Option<u32>,
+) -> Result<()> {
+    let mut poller = Poller {
+        inbox_query: inbox_query.to_string(),
+        general_notifications,
+        seen_unread: HashSet::new(),
+        seeded: false,
+    };
+    println!("Polling every {}s (Ctrl-C to stop)", interval.as_secs());
+    // Sending with a token that can't would be rejected, and every queued message marked failed
+    let can_send = token.has(google_api::Capability::Send);
+    if !can_send && !outbox::Outbox::load().entries.is_empty() {
+        println!("The outbox isn't retried: this authorization can't send mail");
+    }
+    let mut last_purge: Option<Instant> = None;
+
+    loop {
+        // Reload every cycle so threads watched from the TUI are picked up without a restart
+        let mut watched = WatchedThreads::load();
+        if let Err(e) = poller.check_inbox(token, &watched).await {
+            eprintln!("Inbox check failed: {:?}", e);
+        }
+        if let Err(e) = poller.check_watched(token, &mut watched).await {
+            eprintln!("Watched thread check failed: {:?}", e);
+        }
+        if let Err(e) = check_deadlines() {
+            eprintln!("Deadline check failed: {:?}", e);
+        }
+        if can_send {
+            match outbox::retry_pending(token).await {
+                Ok(0) => {}
+                Ok(sent) => println!("Sent {} queued message(s) from the outbox", sent),
+                Err(e) => eprintln!("Outbox retry failed: {:?}", e),
+            }
+        }
+        if let Some(days) = purge_after_days
+            && last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL)
+        {
+            last_purge = Some(Instant::now());
+            match trash::purge(token, days, false).await {
+                Ok(0) => {}
+                Ok(purged) => println!("Purged {} message(s) from Trash", purged),
+                Err(e) => eprintln!("Trash purge failed: {:?}", e),
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DateRange {
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+}
+
+// Accepts "2023", "2023-06" or "2023-06-15" (also with '/'), returning the first day of the
+// period and the first day after it
+fn parse_period_bounds(value: &str) -> Result<(NaiveDate, NaiveDate)> {
+    let normalized = value.trim().replace('/', "-");
+    let parts: Vec<&str> = normalized.split('-').collect();
+    let invalid = || format!("Invalid date '{}' (expected YYYY, YYYY-MM or YYYY-MM-DD)", value);
+    let year: i32 = parts[0].parse().with_context(invalid)?;
+
+    let (start, end) = match parts.len() {
+        1 => (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year + 1, 1, 1),
+        ),
+        2 => {
+            let month: u32 = parts[1].parse().with_context(invalid)?;
+            let start = NaiveDate::from_ymd_opt(year, month, 1);
+            let end = if month == 12 {
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)
+            };
+            (start, end)
+        }
+        3 => {
+            let month: u32 = parts[1].parse().with_context(invalid)?;
+            let day: u32 = parts[2].parse().with_context(invalid)?;
+            let start = NaiveDate::from_ymd_opt(year, month, day);
+            (start, start.and_then(|d| d.succ_opt()))
+        }
+        _ => bail!(invalid()),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) => Ok((start, end)),
+        _ => bail!(invalid()),
+    }
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    parse_period_bounds(value).map(|(start, _)| start)
+}
+
+/// "7d" or "2w" as a number of days.
+pub fn parse_days(value: &str) -> Result<u32> {
+    let value = value.trim();
+    let invalid = || format!("Invalid period '{}' (expected e.g. 7d or 2w)", value);
+    let (number, days_per_unit) = match (value.strip_suffix('d'), value.strip_suffix('w')) {
+        (Some(number), _) => (number, 1),
+        (_, Some(number)) => (number, 7),
+        _ => bail!(invalid()),
+    };
+    let number: u32 = number.parse().with_context(invalid)?;
+    number.checked_mul(days_per_unit).with_context(invalid)
+}
+
+impl DateRange {
+    /// The whole period named by `value`, e.g. all of June 2023 for "2023-06".
+    pub fn period(value: &str) -> Result<Self> {
+        let (start, end) = parse_period_bounds(value)?;
+        Ok(DateRange {
+            after: Some(start),
+            before: Some(end),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.after, self.before) {
+            (Some(after), Some(before)) => {
+                format!("{} – {}", after, before.pred_opt().unwrap_or(before))
+            }
+            (Some(after), None) => format!("from {}", after),
+            (None, Some(before)) => format!("before {}", before),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Adds Gmail's `after:` (inclusive) and `before:` (exclusive) terms to `query`, dropping
+    /// relative age terms that would otherwise hide the requested window.
+    pub fn apply(&self, query: &str) -> String {
+        if self.is_empty() {
+            return query.to_string();
+        }
+        let mut terms: Vec<String> = query
+            .split_whitespace()
+            .filter(|t| !t.starts_with("newer_than:") && !t.starts_with("older_than:"))
+            .map(str::to_string)
+            .collect();
+        if let Some(after) = self.after {
+            terms.push(format!("after:{}/{:02}/{:02}", after.year(), after.month(), after.day()));
+        }
+        if let Some(before) = self.before {
+            terms.push(format!("before:{}/{:02}/{:02}", before.year(), before.month(), before.day()));
+        }
+        terms.join(" ")
+    }
+}
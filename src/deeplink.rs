@@ -0,0 +1,109 @@
+use crate::google_api::{self, ApiToken, MessageDetail};
+use anyhow::{bail, Context, Result};
+
+/// What an id or link given to `open` points at.
+enum Target {
+    // An API id in hex, which Gmail uses for both messages and threads
+    Id(String),
+    // The Message-ID header, without its angle brackets
+    MessageId(String),
+}
+
+fn is_hex_id(value: &str) -> bool {
+    (8..=24).contains(&value.len()) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// The web UI's "msg-f:<decimal>" and "thread-f:<decimal>" ids are the API ids in decimal
+fn from_decimal(value: &str) -> Option<String> {
+    let decimal = value.strip_prefix("msg-f:").or_else(|| value.strip_prefix("thread-f:"))?;
+    decimal.parse::<u64>().ok().map(|n| format!("{:x}", n))
+}
+
+fn parse_id(value: &str) -> Result<Target> {
+    let value = value.trim();
+    if let Some(hex) = from_decimal(value) {
+        return Ok(Target::Id(hex));
+    }
+    if value.contains('@') {
+        return Ok(Target::MessageId(value.trim_start_matches('<').trim_end_matches('>').to_string()));
+    }
+    if is_hex_id(value) {
+        return Ok(Target::Id(value.to_ascii_lowercase()));
+    }
+    bail!(
+        "'{}' is not a message id. Newer Gmail links (#inbox/FMfcg...) use an encoded id that only Gmail can \
+         resolve; use \"Show original\" in Gmail and pass the Message-ID instead",
+        value
+    )
+}
+
+fn parse(input: &str) -> Result<Target> {
+    let input = input.trim();
+    if !input.starts_with("http://") && !input.starts_with("https://") {
+        return parse_id(input);
+    }
+    let url = url::Url::parse(input).with_context(|| format!("Invalid link {}", input))?;
+    // Links from notifications and the print view carry the id as a parameter
+    for (name, value) in url.query_pairs() {
+        if name == "th" || name == "permmsgid" || name == "permthid" {
+            return parse_id(&value);
+        }
+    }
+    // .../mail/u/0/#inbox/<id>, #label/Work/<id>, #search/query/<id>?compose=...
+    let fragment = url.fragment().unwrap_or_default();
+    let path = fragment.split('?').next().unwrap_or_default();
+    match path.rsplit('/').next().filter(|id| !id.is_empty() && path.contains('/')) {
+        Some(id) => parse_id(id),
+        None => bail!("The link doesn't point at a message; open the message in Gmail and copy the link again"),
+    }
+}
+
+/// Finds the message an id, Message-ID or Gmail web link refers to. A thread id opens the
+/// thread's newest message.
+pub async fn resolve(token: &ApiToken, input: &str) -> Result<MessageDetail> {
+    match parse(input)? {
+        Target::Id(id) => match google_api::get_full_message(token, &id).await {
+            Ok(detail) => Ok(detail),
+            Err(_) => {
+                let thread = google_api::get_thread(token, &id, &[])
+                    .await
+                    .with_context(|| format!("No message or thread with id {}", id))?;
+                let latest = thread.latest().context("The thread is empty")?;
+                google_api::get_full_message(token, &latest.id).await
+            }
+        },
+        Target::MessageId(message_id) => {
+            let found = google_api::MessagesList::new()
+                .q(format!("rfc822msgid:{}", message_id))
+                .include_spam_trash(true)
+                .max_results(1)
+                .send(token)
+                .await?
+                .messages
+                .unwrap_or_default();
+            let message = found.first().with_context(|| format!("No message with Message-ID <{}>", message_id))?;
+            google_api::get_full_message(token, &message.id).await
+        }
+    }
+}
+
+/// The Gmail query that lists just this message, for opening it in the TUI.
+pub fn query_for(detail: &MessageDetail) -> Result<String> {
+    let message_id = detail.get_header("Message-ID");
+    let message_id = message_id.trim().trim_start_matches('<').trim_end_matches('>');
+    if message_id.is_empty() {
+        bail!("The message has no Message-ID to open it by; use --no-tui to print it");
+    }
+    Ok(format!("rfc822msgid:{}", message_id))
+}
+
+pub fn print(detail: &MessageDetail) {
+    for name in ["From", "To", "Cc", "Date", "Subject"] {
+        let value = detail.get_header(name);
+        if !value.is_empty() {
+            println!("{}: {}", name, value);
+        }
+    }
+    println!();
+    println!("{}", google_api::decode_email_body(detail).trim_end());
+}
@@ -0,0 +1,158 @@
+use crate::address;
+use crate::google_api::{self, ApiToken, MessageDetail};
+use crate::text;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+const LIST_PAGE_SIZE: u32 = 100;
+// Header fetches in flight at once, well under Gmail's per-user rate limit
+const FETCH_CONCURRENCY: usize = 10;
+// Keeps a digest of a very busy mailbox from taking minutes; the report says when it is cut
+const MAX_MESSAGES: usize = 2000;
+const TOP_SENDERS: usize = 15;
+const NOTABLE_SUBJECTS: usize = 20;
+// Labels that say nothing about the mail itself
+const IGNORED_LABELS: [&str; 3] = ["UNREAD", "CATEGORY_PERSONAL", "CATEGORY_UPDATES"];
+
+struct Fetched {
+    messages: Vec<MessageDetail>,
+    // More matched than MAX_MESSAGES
+    truncated: bool,
+    // Listed but couldn't be fetched, so missing from every count
+    failed: usize,
+}
+
+async fn fetch(token: &ApiToken, days: u32) -> Result<Fetched> {
+    let query = format!("newer_than:{}d -in:sent -in:drafts -in:chats", days);
+    let mut messages = Vec::new();
+    let mut failed = 0;
+    let mut page_token = None;
+    loop {
+        let page = google_api::MessagesList::new()
+            .q(&query)
+            .max_results(LIST_PAGE_SIZE)
+            .page_token(page_token.as_deref())
+            .send(token)
+            .await?;
+        let ids = page.messages.unwrap_or_default();
+        let details: Vec<Result<MessageDetail>> = futures::stream::iter(&ids)
+            .map(|m| google_api::get_message_headers(token, &m.id, &["From", "Subject"]))
+            .buffered(FETCH_CONCURRENCY)
+            .collect()
+            .await;
+        for detail in details {
+            match detail {
+                Ok(detail) => messages.push(detail),
+                Err(_) => failed += 1,
+            }
+        }
+        page_token = page.next_page_token;
+        if messages.len() >= MAX_MESSAGES {
+            messages.truncate(MAX_MESSAGES);
+            return Ok(Fetched { messages, truncated: page_token.is_some(), failed });
+        }
+        if page_token.is_none() {
+            return Ok(Fetched { messages, truncated: false, failed });
+        }
+    }
+}
+
+// "CATEGORY_PROMOTIONS" -> "Promotions", "INBOX" -> "Inbox"; user labels by their name
+fn label_name(id: &str, names: &HashMap<String, String>) -> String {
+    if let Some(name) = names.get(id).filter(|_| id.starts_with("Label_")) {
+        return name.clone();
+    }
+    let id = id.strip_prefix("CATEGORY_").unwrap_or(id);
+    let mut name = id.to_ascii_lowercase();
+    if let Some(first) = name.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    name
+}
+
+fn has_label(message: &MessageDetail, label: &str) -> bool {
+    message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == label))
+}
+
+// Markdown table cells can't hold pipes or line breaks
+fn cell(value: &str) -> String {
+    text::single_line(value).replace('|', "\\|")
+}
+
+/// A Markdown summary of mail received in the last `days` days: counts per label, the
+/// busiest senders and the starred or important subjects.
+pub async fn generate(token: &ApiToken, days: u32) -> Result<String> {
+    let Fetched { messages, truncated, failed } = fetch(token, days).await?;
+    let names: HashMap<String, String> =
+        google_api::list_labels(token).await?.into_iter().map(|l| (l.id, l.name)).collect();
+
+    let now = Local::now();
+    let since = now - Duration::days(days as i64);
+    let unread = messages.iter().filter(|m| m.is_unread()).count();
+    let mut out = String::new();
+    writeln!(out, "# Mail digest: {} to {}\n", since.format("%Y-%m-%d"), now.format("%Y-%m-%d"))?;
+    writeln!(out, "{} messages received, {} still unread.", messages.len(), unread)?;
+    if truncated {
+        writeln!(out, "\nOnly the newest {} messages are included.", MAX_MESSAGES)?;
+    }
+    if failed > 0 {
+        writeln!(out, "\n{} message(s) couldn't be fetched and are left out of the counts.", failed)?;
+    }
+
+    let mut labels: HashMap<String, (usize, usize)> = HashMap::new();
+    for message in &messages {
+        for label in message.label_ids.iter().flatten().filter(|l| !IGNORED_LABELS.contains(&l.as_str())) {
+            let entry = labels.entry(label_name(label, &names)).or_default();
+            entry.0 += 1;
+            if message.is_unread() {
+                entry.1 += 1;
+            }
+        }
+    }
+    let mut labels: Vec<_> = labels.into_iter().collect();
+    labels.sort_by(|a, b| b.1.0.cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+    writeln!(out, "\n## By label\n\n| Label | Messages | Unread |\n|---|---:|---:|")?;
+    for (label, (count, unread)) in &labels {
+        writeln!(out, "| {} | {} | {} |", cell(label), count, unread)?;
+    }
+
+    let mut senders: HashMap<String, usize> = HashMap::new();
+    for message in &messages {
+        *senders.entry(address::extract_address(&message.get_header("From"))).or_default() += 1;
+    }
+    let mut senders: Vec<_> = senders.into_iter().collect();
+    senders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    writeln!(out, "\n## Top senders\n\n| Sender | Messages |\n|---|---:|")?;
+    for (sender, count) in senders.iter().take(TOP_SENDERS) {
+        writeln!(out, "| {} | {} |", cell(sender), count)?;
+    }
+
+    let notable: Vec<&MessageDetail> = messages
+        .iter()
+        .filter(|m| has_label(m, "STARRED") || has_label(m, "IMPORTANT"))
+        .take(NOTABLE_SUBJECTS)
+        .collect();
+    if !notable.is_empty() {
+        writeln!(out, "\n## Notable\n")?;
+        for message in notable {
+            let date = message
+                .received_at()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .map(|d| d.with_timezone(&Local).format("%a %b %-d").to_string())
+                .unwrap_or_default();
+            let star = if has_label(message, "STARRED") { " ★" } else { "" };
+            writeln!(
+                out,
+                "- **{}**{} from {} ({})",
+                text::single_line(&message.get_header("Subject")),
+                star,
+                text::single_line(&message.get_header("From")),
+                date
+            )?;
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,292 @@
+use crate::cache;
+use crate::config::Config;
+use crate::google_api;
+use crate::outbox::{self, Outbox};
+use crate::reminders::{self, Deadlines};
+use crate::senders::{self, SenderLists};
+use crate::secure;
+use crate::store;
+use crate::threading::{self, ThreadOverrides};
+use crate::trash::{self, TrashLog};
+use crate::watch::{self, WatchedThreads};
+use std::io::IsTerminal;
+use std::time::Duration;
+
+const MIN_COLUMNS: u16 = 80;
+const MIN_ROWS: u16 = 24;
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn line(&mut self, status: Status, name: &str, detail: &str, fix: Option<&str>) {
+        let mark = match status {
+            Status::Ok => "✓",
+            Status::Warn => "!",
+            Status::Fail => {
+                self.failures += 1;
+                "✗"
+            }
+        };
+        println!("{} {:<10} {}", mark, name, detail);
+        if let Some(fix) = fix {
+            println!("  {:<10} fix: {}", "", fix);
+        }
+    }
+}
+
+/// Runs every check and prints a fix for each problem. Returns false if any check failed.
+pub async fn run() -> bool {
+    let mut report = Report { failures: 0 };
+
+    match Config::load() {
+        Ok(config) => {
+            crate::paths::configure(&config.paths);
+            google_api::encrypt_saved_tokens(config.security.encrypt_token);
+            if let Err(e) = google_api::configure_network(&config.network.for_command(Some("doctor"))) {
+                report.line(Status::Fail, "network", &format!("{:#}", e), Some("correct the [network] section of the config"));
+            }
+            let path = store::data_path("config.toml");
+            let detail = match &path {
+                Some(p) if p.exists() => format!("{} parsed", p.display()),
+                _ => "no config file, using defaults".to_string(),
+            };
+            report.line(Status::Ok, "config", &detail, None);
+        }
+        Err(e) => report.line(
+            Status::Fail,
+            "config",
+            &format!("{:#}", e),
+            Some("correct the reported key, or move the file aside to fall back to defaults"),
+        ),
+    }
+
+    let credentials_path = google_api::credentials_path().map(|p| p.display().to_string()).unwrap_or_default();
+    match google_api::load_credentials() {
+        Ok(_) => report.line(Status::Ok, "oauth", &format!("client credentials in {}", credentials_path), None),
+        Err(_) if !google_api::credentials_path().is_some_and(|p| p.exists()) => report.line(
+            Status::Fail,
+            "oauth",
+            &format!("no client credentials at {}", credentials_path),
+            Some("create a \"Desktop app\" OAuth client in the Google Cloud console and save its JSON there"),
+        ),
+        Err(e) => report.line(
+            Status::Fail,
+            "oauth",
+            &format!("{:#}", e),
+            Some("download the client JSON from the Cloud console again"),
+        ),
+    }
+
+    check_network(&mut report).await;
+    check_token(&mut report).await;
+    check_storage(&mut report);
+    check_permissions(&mut report);
+    check_terminal(&mut report);
+
+    if report.failures == 0 {
+        println!("\nAll checks passed.");
+    } else {
+        println!("\n{} check(s) failed.", report.failures);
+    }
+    report.failures == 0
+}
+
+async fn check_network(report: &mut Report) {
+    let client = google_api::http_client();
+    let endpoints = google_api::endpoints();
+    for endpoint in [&endpoints.accounts, &endpoints.oauth, &endpoints.api] {
+        // Any HTTP response, even an error status, means the host is reachable
+        match client.head(endpoint).timeout(Duration::from_secs(5)).send().await {
+            Ok(_) => report.line(Status::Ok, "network", &format!("{} reachable", endpoint), None),
+            Err(e) => report.line(
+                Status::Fail,
+                "network",
+                &format!("{} unreachable: {}", endpoint, e),
+                Some("check your connection, DNS, and the proxy settings (network.proxy or HTTPS_PROXY)"),
+            ),
+        }
+    }
+}
+
+async fn check_token(report: &mut Report) {
+    let token_path = google_api::token_path()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "token.json".to_string());
+    let reauthorize = format!("delete {} and run `gmail-cli list` to authorize again", token_path);
+
+    let token = match google_api::saved_token().await {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            report.line(
+                Status::Warn,
+                "token",
+                "not authorized yet",
+                Some("run `gmail-cli list` to sign in"),
+            );
+            return;
+        }
+        Err(e) => {
+            report.line(Status::Fail, "token", &format!("unreadable: {:#}", e), Some(&reauthorize));
+            return;
+        }
+    };
+
+    let mut info = google_api::token_info(&token).await;
+    // Access tokens expire hourly; that is only a problem if the refresh token no longer works
+    if let Err(e) = &info
+        && !google_api::is_network_error(e)
+        && token.can_refresh()
+    {
+        match token.refresh(&token.bearer()).await {
+            Ok(()) => {
+                report.line(Status::Ok, "token", "expired access token refreshed", None);
+                info = google_api::token_info(&token).await;
+            }
+            Err(e) => {
+                report.line(Status::Fail, "token", &format!("refresh failed: {:#}", e), Some(&reauthorize));
+                return;
+            }
+        }
+    }
+    let info = match info {
+        Ok(info) => info,
+        Err(e) if google_api::is_network_error(&e) => {
+            report.line(Status::Warn, "token", "saved, but could not be verified while offline", None);
+            return;
+        }
+        Err(e) => {
+            report.line(
+                Status::Fail,
+                "token",
+                &format!("rejected by Google: {}", e),
+                Some(&reauthorize),
+            );
+            return;
+        }
+    };
+    let expires_in: u64 = info.expires_in.parse().unwrap_or(0);
+    let status = if expires_in < 300 { Status::Warn } else { Status::Ok };
+    report.line(status, "token", &format!("valid for another {} min", expires_in / 60), None);
+
+    let granted: Vec<&str> = info.scope.split_whitespace().collect();
+    let missing: Vec<&str> = google_api::requested_scopes()
+        .iter()
+        .copied()
+        .filter(|scope| !granted.contains(scope))
+        .collect();
+    if missing.is_empty() {
+        report.line(Status::Ok, "scopes", &granted.join(" "), None);
+    } else {
+        report.line(
+            Status::Fail,
+            "scopes",
+            &format!("missing {}", missing.join(" ")),
+            Some(&reauthorize),
+        );
+    }
+}
+
+fn check_storage(report: &mut Report) {
+    let (total, corrupt) = cache::check();
+    if corrupt.is_empty() {
+        report.line(Status::Ok, "cache", &format!("{} cached message(s) readable", total), None);
+    } else {
+        let list = corrupt.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ");
+        report.line(
+            Status::Warn,
+            "cache",
+            &format!("{} of {} cached message(s) unreadable", corrupt.len(), total),
+            Some(&format!("delete {}; they are fetched again when opened", list)),
+        );
+    }
+
+    let state_files = [
+        (outbox::OUTBOX_FILE, store::verify::<Outbox>(outbox::OUTBOX_FILE)),
+        (watch::WATCHED_THREADS_FILE, store::verify::<WatchedThreads>(watch::WATCHED_THREADS_FILE)),
+        (reminders::DEADLINES_FILE, store::verify::<Deadlines>(reminders::DEADLINES_FILE)),
+        (trash::TRASH_FILE, store::verify::<TrashLog>(trash::TRASH_FILE)),
+        (senders::SENDERS_FILE, store::verify::<SenderLists>(senders::SENDERS_FILE)),
+        (threading::THREAD_OVERRIDES_FILE, store::verify::<ThreadOverrides>(threading::THREAD_OVERRIDES_FILE)),
+    ];
+    for (name, result) in state_files {
+        match result {
+            Ok(()) => report.line(Status::Ok, "state", &format!("{} ok", name), None),
+            Err(e) => report.line(
+                Status::Fail,
+                "state",
+                &format!("{:#}", e),
+                Some("fix or remove the file; it is ignored (and overwritten) until then"),
+            ),
+        }
+    }
+}
+
+fn check_permissions(report: &mut Report) {
+    let exposed = secure::exposed();
+    if exposed.is_empty() {
+        report.line(Status::Ok, "secrets", "token, credentials and cache are private", None);
+        return;
+    }
+    let list = exposed.iter().take(3).map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ");
+    let more = if exposed.len() > 3 { format!(" and {} more", exposed.len() - 3) } else { String::new() };
+    report.line(
+        Status::Fail,
+        "secrets",
+        &format!("readable by other users: {}{}", list, more),
+        Some("run `gmail-cli auth secure`"),
+    );
+}
+
+fn check_terminal(report: &mut Report) {
+    if !std::io::stdout().is_terminal() {
+        report.line(
+            Status::Warn,
+            "terminal",
+            "stdout is not a terminal",
+            Some("run `gmail-cli list` from an interactive terminal"),
+        );
+        return;
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" {
+        report.line(
+            Status::Warn,
+            "terminal",
+            &format!("TERM={:?} lacks cursor control", term),
+            Some("set TERM to your terminal's type, e.g. xterm-256color"),
+        );
+    }
+
+    match crossterm::terminal::size() {
+        Ok((cols, rows)) if cols < MIN_COLUMNS || rows < MIN_ROWS => report.line(
+            Status::Warn,
+            "terminal",
+            &format!("{}x{} is small", cols, rows),
+            Some(&format!("enlarge the window to at least {}x{}", MIN_COLUMNS, MIN_ROWS)),
+        ),
+        Ok((cols, rows)) => report.line(Status::Ok, "terminal", &format!("{}x{}, TERM={}", cols, rows, term), None),
+        Err(e) => report.line(Status::Warn, "terminal", &format!("size unknown: {}", e), None),
+    }
+
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default();
+    if !locale.to_ascii_uppercase().replace('-', "").contains("UTF8") {
+        report.line(
+            Status::Warn,
+            "terminal",
+            &format!("locale {:?} may not be UTF-8", locale),
+            Some("use a UTF-8 locale (e.g. LANG=en_US.UTF-8) so status glyphs render"),
+        );
+    }
+}
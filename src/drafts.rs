@@ -0,0 +1,21 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::Result;
+
+pub async fn print_list(token: &ApiToken, limit: u32) -> Result<()> {
+    let drafts = google_api::list_drafts(token, "", limit).await?;
+    if drafts.is_empty() {
+        println!("No drafts");
+    }
+    for draft in drafts {
+        let detail = google_api::get_draft(token, &draft.id).await?;
+        let to = detail.message.get_header("To");
+        let subject = detail.message.get_header("Subject");
+        println!(
+            "{}  {}  {}",
+            detail.id,
+            if to.is_empty() { "(no recipients)" } else { to.as_str() },
+            if subject.is_empty() { "(no subject)" } else { subject.as_str() }
+        );
+    }
+    Ok(())
+}
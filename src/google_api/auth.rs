@@ -0,0 +1,751 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use oauth2::{
+    basic::{BasicClient, BasicTokenType},
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, EmptyExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, RequestTokenError, Scope, StandardTokenResponse, TokenUrl,
+    TokenResponse,
+};
+use super::service_account;
+use crate::vault;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+const TOKEN_FILE: &str = "token.json";
+const CREDENTIALS_FILE: &str = "credentials.json";
+const READONLY_SCOPE: &str = "https://www.googleapis.com/auth/gmail.readonly";
+const MODIFY_SCOPE: &str = "https://www.googleapis.com/auth/gmail.modify";
+const SEND_SCOPE: &str = "https://www.googleapis.com/auth/gmail.send";
+// A service account can't ask for consent, so it asks for what domain-wide delegation
+// was always documented to grant
+const DELEGATED_SCOPES: [&str; 2] = [READONLY_SCOPE, MODIFY_SCOPE];
+// Only permanent deletion needs this
+pub const FULL_ACCESS_SCOPE: &str = "https://mail.google.com/";
+// Forwarding settings are only covered by this scope, not even by full access
+const SETTINGS_SHARING_SCOPE: &str = "https://www.googleapis.com/auth/gmail.settings.sharing";
+// How long before expiry the background refresher renews the access token
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+// Retry delay after a failed background refresh, and the recheck interval with no expiry known
+const REFRESH_RETRY: Duration = Duration::from_secs(60);
+
+/// What an action needs from the authorization. Sign-in starts with `Read`; the others are
+/// requested through incremental consent the first time they are needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capability {
+    Read,
+    Modify,
+    Send,
+    Delete,
+    Forwarding,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] =
+        [Capability::Read, Capability::Modify, Capability::Send, Capability::Delete, Capability::Forwarding];
+
+    fn scope(self) -> &'static str {
+        match self {
+            Capability::Read => READONLY_SCOPE,
+            Capability::Modify => MODIFY_SCOPE,
+            Capability::Send => SEND_SCOPE,
+            Capability::Delete => FULL_ACCESS_SCOPE,
+            Capability::Forwarding => SETTINGS_SHARING_SCOPE,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Capability::Read => "reading mail",
+            Capability::Modify => "changing mail (labels, read state, trash)",
+            Capability::Send => "sending mail",
+            Capability::Delete => "deleting mail permanently",
+            Capability::Forwarding => "managing forwarding addresses",
+        }
+    }
+}
+
+// The access token in use and when it expires
+#[derive(Clone, Debug)]
+struct Current {
+    access_token: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    // What Google granted; empty for tokens saved before this was recorded, which always had
+    // the default scopes
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // When `access_token` was issued and for how many seconds; unknown for older token files
+    #[serde(default)]
+    pub obtained_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    // Replaces `access_token` once refreshed. Every clone shares the channel, so API workers
+    // holding a copy use the new token from their next request on.
+    #[serde(skip)]
+    current: watch::Sender<Option<Current>>,
+    #[serde(skip)]
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    // Set when Google keeps refusing the token even after a refresh; shared like `current`
+    #[serde(skip)]
+    rejected: Arc<AtomicBool>,
+    // The file this was read from, so a refresh is saved there even when another account
+    // has been selected since
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ApiToken {
+    fn new(access_token: String, refresh_token: Option<String>, scopes: Vec<String>, expires_in: Option<u64>) -> Self {
+        ApiToken {
+            access_token,
+            refresh_token,
+            scopes,
+            obtained_at: Some(Utc::now()),
+            expires_in,
+            current: Default::default(),
+            refresh_lock: Default::default(),
+            rejected: Default::default(),
+            path: None,
+        }
+    }
+
+    /// Whether the grant covers `capability`. Broader scopes imply narrower ones: modify
+    /// includes sending, full access includes everything.
+    pub fn has(&self, capability: Capability) -> bool {
+        // Tokens saved before scopes were recorded were granted readonly and modify
+        if self.scopes.is_empty() {
+            return !matches!(capability, Capability::Delete | Capability::Forwarding);
+        }
+        let granted = |scope: &str| self.scopes.iter().any(|s| s == scope || s == FULL_ACCESS_SCOPE);
+        match capability {
+            Capability::Read => true,
+            Capability::Modify => granted(MODIFY_SCOPE),
+            Capability::Send => granted(SEND_SCOPE) || granted(MODIFY_SCOPE),
+            Capability::Delete => granted(FULL_ACCESS_SCOPE),
+            Capability::Forwarding => self.scopes.iter().any(|s| s == SETTINGS_SHARING_SCOPE),
+        }
+    }
+
+    /// Whether a new access token can be had without the user, from the refresh token or by
+    /// signing a new service account assertion.
+    pub fn can_refresh(&self) -> bool {
+        self.refresh_token.is_some() || service_account::service_account().is_some()
+    }
+
+    /// The access token to send, which may be newer than the one this was loaded with.
+    pub fn bearer(&self) -> String {
+        match &*self.current.borrow() {
+            Some(current) => current.access_token.clone(),
+            None => self.access_token.clone(),
+        }
+    }
+
+    /// Whether the authorization has been revoked or expired for good, so only signing in
+    /// again helps.
+    pub fn is_rejected(&self) -> bool {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn mark_rejected(&self) {
+        self.rejected.store(true, Ordering::Relaxed);
+    }
+
+    /// When the access token in use stops working, if known.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        match &*self.current.borrow() {
+            Some(current) => current.expires_at,
+            None => Some(self.obtained_at? + chrono::Duration::seconds(self.expires_in? as i64)),
+        }
+    }
+
+    /// Exchanges the refresh token for a new access token and saves it. `stale` is the token
+    /// that was rejected: when several requests fail together only the first one refreshes.
+    /// Fails with [`super::AuthRejected`] when Google refuses, as opposed to being unreachable.
+    pub async fn refresh(&self, stale: &str) -> Result<()> {
+        let _guard = self.refresh_lock.lock().await;
+        if self.bearer() != stale {
+            return Ok(());
+        }
+        if let Some(account) = service_account::service_account() {
+            let response = match service_account::request_token(account).await {
+                Ok(response) => response,
+                Err(e) if super::is_network_error(&e) => return Err(e),
+                Err(e) => return Err(super::rejected(self, format!("{:#}", e))),
+            };
+            let expires_at = response.expires_in.map(|secs| Utc::now() + chrono::Duration::seconds(secs as i64));
+            self.current.send_replace(Some(Current { access_token: response.access_token, expires_at }));
+            self.rejected.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+        let Some(refresh_token) = self.refresh_token.clone() else {
+            return Err(super::rejected(self, "the access token expired and there is no refresh token"));
+        };
+        let response = match oauth_client("http://localhost".to_string())?
+            .exchange_refresh_token(&RefreshToken::new(refresh_token.clone()))
+            .request_async(super::network::oauth_http_client)
+            .await
+        {
+            Ok(response) => response,
+            // Typically invalid_grant: revoked, expired, or the password was changed
+            Err(RequestTokenError::ServerResponse(response)) => {
+                return Err(super::rejected(self, response.error().to_string()));
+            }
+            Err(e) => return Err(anyhow::Error::new(e).context("Failed to refresh the access token")),
+        };
+        let access_token = response.access_token().secret().clone();
+        // Google usually doesn't rotate the refresh token; keep the old one when it doesn't
+        let refresh_token = response.refresh_token().map(|t| t.secret().clone()).or(Some(refresh_token));
+        let renewed = ApiToken {
+            path: self.path.clone(),
+            ..ApiToken::new(
+                access_token.clone(),
+                refresh_token,
+                self.scopes.clone(),
+                response.expires_in().map(|d| d.as_secs()),
+            )
+        };
+        save_token_to_file(&renewed).await?;
+        self.current.send_replace(Some(Current { access_token, expires_at: renewed.expires_at() }));
+        self.rejected.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// The OAuth client this app authorizes as, from the user's own Google Cloud project.
+#[derive(Deserialize, Debug)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CredentialsFile {
+    // The client_secret_*.json downloaded from the Cloud console, for a Desktop or Web client
+    Installed { installed: ClientCredentials },
+    Web { web: ClientCredentials },
+    Plain(ClientCredentials),
+}
+
+/// Shared by all accounts.
+pub fn credentials_path() -> Option<std::path::PathBuf> {
+    crate::store::data_path(CREDENTIALS_FILE)
+}
+
+pub fn load_credentials() -> Result<ClientCredentials> {
+    let path = credentials_path().context("No config directory to read OAuth credentials from")?;
+    if !path.exists() {
+        bail!(
+            "No OAuth client credentials found at {}.\n\
+             To set them up:\n  \
+             1. In the Google Cloud console, enable the Gmail API for a project.\n  \
+             2. Under APIs & Services > Credentials, create an OAuth client ID of type \"Desktop app\".\n  \
+             3. Download its JSON and save it as {}.\n\
+             A file of the form {{\"client_id\": \"...\", \"client_secret\": \"...\"}} works too.",
+            path.display(),
+            path.display()
+        );
+    }
+    let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: CredentialsFile = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a recognised OAuth client file", path.display()))?;
+    Ok(match file {
+        CredentialsFile::Installed { installed } => installed,
+        CredentialsFile::Web { web } => web,
+        CredentialsFile::Plain(credentials) => credentials,
+    })
+}
+
+// Google reports these as strings
+#[derive(Deserialize, Debug)]
+pub struct TokenInfo {
+    pub expires_in: String,
+    pub scope: String,
+}
+
+pub async fn get_auth_token() -> Result<ApiToken> {
+    let token = saved_token().await?;
+    match token {
+        Some(token) => Ok(token),
+        None => get_new_token_from_auth_code().await,
+    }
+}
+
+/// Always asks for a new authorization, on the terminal, accepting a pasted code as well as the
+/// redirect.
+pub async fn sign_in_headless() -> Result<ApiToken> {
+    paste_or_redirect().await
+}
+
+/// The token on file, or with `--auth service-account` a newly signed delegated one.
+pub async fn saved_token() -> Result<Option<ApiToken>> {
+    if let Some(account) = service_account::service_account() {
+        let response = service_account::request_token(account).await?;
+        let scopes = requested_scopes().iter().map(|s| s.to_string()).collect();
+        return Ok(Some(ApiToken::new(response.access_token, None, scopes, response.expires_in)));
+    }
+    read_token_from_file(token_path(), true).await
+}
+
+/// The saved token of `account` without selecting it, e.g. for the unified inbox. Never asks for
+/// a passphrase: a sealed token is an error unless the passphrase is in the environment, and a
+/// plaintext one is left to be sealed when the account is next opened.
+pub async fn saved_token_for(account: Option<&str>) -> Result<Option<ApiToken>> {
+    if service_account::service_account().is_some() {
+        bail!("A service account signs in as a single user; use OAuth sign-ins per account");
+    }
+    let path = token_path_for(account);
+    if std::env::var(vault::PASSPHRASE_ENV).is_err()
+        && path.as_ref().and_then(|path| fs::read_to_string(path).ok()).is_some_and(|content| vault::is_sealed(&content))
+    {
+        bail!("The saved token is sealed; open the account to unlock it");
+    }
+    read_token_from_file(path, false).await
+}
+
+/// Whether loading `account`'s saved token would ask for a passphrase on the terminal, which a
+/// full-screen interface has to step aside for first.
+pub fn token_prompts(account: Option<&str>) -> bool {
+    if service_account::service_account().is_some() || std::env::var(vault::PASSPHRASE_ENV).is_ok() {
+        return false;
+    }
+    let Some(content) = token_path_for(account).and_then(|path| fs::read_to_string(path).ok()) else {
+        return false;
+    };
+    vault::is_sealed(&content) || encryption_enabled()
+}
+
+type GoogleTokenResponse = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Makes the session refuse any capability beyond reading (`--read-only`).
+pub fn request_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+pub fn read_only_requested() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+static NO_BROWSER: AtomicBool = AtomicBool::new(false);
+
+/// Stops authorization from launching a browser (`--no-browser`); the URL is still printed.
+pub fn disable_browser() {
+    NO_BROWSER.store(true, Ordering::Relaxed);
+}
+
+/// Opens the authorization page in the default browser when there is one to open. Returns
+/// whether it was launched.
+pub fn open_in_browser(url: &str) -> bool {
+    if NO_BROWSER.load(Ordering::Relaxed) || is_headless() {
+        return false;
+    }
+    webbrowser::open(url).is_ok()
+}
+
+// Added to the next authorization by `request_capability`
+static EXTRA_SCOPES: std::sync::Mutex<Vec<&'static str>> = std::sync::Mutex::new(Vec::new());
+
+/// Makes the next sign-in ask for `capability` on top of what was granted before. Fails in
+/// read-only sessions.
+pub fn request_capability(capability: Capability) -> Result<()> {
+    if read_only_requested() && capability != Capability::Read {
+        bail!("Read-only mode: {} is disabled", capability.describe());
+    }
+    if let Ok(mut extra) = EXTRA_SCOPES.lock()
+        && !extra.contains(&capability.scope())
+    {
+        extra.push(capability.scope());
+    }
+    Ok(())
+}
+
+/// Scopes the next authorization asks for: readonly plus whatever was requested since.
+/// Google adds the scopes granted earlier (`include_granted_scopes`).
+pub fn requested_scopes() -> Vec<&'static str> {
+    if service_account::service_account().is_some() {
+        if read_only_requested() {
+            return vec![READONLY_SCOPE];
+        }
+        // Capabilities requested before the first token, since delegation can't be extended later
+        let mut scopes = DELEGATED_SCOPES.to_vec();
+        if let Ok(extra) = EXTRA_SCOPES.lock() {
+            scopes.extend(extra.iter().copied().filter(|scope| !DELEGATED_SCOPES.contains(scope)));
+        }
+        return scopes;
+    }
+    let mut scopes = vec![READONLY_SCOPE];
+    if let Ok(extra) = EXTRA_SCOPES.lock() {
+        scopes.extend(extra.iter().copied());
+    }
+    scopes
+}
+
+fn api_token_from_response(token_response: &GoogleTokenResponse) -> ApiToken {
+    let scopes = match token_response.scopes() {
+        Some(granted) => granted.iter().map(|s| s.to_string()).collect(),
+        None => requested_scopes().iter().map(|s| s.to_string()).collect(),
+    };
+    ApiToken::new(
+        token_response.access_token().secret().clone(),
+        token_response.refresh_token().map(|t| t.secret().clone()),
+        scopes,
+        token_response.expires_in().map(|d| d.as_secs()),
+    )
+}
+
+fn oauth_client(redirect_uri: String) -> Result<BasicClient> {
+    let credentials = load_credentials()?;
+    let client_id = ClientId::new(credentials.client_id);
+    let client_secret = credentials.client_secret.map(ClientSecret::new);
+    let auth_url = AuthUrl::new(super::endpoints().authorize())?;
+    let token_url = TokenUrl::new(super::endpoints().token())?;
+
+    Ok(BasicClient::new(client_id, client_secret, auth_url, Some(token_url))
+        .set_redirect_uri(RedirectUrl::new(redirect_uri)?))
+}
+
+fn authorize_url(client: &BasicClient) -> (url::Url, CsrfToken, PkceCodeVerifier) {
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (auth_url, csrf_token) = client
+        .authorize_url(CsrfToken::new_random)
+        .add_scopes(requested_scopes().iter().map(|s| Scope::new(s.to_string())))
+        .add_extra_param("include_granted_scopes", "true")
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+    (auth_url, csrf_token, pkce_verifier)
+}
+
+// The redirect lands on a loopback listener, so the code is captured without copying it out of
+// the browser. Nothing is read from the terminal here: a read left waiting after the redirect
+// would take keystrokes meant for the mail view.
+async fn get_new_token_from_auth_code() -> Result<ApiToken> {
+    let auth = LoopbackAuth::start().await?;
+
+    if open_in_browser(auth.auth_url.as_str()) {
+        println!("Opened the authorization page in your browser. If it didn't appear, use this URL:");
+        println!("{}", auth.auth_url);
+    } else {
+        println!("Open this URL in your browser to authorize this app: {}", auth.auth_url);
+    }
+    if is_headless() {
+        println!("From a browser on another machine, forward the redirect port first:");
+        println!("  {}", auth.forward_command());
+    }
+    println!("Waiting for the browser to finish. Without a browser on this machine, run");
+    println!("`gmail-cli auth login --headless` to paste the code instead.");
+    auth.finish().await
+}
+
+// For `auth login --headless`: the redirect may never reach this machine, so the code or the
+// URL the browser was left on can be pasted too. The process exits once signed in, so the
+// stdin read that loses the race is simply left behind on its thread.
+async fn paste_or_redirect() -> Result<ApiToken> {
+    let auth = LoopbackAuth::start().await?;
+
+    match qr_code(auth.auth_url.as_str()) {
+        Ok(qr) => println!("Scan this code to authorize from another device:\n{}", qr),
+        Err(e) => eprintln!("Could not render a QR code: {}", e),
+    }
+    println!("Open this URL in your browser to authorize this app: {}", auth.auth_url);
+    println!("With the redirect port forwarded from the machine the browser runs on, sign-in");
+    println!("finishes by itself:");
+    println!("  {}", auth.forward_command());
+    println!("Otherwise paste the authorization code (or the whole redirected URL) below:");
+
+    let (line_tx, line_rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let _ = line_tx.send(std::io::stdin().read_line(&mut line).map(|_| line));
+    });
+    let code = tokio::select! {
+        code = auth.wait_for_code() => code?,
+        line = line_rx => {
+            let input = line.context("Failed to read authorization code")??;
+            auth.check_pasted_state(&input)?;
+            code_from_input(&input)
+        }
+    };
+    auth.exchange(code).await
+}
+
+// The redirect to localhost fails on a phone, so accept the URL it was left on as well
+fn code_from_input(input: &str) -> String {
+    let input = input.trim();
+    url::Url::parse(input)
+        .ok()
+        .and_then(|u| u.query_pairs().find(|(k, _)| k == "code").map(|(_, v)| v.into_owned()))
+        .unwrap_or_else(|| input.to_string())
+}
+
+/// True when there is no local browser to follow a link or receive a loopback redirect, such
+/// as over SSH or on a Linux box without a display server.
+pub fn is_headless() -> bool {
+    let has_display = std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some();
+    std::env::var_os("SSH_CONNECTION").is_some() || (cfg!(target_os = "linux") && !has_display)
+}
+
+// Half-block rendering packs two module rows per line; light modules are drawn as blocks so
+// the code reads correctly on the usual dark terminal background
+fn qr_code(data: &str) -> Result<String> {
+    let code = qrcode::QrCode::with_error_correction_level(data, qrcode::EcLevel::L)?;
+    Ok(code
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// A pending authorization whose redirect lands on a listener bound to a free loopback port,
+/// so the code is captured without the user copying it out of the browser.
+pub struct LoopbackAuth {
+    client: BasicClient,
+    listener: TcpListener,
+    csrf_token: CsrfToken,
+    pkce_verifier: PkceCodeVerifier,
+    pub auth_url: url::Url,
+}
+
+impl LoopbackAuth {
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind a local port for the OAuth redirect")?;
+        let port = listener.local_addr()?.port();
+        let client = oauth_client(format!("http://127.0.0.1:{}", port))?;
+        let (auth_url, csrf_token, pkce_verifier) = authorize_url(&client);
+        Ok(Self { client, listener, csrf_token, pkce_verifier, auth_url })
+    }
+
+    /// An `ssh` command that carries the redirect from a browser on the user's own machine back
+    /// to this listener, for signing in on a remote host.
+    pub fn forward_command(&self) -> String {
+        let port = self.listener.local_addr().map(|a| a.port()).unwrap_or_default();
+        // SSH_CONNECTION is "client_ip client_port server_ip server_port"
+        let host = std::env::var("SSH_CONNECTION")
+            .ok()
+            .and_then(|c| c.split_whitespace().nth(2).map(str::to_string))
+            .unwrap_or_else(|| "<this host>".to_string());
+        format!("ssh -N -L {port}:127.0.0.1:{port} {host}")
+    }
+
+    /// Waits for the browser redirect, exchanges the code and saves the resulting token.
+    pub async fn finish(self) -> Result<ApiToken> {
+        let code = self.wait_for_code().await?;
+        self.exchange(code).await
+    }
+
+    // Pasted input is just the code unless it's the full redirected URL, which carries a state
+    fn check_pasted_state(&self, input: &str) -> Result<()> {
+        let state = url::Url::parse(input.trim())
+            .ok()
+            .and_then(|u| u.query_pairs().find(|(k, _)| k == "state").map(|(_, v)| v.into_owned()));
+        if state.is_some_and(|state| state != *self.csrf_token.secret()) {
+            bail!("OAuth state mismatch; the pasted URL did not come from this login attempt");
+        }
+        Ok(())
+    }
+
+    async fn wait_for_code(&self) -> Result<String> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            // Anything else on the port may connect too; only the redirect ends the wait
+            let Some(redirect) = read_request_url(&mut stream).await else {
+                respond(&mut stream, "400 Bad Request", "").await;
+                continue;
+            };
+            let param = |name: &str| {
+                redirect.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned())
+            };
+
+            // Browsers also ask for /favicon.ico; ignore anything that isn't the redirect
+            if param("code").is_none() && param("error").is_none() {
+                respond(&mut stream, "404 Not Found", "").await;
+                continue;
+            }
+            if param("state").as_deref() != Some(self.csrf_token.secret().as_str()) {
+                respond(&mut stream, "400 Bad Request", "Authorization state mismatch.").await;
+                continue;
+            }
+            if let Some(error) = param("error") {
+                respond(&mut stream, "200 OK", "Authorization was denied. You can close this tab.").await;
+                bail!("Authorization failed: {}", error);
+            }
+            respond(&mut stream, "200 OK", "Authorization complete. You can close this tab.").await;
+            return Ok(param("code").unwrap_or_default());
+        }
+    }
+
+    async fn exchange(self, code: String) -> Result<ApiToken> {
+        let token_response = self
+            .client
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(self.pkce_verifier)
+            .request_async(super::network::oauth_http_client)
+            .await?;
+        let api_token = api_token_from_response(&token_response);
+        save_token_to_file(&api_token).await?;
+        Ok(api_token)
+    }
+}
+
+// None when the request can't be read or has no parsable target
+async fn read_request_url(stream: &mut TcpStream) -> Option<url::Url> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    // Only the request line matters; stop at the end of the headers
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 16 * 1024 {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1)?;
+    url::Url::parse(&format!("http://127.0.0.1{}", path)).ok()
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = format!("<!DOCTYPE html><html><body><p>{}</p></body></html>", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // The browser may already have gone away; the code is what matters
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+pub async fn token_info(token: &ApiToken) -> Result<TokenInfo> {
+    let res = super::http_client()
+        .get(format!("{}/tokeninfo", super::endpoints().oauth))
+        .query(&[("access_token", token.bearer())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<TokenInfo>()
+        .await?;
+    Ok(res)
+}
+
+/// Invalidates the grant at Google. Revoking the refresh token also revokes every access
+/// token issued from it.
+pub async fn revoke_token(token: &ApiToken) -> Result<()> {
+    let secret = token.refresh_token.clone().unwrap_or_else(|| token.bearer());
+    let url = format!("{}/revoke", super::endpoints().oauth);
+    let res = super::http_client().post(url).form(&[("token", secret)]).send().await?;
+    if !res.status().is_success() {
+        bail!("Google refused to revoke the token ({}): {}", res.status(), res.text().await.unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// Deletes the saved token. Returns false when there was none.
+pub fn forget_token() -> Result<bool> {
+    match token_path() {
+        Some(path) if path.exists() => {
+            fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Renews the access token a few minutes before it expires, for as long as the session runs.
+/// A refresh triggered elsewhere (by a 401) arrives on the channel and reschedules this one.
+pub fn spawn_token_refresher(token: ApiToken) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        if !token.can_refresh() {
+            return;
+        }
+        let mut changes = token.current.subscribe();
+        loop {
+            let wait = match token.expires_at() {
+                Some(expires_at) => (expires_at - Utc::now()).to_std().unwrap_or_default().saturating_sub(REFRESH_MARGIN),
+                // Older token files don't record it; the next refresh does
+                None => REFRESH_RETRY,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {
+                    if token.refresh(&token.bearer()).await.is_err() {
+                        // Retrying can't fix a refused grant; the session has to sign in again
+                        if token.is_rejected() {
+                            return;
+                        }
+                        tokio::time::sleep(REFRESH_RETRY).await;
+                    }
+                }
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Per account when `--account` is given.
+pub fn token_path() -> Option<std::path::PathBuf> {
+    crate::store::state_path(TOKEN_FILE)
+}
+
+pub fn token_path_for(account: Option<&str>) -> Option<std::path::PathBuf> {
+    crate::store::account_state_path(account, TOKEN_FILE)
+}
+
+static ENCRYPT_TOKEN: AtomicBool = AtomicBool::new(false);
+
+/// Seals saved tokens under a passphrase (`security.encrypt_token`), set once the config is read.
+pub fn encrypt_saved_tokens(enabled: bool) {
+    ENCRYPT_TOKEN.store(enabled, Ordering::Relaxed);
+}
+
+fn encryption_enabled() -> bool {
+    ENCRYPT_TOKEN.load(Ordering::Relaxed)
+}
+
+// `seal_plaintext` is false where no terminal may be used to ask for a new passphrase
+async fn read_token_from_file(path: Option<PathBuf>, seal_plaintext: bool) -> Result<Option<ApiToken>> {
+    if let Some(path) = path
+        && path.exists()
+    {
+        let content = fs::read_to_string(&path)?;
+        if vault::is_sealed(&content) {
+            let token: ApiToken = serde_json::from_slice(&vault::open(&path, &content)?)?;
+            return Ok(Some(ApiToken { path: Some(path), ..token }));
+        }
+        let token = ApiToken { path: Some(path), ..serde_json::from_str(&content)? };
+        // Encrypt a plaintext token as soon as encryption is turned on, while there is still a
+        // terminal to ask for the passphrase
+        if seal_plaintext && encryption_enabled() {
+            save_token_to_file(&token).await?;
+        }
+        return Ok(Some(token));
+    }
+    Ok(None)
+}
+
+async fn save_token_to_file(token: &ApiToken) -> Result<()> {
+    if let Some(path) = token.path.clone().or_else(token_path) {
+        let content = serde_json::to_string_pretty(token)?;
+        let content = if encryption_enabled() { vault::seal(&path, content.as_bytes())? } else { content };
+        crate::secure::write_private(&path, content)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use super::{MessageDetail, MessagePayload};
+use crate::html;
+use crate::mailcap;
+use crate::senders::RenderPrefs;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+pub fn decode_part_data(payload: &MessagePayload) -> Option<Vec<u8>> {
+    let data = payload.body.as_ref().and_then(|b| b.data.as_ref())?;
+    URL_SAFE_NO_PAD.decode(data).ok()
+}
+
+fn find_body_parts(payload: &MessagePayload) -> (Option<String>, Option<String>) {
+    let mut plain_text = None;
+    let mut html_text = None;
+
+    if payload.mime_type == "text/plain" {
+        plain_text = decode_part_data(payload).and_then(|bytes| String::from_utf8(bytes).ok());
+    } else if payload.mime_type == "text/html" {
+        html_text = decode_part_data(payload).and_then(|bytes| String::from_utf8(bytes).ok());
+    }
+
+    if let Some(parts) = &payload.parts {
+        for part in parts {
+            let (part_plain, part_html) = find_body_parts(part);
+            if plain_text.is_none() {
+                plain_text = part_plain;
+            }
+            if html_text.is_none() {
+                html_text = part_html;
+            }
+        }
+    }
+
+    (plain_text, html_text)
+}
+
+/// Every part carrying a filename, depth first.
+pub fn attachment_parts(payload: &MessagePayload) -> Vec<&MessagePayload> {
+    let mut found = Vec::new();
+    if !payload.filename.is_empty() {
+        found.push(payload);
+    }
+    for part in payload.parts.iter().flatten() {
+        found.extend(attachment_parts(part));
+    }
+    found
+}
+
+fn find_part<'a>(payload: &'a MessagePayload, mime_type: &str) -> Option<&'a MessagePayload> {
+    if payload.mime_type.eq_ignore_ascii_case(mime_type) {
+        return Some(payload);
+    }
+    payload
+        .parts
+        .iter()
+        .flatten()
+        .find_map(|part| find_part(part, mime_type))
+}
+
+pub fn find_part_text(detail: &MessageDetail, mime_type: &str) -> Option<String> {
+    let part = find_part(detail.payload.as_ref()?, mime_type)?;
+    decode_part_data(part).map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// Non-text parts (PDFs, calendar invites, ...) rendered through a mailcap `copiousoutput` viewer
+fn render_with_mailcap(payload: &MessagePayload, entries: &[mailcap::MailcapEntry]) -> Option<String> {
+    if !payload.mime_type.starts_with("multipart/")
+        && let Some(entry) = mailcap::find_entry(entries, &payload.mime_type, true)
+        && let Some(data) = decode_part_data(payload)
+        && let Ok(output) = entry.render(&data)
+    {
+        return Some(output);
+    }
+
+    payload
+        .parts
+        .iter()
+        .flatten()
+        .find_map(|part| render_with_mailcap(part, entries))
+}
+
+/// The body as the viewer shows it, following the sender's preferences. `reader` turns reader
+/// mode on even when the sender's preferences don't.
+pub fn render_body(detail: &MessageDetail, prefs: &RenderPrefs, reader: bool) -> String {
+    let Some(payload) = &detail.payload else {
+        return detail.snippet.clone();
+    };
+    let (plain, html) = find_body_parts(payload);
+    let width = prefs.width.unwrap_or(html::DEFAULT_WIDTH);
+    let text = match (&plain, &html) {
+        _ if reader || prefs.reader => html::reader(html.as_deref(), plain.as_deref(), width),
+        (_, Some(html_text)) if prefs.prefer_html || plain.is_none() => {
+            Some(html::to_text(html_text, &detail.get_header("From"), width))
+        }
+        _ => plain.clone(),
+    };
+    let mut text = text.unwrap_or_else(|| decode_email_body(detail));
+    let images = html.as_deref().map(html::remote_images).unwrap_or_default();
+    if prefs.remote_images && !images.is_empty() {
+        text.truncate(text.trim_end().len());
+        text.push_str("\n\nImages:\n");
+        for (n, url) in images.iter().enumerate() {
+            text.push_str(&format!("  [{}] {}\n", n + 1, url));
+        }
+    }
+    text
+}
+
+pub fn decode_email_body(detail: &MessageDetail) -> String {
+    if let Some(payload) = &detail.payload {
+        let (plain, html) = find_body_parts(payload);
+
+        if let Some(plain_text) = plain {
+            return plain_text;
+        }
+
+        if let Some(html_text) = html {
+            return html::to_text(&html_text, &detail.get_header("From"), html::DEFAULT_WIDTH);
+        }
+
+        let entries = mailcap::load_entries();
+        if let Some(rendered) = render_with_mailcap(payload, &entries) {
+            return rendered;
+        }
+    }
+
+    detail.snippet.clone()
+}
@@ -0,0 +1,87 @@
+use super::{delete, get_json, post_json, put_json, ApiToken, Message, MessageDetail};
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+pub struct Draft {
+    pub id: String,
+    pub message: Message,
+}
+
+/// users.drafts.get with the full message.
+#[derive(Deserialize, Debug)]
+pub struct DraftDetail {
+    pub id: String,
+    pub message: MessageDetail,
+}
+
+#[derive(Deserialize)]
+struct DraftList {
+    #[serde(default)]
+    drafts: Vec<Draft>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DraftMessage {
+    raw: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DraftRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    message: DraftMessage,
+}
+
+fn draft_request(id: Option<&str>, rfc2822: &str, thread_id: Option<&str>) -> DraftRequest {
+    DraftRequest {
+        id: id.map(str::to_string),
+        message: DraftMessage {
+            raw: URL_SAFE_NO_PAD.encode(rfc2822),
+            thread_id: thread_id.map(str::to_string),
+        },
+    }
+}
+
+pub async fn create_draft(token: &ApiToken, rfc2822: &str, thread_id: Option<&str>) -> Result<Draft> {
+    let result: Result<Draft> = post_json(token, "drafts", &draft_request(None, rfc2822, thread_id)).await;
+    crate::audit::record("draft", result.as_ref().ok().map(|d| d.id.as_str()), "", &result);
+    result
+}
+
+/// Replaces the draft's whole message.
+pub async fn update_draft(token: &ApiToken, draft_id: &str, rfc2822: &str, thread_id: Option<&str>) -> Result<Draft> {
+    let result = put_json(token, &format!("drafts/{}", draft_id), &draft_request(Some(draft_id), rfc2822, thread_id)).await;
+    crate::audit::record("draft", Some(draft_id), "update", &result);
+    result
+}
+
+/// users.drafts.list, newest first; `query` takes the same syntax as a search.
+pub async fn list_drafts(token: &ApiToken, query: &str, max_results: u32) -> Result<Vec<Draft>> {
+    let mut params = vec![("maxResults", max_results.to_string())];
+    if !query.is_empty() {
+        params.push(("q", query.to_string()));
+    }
+    let list: DraftList = get_json(token, "drafts", &params).await?;
+    Ok(list.drafts)
+}
+
+pub async fn get_draft(token: &ApiToken, draft_id: &str) -> Result<DraftDetail> {
+    get_json(token, &format!("drafts/{}", draft_id), &[("format", "full".to_string())]).await
+}
+
+pub async fn send_draft(token: &ApiToken, draft_id: &str) -> Result<Message> {
+    let result: Result<Message> = post_json(token, "drafts/send", &serde_json::json!({ "id": draft_id })).await;
+    crate::audit::record("send", result.as_ref().ok().map(|m| m.id.as_str()), &format!("draft={}", draft_id), &result);
+    result
+}
+
+pub async fn delete_draft(token: &ApiToken, draft_id: &str) -> Result<()> {
+    let result = delete(token, &format!("drafts/{}", draft_id)).await;
+    crate::audit::record("delete_draft", None, &format!("draft={}", draft_id), &result);
+    result
+}
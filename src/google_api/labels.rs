@@ -0,0 +1,108 @@
+use super::{get_json, post_empty, post_json, ApiToken, Message};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// The counts part of users.labels.get.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Label {
+    pub messages_total: Option<u32>,
+    pub messages_unread: Option<u32>,
+}
+
+pub async fn get_label(token: &ApiToken, label_id: &str) -> Result<Label> {
+    get_json(token, &format!("labels/{}", label_id), &[]).await
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LabelInfo {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct LabelList {
+    #[serde(default)]
+    labels: Vec<LabelInfo>,
+}
+
+/// users.labels.list: every system and user label, without counts.
+pub async fn list_labels(token: &ApiToken) -> Result<Vec<LabelInfo>> {
+    let list: LabelList = get_json(token, "labels", &[]).await?;
+    Ok(list.labels)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModifyRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    add_label_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    remove_label_ids: Vec<String>,
+}
+
+pub async fn modify_labels(token: &ApiToken, message_id: &str, add: &[&str], remove: &[&str]) -> Result<()> {
+    let request_body = ModifyRequest {
+        add_label_ids: add.iter().map(|l| l.to_string()).collect(),
+        remove_label_ids: remove.iter().map(|l| l.to_string()).collect(),
+    };
+    let result: Result<Message> = post_json(token, &format!("messages/{}/modify", message_id), &request_body).await;
+    let details = format!("add={} remove={}", add.join(","), remove.join(","));
+    crate::audit::record("modify", Some(message_id), &details, &result);
+    result.map(|_| ())
+}
+
+/// users.threads.modify: changes the labels of every message in the conversation.
+pub async fn modify_thread_labels(token: &ApiToken, thread_id: &str, add: &[&str], remove: &[&str]) -> Result<()> {
+    let request_body = ModifyRequest {
+        add_label_ids: add.iter().map(|l| l.to_string()).collect(),
+        remove_label_ids: remove.iter().map(|l| l.to_string()).collect(),
+    };
+    let result: Result<super::Thread> = post_json(token, &format!("threads/{}/modify", thread_id), &request_body).await;
+    let details = format!("thread={} add={} remove={}", thread_id, add.join(","), remove.join(","));
+    crate::audit::record("modify_thread", None, &details, &result);
+    result.map(|_| ())
+}
+
+/// The most ids users.messages.batchModify accepts in one call.
+pub const BATCH_MODIFY_LIMIT: usize = 1000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchModifyRequest<'a> {
+    ids: &'a [String],
+    #[serde(flatten)]
+    labels: ModifyRequest,
+}
+
+/// users.messages.batchModify, for up to [`BATCH_MODIFY_LIMIT`] messages at once.
+pub async fn batch_modify_labels(token: &ApiToken, message_ids: &[String], add: &[&str], remove: &[&str]) -> Result<()> {
+    let request_body = BatchModifyRequest {
+        ids: message_ids,
+        labels: ModifyRequest {
+            add_label_ids: add.iter().map(|l| l.to_string()).collect(),
+            remove_label_ids: remove.iter().map(|l| l.to_string()).collect(),
+        },
+    };
+    let result = post_empty(token, "messages/batchModify", &request_body).await;
+    let details = format!("count={} add={} remove={}", message_ids.len(), add.join(","), remove.join(","));
+    crate::audit::record("batch_modify", None, &details, &result);
+    result
+}
+
+pub async fn mark_as_read(token: &ApiToken, message_id: &str) -> Result<()> {
+    modify_labels(token, message_id, &[], &["UNREAD"]).await
+}
+
+pub async fn mark_as_unread(token: &ApiToken, message_id: &str) -> Result<()> {
+    modify_labels(token, message_id, &["UNREAD"], &[]).await
+}
+
+/// Gmail's archive: the message leaves the inbox and keeps its other labels.
+pub async fn archive_message(token: &ApiToken, message_id: &str) -> Result<()> {
+    modify_labels(token, message_id, &[], &["INBOX"]).await
+}
+
+pub async fn archive_thread(token: &ApiToken, thread_id: &str) -> Result<()> {
+    modify_thread_labels(token, thread_id, &[], &["INBOX"]).await
+}
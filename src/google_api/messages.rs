@@ -0,0 +1,303 @@
+use super::{delete, get_json, post_json, ApiToken};
+use crate::mime::OutgoingMessage;
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageList {
+    pub messages: Option<Vec<Message>>,
+    pub next_page_token: Option<String>,
+    pub result_size_estimate: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Message {
+    pub id: String,
+    pub thread_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageDetail {
+    pub id: String,
+    pub thread_id: String,
+    pub snippet: String,
+    pub payload: Option<MessagePayload>,
+    pub label_ids: Option<Vec<String>>,
+    // Milliseconds since the epoch, as a string
+    pub internal_date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePayload {
+    pub headers: Vec<MessageHeader>,
+    pub body: Option<MessageBody>,
+    pub parts: Option<Vec<MessagePayload>>,
+    pub mime_type: String,
+    // Empty unless the part is an attachment
+    #[serde(default)]
+    pub filename: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageBody {
+    pub data: Option<String>,
+    // Set instead of `data` when the content has to be fetched separately
+    pub attachment_id: Option<String>,
+    // Decoded bytes; missing from messages cached by older versions
+    #[serde(default)]
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendRequest {
+    raw: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_id: Option<String>,
+}
+
+impl MessageDetail {
+    pub fn get_header(&self, name: &str) -> String {
+        self.payload
+            .as_ref()
+            .and_then(|p| {
+                p.headers
+                    .iter()
+                    .find(|h| h.name.eq_ignore_ascii_case(name))
+            })
+            .map_or_else(String::new, |h| h.value.clone())
+    }
+
+    /// When Gmail received the message, as seconds since the epoch.
+    pub fn received_at(&self) -> Option<i64> {
+        self.internal_date.as_deref()?.parse::<i64>().ok().map(|ms| ms / 1000)
+    }
+
+    pub fn is_unread(&self) -> bool {
+        if let Some(labels) = &self.label_ids {
+            labels.contains(&"UNREAD".to_string())
+        } else {
+            false
+        }
+    }
+}
+
+/// `format` for messages.get and threads.get.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Full,
+    Metadata,
+}
+
+impl Format {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Format::Full => "full",
+            Format::Metadata => "metadata",
+        }
+    }
+}
+
+/// users.messages.list
+#[derive(Default, Debug, Clone)]
+pub struct MessagesList {
+    q: Option<String>,
+    max_results: Option<u32>,
+    page_token: Option<String>,
+    label_ids: Vec<String>,
+    include_spam_trash: bool,
+}
+
+impl MessagesList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn q(mut self, query: impl Into<String>) -> Self {
+        self.q = Some(query.into());
+        self
+    }
+
+    pub fn max_results(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    pub fn page_token(mut self, page_token: Option<&str>) -> Self {
+        self.page_token = page_token.map(str::to_string);
+        self
+    }
+
+    /// Only messages carrying all of these label ids.
+    pub fn label_ids(mut self, label_ids: &[&str]) -> Self {
+        self.label_ids = label_ids.iter().map(|l| l.to_string()).collect();
+        self
+    }
+
+    pub fn include_spam_trash(mut self, include: bool) -> Self {
+        self.include_spam_trash = include;
+        self
+    }
+
+    pub async fn send(&self, token: &ApiToken) -> Result<MessageList> {
+        let mut params = Vec::new();
+        if let Some(q) = &self.q {
+            params.push(("q", q.clone()));
+        }
+        if let Some(max_results) = self.max_results {
+            params.push(("maxResults", max_results.to_string()));
+        }
+        if let Some(page_token) = &self.page_token {
+            params.push(("pageToken", page_token.clone()));
+        }
+        for label_id in &self.label_ids {
+            params.push(("labelIds", label_id.clone()));
+        }
+        if self.include_spam_trash {
+            params.push(("includeSpamTrash", "true".to_string()));
+        }
+        get_json(token, "messages", &params).await
+    }
+}
+
+/// users.messages.get
+#[derive(Debug, Clone)]
+pub struct MessagesGet {
+    id: String,
+    format: Format,
+    metadata_headers: Vec<String>,
+}
+
+impl MessagesGet {
+    pub fn new(id: &str) -> Self {
+        MessagesGet {
+            id: id.to_string(),
+            format: Format::Full,
+            metadata_headers: Vec::new(),
+        }
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Restricts a `Format::Metadata` response to these headers.
+    pub fn metadata_headers(mut self, headers: &[&str]) -> Self {
+        self.metadata_headers = headers.iter().map(|h| h.to_string()).collect();
+        self
+    }
+
+    pub async fn send(&self, token: &ApiToken) -> Result<MessageDetail> {
+        let mut params = vec![("format", self.format.as_str().to_string())];
+        for header in &self.metadata_headers {
+            params.push(("metadataHeaders", header.clone()));
+        }
+        get_json(token, &format!("messages/{}", self.id), &params).await
+    }
+}
+
+/// Shortcut for the common case of one page of `query`; anything more goes through
+/// [`MessagesList`], which encodes every parameter.
+pub async fn list_messages(token: &ApiToken, query: &str, max_results: u32) -> Result<MessageList> {
+    MessagesList::new().q(query).max_results(max_results).send(token).await
+}
+
+/// A message search kept across pages: set `page_token` from each page to fetch the next.
+#[derive(Default, Debug, Clone)]
+pub struct ListParams {
+    pub query: Option<String>,
+    pub label_ids: Vec<String>,
+    pub max_results: Option<u32>,
+    pub page_token: Option<String>,
+}
+
+pub async fn list_messages_page(token: &ApiToken, params: &ListParams) -> Result<MessageList> {
+    let label_ids: Vec<&str> = params.label_ids.iter().map(String::as_str).collect();
+    let mut list = MessagesList::new().label_ids(&label_ids).page_token(params.page_token.as_deref());
+    if let Some(query) = &params.query {
+        list = list.q(query);
+    }
+    if let Some(max_results) = params.max_results {
+        list = list.max_results(max_results);
+    }
+    list.send(token).await
+}
+
+/// Fetches only the named headers (plus labels, snippet and date), which keeps list loads small.
+pub async fn get_message_headers(token: &ApiToken, message_id: &str, headers: &[&str]) -> Result<MessageDetail> {
+    MessagesGet::new(message_id)
+        .format(Format::Metadata)
+        .metadata_headers(headers)
+        .send(token)
+        .await
+}
+
+pub async fn get_full_message(token: &ApiToken, message_id: &str) -> Result<MessageDetail> {
+    MessagesGet::new(message_id).send(token).await
+}
+
+pub async fn send_message(token: &ApiToken, message: &OutgoingMessage) -> Result<Message> {
+    // Gmail's own answer to this is a bare 400 "Invalid To header"
+    if message.to.is_empty() && message.cc.is_empty() && message.bcc.is_empty() {
+        anyhow::bail!("The message has no recipients; add a To, Cc or Bcc address");
+    }
+    send_raw(token, &message.to_rfc2822()?, message.thread_id.as_deref()).await
+}
+
+pub async fn send_raw(token: &ApiToken, rfc2822: &str, thread_id: Option<&str>) -> Result<Message> {
+    let request_body = SendRequest {
+        raw: URL_SAFE_NO_PAD.encode(rfc2822),
+        thread_id: thread_id.map(str::to_string),
+    };
+    let result: Result<Message> = post_json(token, "messages/send", &request_body).await;
+    // Recipients are enough to identify a send; the body stays out of the log
+    let recipients = rfc2822
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix("To:"))
+        .unwrap_or("")
+        .trim();
+    let id = result.as_ref().ok().map(|message| message.id.as_str());
+    crate::audit::record("send", id, &format!("to={}", recipients), &result);
+    result
+}
+
+pub async fn trash_message(token: &ApiToken, message_id: &str) -> Result<()> {
+    let result: Result<Message> = post_json(token, &format!("messages/{}/trash", message_id), &serde_json::json!({})).await;
+    crate::audit::record("trash", Some(message_id), "", &result);
+    result.map(|_| ())
+}
+
+/// Moves a message out of Trash, back to the labels it had before.
+pub async fn untrash_message(token: &ApiToken, message_id: &str) -> Result<()> {
+    let result: Result<Message> = post_json(token, &format!("messages/{}/untrash", message_id), &serde_json::json!({})).await;
+    crate::audit::record("untrash", Some(message_id), "", &result);
+    result.map(|_| ())
+}
+
+/// Permanently deletes a message, bypassing Trash. Needs `FULL_ACCESS_SCOPE`.
+pub async fn delete_message(token: &ApiToken, message_id: &str) -> Result<()> {
+    let result = delete(token, &format!("messages/{}", message_id)).await;
+    crate::audit::record("delete", Some(message_id), "", &result);
+    result
+}
+
+/// users.messages.attachments.get, decoded.
+pub async fn get_attachment(token: &ApiToken, message_id: &str, attachment_id: &str) -> Result<Vec<u8>> {
+    let body: MessageBody = get_json(token, &format!("messages/{}/attachments/{}", message_id, attachment_id), &[]).await?;
+    let data = body.data.unwrap_or_default();
+    Ok(URL_SAFE_NO_PAD.decode(data.trim_end_matches('='))?)
+}
@@ -0,0 +1,111 @@
+//! Gmail REST API client. Each submodule covers one resource; request builders such as
+//! [`MessagesList`] collect the parameters and `send` them through the helpers below.
+
+mod auth;
+mod body;
+mod drafts;
+mod labels;
+mod messages;
+mod network;
+mod service_account;
+mod settings;
+mod threads;
+
+pub use auth::*;
+pub use body::*;
+pub use drafts::*;
+pub use labels::*;
+pub use messages::*;
+pub use network::{configure_network, endpoints, http_client};
+pub use service_account::{service_account, use_service_account, ServiceAccount};
+pub use settings::*;
+pub use threads::*;
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub const DEFAULT_INBOX_QUERY: &str = "in:inbox category:primary newer_than:30d";
+
+/// Google refused the authorization and refreshing didn't help: the grant was revoked, the
+/// refresh token expired, or there is none. The token is marked, see [`ApiToken::is_rejected`].
+#[derive(Debug)]
+pub struct AuthRejected(String);
+
+impl std::fmt::Display for AuthRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Authorization rejected ({}); sign in again", self.0)
+    }
+}
+
+impl std::error::Error for AuthRejected {}
+
+fn rejected(token: &ApiToken, reason: impl Into<String>) -> anyhow::Error {
+    token.mark_rejected();
+    AuthRejected(reason.into()).into()
+}
+
+// Sends the request `build` describes with the current access token. An expired token is
+// refreshed and the request retried once, so callers never see the hourly expiry.
+async fn send_authorized(
+    token: &ApiToken,
+    build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let bearer = token.bearer();
+    let res = network::send(|client| build(client).bearer_auth(&bearer)).await?;
+    if res.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(res.error_for_status()?);
+    }
+    token.refresh(&bearer).await?;
+    let bearer = token.bearer();
+    let retried = network::send(|client| build(client).bearer_auth(&bearer)).await?;
+    if retried.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(rejected(token, "refused even after a refresh"));
+    }
+    Ok(retried.error_for_status()?)
+}
+
+// `path` is relative to the signed-in user, e.g. "messages/<id>"
+async fn get_json<T: DeserializeOwned>(token: &ApiToken, path: &str, params: &[(&str, String)]) -> Result<T> {
+    let url = format!("{}/{}", endpoints().gmail(), path);
+    let res = send_authorized(token, |client| client.get(&url).query(params)).await?;
+    Ok(res.json::<T>().await?)
+}
+
+async fn post_json<B: Serialize, T: DeserializeOwned>(token: &ApiToken, path: &str, body: &B) -> Result<T> {
+    let url = format!("{}/{}", endpoints().gmail(), path);
+    let res = send_authorized(token, |client| client.post(&url).json(body)).await?;
+    Ok(res.json::<T>().await?)
+}
+
+async fn put_json<B: Serialize, T: DeserializeOwned>(token: &ApiToken, path: &str, body: &B) -> Result<T> {
+    let url = format!("{}/{}", endpoints().gmail(), path);
+    let res = send_authorized(token, |client| client.put(&url).json(body)).await?;
+    Ok(res.json::<T>().await?)
+}
+
+// For methods that answer with an empty body
+async fn post_empty<B: Serialize>(token: &ApiToken, path: &str, body: &B) -> Result<()> {
+    let url = format!("{}/{}", endpoints().gmail(), path);
+    send_authorized(token, |client| client.post(&url).json(body)).await?;
+    Ok(())
+}
+
+async fn delete(token: &ApiToken, path: &str) -> Result<()> {
+    let url = format!("{}/{}", endpoints().gmail(), path);
+    send_authorized(token, |client| client.delete(&url)).await?;
+    Ok(())
+}
+
+// True when Gmail answered 404, e.g. for a message deleted since its id was seen
+pub fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.status() == Some(reqwest::StatusCode::NOT_FOUND))
+}
+
+// True for failures where the request never got a response, as opposed to an API rejection
+pub fn is_network_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || e.is_timeout() || (e.is_request() && e.status().is_none()))
+}
@@ -0,0 +1,170 @@
+use crate::config::NetworkConfig;
+use anyhow::{Context, Result};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DEFAULT_API_URL: &str = "https://www.googleapis.com";
+const DEFAULT_OAUTH_URL: &str = "https://oauth2.googleapis.com";
+const DEFAULT_ACCOUNTS_URL: &str = "https://accounts.google.com";
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+// A Retry-After longer than this is better reported than waited out
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+static NETWORK: OnceLock<Network> = OnceLock::new();
+
+struct Network {
+    client: reqwest::Client,
+    // Token endpoints answer with redirects only when something is wrong; following them
+    // could hand an authorization code to another host
+    oauth_client: reqwest::Client,
+    endpoints: Endpoints,
+    retries: u32,
+}
+
+/// Base URLs of the Google services used, overridable for gateways and test servers.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub api: String,
+    pub oauth: String,
+    pub accounts: String,
+}
+
+impl Endpoints {
+    pub fn gmail(&self) -> String {
+        format!("{}/gmail/v1/users/me", self.api)
+    }
+
+    pub fn token(&self) -> String {
+        format!("{}/token", self.oauth)
+    }
+
+    pub fn authorize(&self) -> String {
+        format!("{}/o/oauth2/v2/auth", self.accounts)
+    }
+
+    pub fn is_custom_oauth(&self) -> bool {
+        self.oauth != DEFAULT_OAUTH_URL
+    }
+}
+
+fn client_builder(config: &NetworkConfig) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+    if config.timeout_secs > 0 {
+        builder = builder.timeout(Duration::from_secs(config.timeout_secs));
+    }
+    if config.connect_timeout_secs > 0 {
+        builder = builder.connect_timeout(Duration::from_secs(config.connect_timeout_secs));
+    }
+    // Without a configured proxy, reqwest still honours HTTPS_PROXY, HTTP_PROXY and NO_PROXY
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).with_context(|| format!("Invalid network.proxy {:?}", proxy))?);
+    }
+    if let Some(path) = &config.ca_bundle {
+        let pem = std::fs::read(path).with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("{} is not a PEM certificate bundle", path.display()))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
+
+fn build(config: &NetworkConfig) -> Result<Network> {
+    let url = |value: &Option<String>, default: &str| value.as_deref().unwrap_or(default).trim_end_matches('/').to_string();
+    Ok(Network {
+        client: client_builder(config)?.build()?,
+        oauth_client: client_builder(config)?.redirect(reqwest::redirect::Policy::none()).build()?,
+        endpoints: Endpoints {
+            api: url(&config.api_url, DEFAULT_API_URL),
+            oauth: url(&config.oauth_url, DEFAULT_OAUTH_URL),
+            accounts: url(&config.accounts_url, DEFAULT_ACCOUNTS_URL),
+        },
+        retries: config.retries,
+    })
+}
+
+/// Builds the shared HTTP clients from `[network]`. Call once, before the first request;
+/// until then (and in later calls) the defaults stay in place.
+pub fn configure_network(config: &NetworkConfig) -> Result<()> {
+    let _ = NETWORK.set(build(config)?);
+    Ok(())
+}
+
+fn network() -> &'static Network {
+    // Like reqwest::Client::new, which also panics if TLS can't be initialised
+    NETWORK.get_or_init(|| build(&NetworkConfig::default()).expect("failed to build the HTTP client"))
+}
+
+/// The client every request should use, so proxy and CA settings apply everywhere. Cheap to
+/// clone; clones share one connection pool.
+pub fn http_client() -> reqwest::Client {
+    network().client.clone()
+}
+
+pub fn endpoints() -> &'static Endpoints {
+    &network().endpoints
+}
+
+// Sends the request `build` describes, retrying up to `network.retries` times with exponential
+// backoff. A failed connection is always retried, since nothing reached the server; timeouts,
+// 429 and 5xx only for methods that are safe to repeat, so a message is never sent twice.
+async fn send_via(
+    client: &reqwest::Client,
+    build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let retries = network().retries;
+    let mut attempt = 0;
+    loop {
+        let request = build(client).build()?;
+        let repeatable = matches!(*request.method(), reqwest::Method::GET | reqwest::Method::HEAD | reqwest::Method::DELETE);
+        let result = client.execute(request).await;
+        let retry_after = match &result {
+            Err(e) if e.is_connect() || (repeatable && e.is_timeout()) => Some(None),
+            Ok(res) if repeatable && (res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error()) => {
+                let seconds = res.headers().get(reqwest::header::RETRY_AFTER).and_then(|v| v.to_str().ok()?.parse().ok());
+                Some(seconds.map(Duration::from_secs))
+            }
+            _ => None,
+        };
+        let delay = match retry_after {
+            Some(delay) if attempt < retries => delay.unwrap_or(RETRY_BASE_DELAY * 2u32.pow(attempt)),
+            _ => return result,
+        };
+        if delay > MAX_RETRY_DELAY {
+            return result;
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+pub(super) async fn send(build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    send_via(&network().client, build).await
+}
+
+// Lets the oauth2 crate's token exchanges go through the shared client instead of its own
+pub(super) async fn oauth_http_client(request: oauth2::HttpRequest) -> Result<oauth2::HttpResponse, reqwest::Error> {
+    let method = reqwest::Method::from_bytes(request.method.as_str().as_bytes()).unwrap_or(reqwest::Method::POST);
+    let response = send_via(&network().oauth_client, |client| {
+        let mut builder = client.request(method.clone(), request.url.as_str()).body(request.body.clone());
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+        builder
+    })
+    .await?;
+    let status_code = oauth2::http::StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(oauth2::http::StatusCode::INTERNAL_SERVER_ERROR);
+    let mut headers = oauth2::http::HeaderMap::new();
+    for (name, value) in response.headers() {
+        if let (Ok(name), Ok(value)) = (
+            oauth2::http::HeaderName::from_bytes(name.as_str().as_bytes()),
+            oauth2::http::HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    let body = response.bytes().await?.to_vec();
+    Ok(oauth2::HttpResponse { status_code, headers, body })
+}
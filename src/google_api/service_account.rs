@@ -0,0 +1,96 @@
+use super::auth::requested_scopes;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+// Google accepts assertions valid for at most an hour
+const ASSERTION_LIFETIME_SECS: i64 = 3600;
+
+/// A Workspace service account with domain-wide delegation, acting as `subject`.
+#[derive(Debug, Clone)]
+pub struct ServiceAccount {
+    pub key_file: PathBuf,
+    pub subject: String,
+}
+
+// The fields used from the JSON key downloaded from the Cloud console
+#[derive(Deserialize)]
+struct KeyFile {
+    client_email: String,
+    private_key: String,
+    token_uri: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    scope: String,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+pub(super) struct AccessToken {
+    pub access_token: String,
+    pub expires_in: Option<u64>,
+}
+
+static SERVICE_ACCOUNT: OnceLock<ServiceAccount> = OnceLock::new();
+
+/// Authenticates as `account` instead of the saved OAuth token (`--auth service-account`).
+pub fn use_service_account(account: ServiceAccount) {
+    let _ = SERVICE_ACCOUNT.set(account);
+}
+
+pub fn service_account() -> Option<&'static ServiceAccount> {
+    SERVICE_ACCOUNT.get()
+}
+
+fn read_key(path: &Path) -> Result<KeyFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the service account key {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("{} is not a service account key file", path.display()))
+}
+
+/// Exchanges a freshly signed assertion for an access token. There is no refresh token:
+/// a new assertion is signed whenever the access token expires.
+pub(super) async fn request_token(account: &ServiceAccount) -> Result<AccessToken> {
+    let key = read_key(&account.key_file)?;
+    let token_uri = key.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+    let now = Utc::now().timestamp();
+    let claims = Claims {
+        iss: &key.client_email,
+        sub: &account.subject,
+        scope: requested_scopes().join(" "),
+        aud: token_uri,
+        iat: now,
+        exp: now + ASSERTION_LIFETIME_SECS,
+    };
+    let signing_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes()).context("Invalid private key")?;
+    let assertion = encode(&Header::new(Algorithm::RS256), &claims, &signing_key)?;
+
+    // The assertion stays addressed to Google; a configured gateway only changes where it goes
+    let endpoint = if super::endpoints().is_custom_oauth() { super::endpoints().token() } else { token_uri.to_string() };
+    let res = super::http_client()
+        .post(endpoint)
+        .form(&[("grant_type", JWT_BEARER_GRANT), ("assertion", assertion.as_str())])
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        anyhow::bail!(
+            "Google refused the service account token for {} ({}): {}. Check that domain-wide \
+             delegation is granted for these scopes in the Workspace admin console.",
+            account.subject,
+            res.status(),
+            res.text().await.unwrap_or_default()
+        );
+    }
+    Ok(res.json().await?)
+}
@@ -0,0 +1,131 @@
+use super::{get_json, post_json, put_json, ApiToken};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub email_address: String,
+}
+
+pub async fn get_profile(token: &ApiToken) -> Result<Profile> {
+    get_json(token, "profile", &[]).await
+}
+
+// users.settings getters, which gmail.readonly is enough for
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoForwarding {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    // What happens to the copy left in Gmail, e.g. "leaveInInbox" or "archive"
+    pub disposition: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImapSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    pub auto_expunge: Option<bool>,
+    pub expunge_behavior: Option<String>,
+    pub max_folder_size: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PopSettings {
+    // "disabled", "fromNowOn" or "allMail"
+    pub access_window: Option<String>,
+    pub disposition: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageSettings {
+    pub display_language: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SendAs {
+    pub send_as_email: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub is_primary: bool,
+    // "accepted" or "pending"; absent for the primary address
+    pub verification_status: Option<String>,
+    // Set when the alias goes out through its own provider's SMTP server instead of Gmail's
+    pub smtp_msa: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SendAsList {
+    #[serde(default)]
+    send_as: Vec<SendAs>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardingAddress {
+    pub forwarding_email: String,
+    pub verification_status: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ForwardingAddressList {
+    #[serde(default)]
+    forwarding_addresses: Vec<ForwardingAddress>,
+}
+
+pub async fn get_auto_forwarding(token: &ApiToken) -> Result<AutoForwarding> {
+    get_json(token, "settings/autoForwarding", &[]).await
+}
+
+pub async fn get_imap(token: &ApiToken) -> Result<ImapSettings> {
+    get_json(token, "settings/imap", &[]).await
+}
+
+pub async fn get_pop(token: &ApiToken) -> Result<PopSettings> {
+    get_json(token, "settings/pop", &[]).await
+}
+
+pub async fn get_language(token: &ApiToken) -> Result<LanguageSettings> {
+    get_json(token, "settings/language", &[]).await
+}
+
+pub async fn list_send_as(token: &ApiToken) -> Result<Vec<SendAs>> {
+    let list: SendAsList = get_json(token, "settings/sendAs", &[]).await?;
+    Ok(list.send_as)
+}
+
+pub async fn list_forwarding_addresses(token: &ApiToken) -> Result<Vec<ForwardingAddress>> {
+    let list: ForwardingAddressList = get_json(token, "settings/forwardingAddresses", &[]).await?;
+    Ok(list.forwarding_addresses)
+}
+
+/// Adds an address to forward to. Unless the account is a Workspace one forwarding inside its
+/// domain, Google mails the address a confirmation link and it stays "pending" until clicked.
+pub async fn create_forwarding_address(token: &ApiToken, email: &str) -> Result<ForwardingAddress> {
+    let result: Result<ForwardingAddress> =
+        post_json(token, "settings/forwardingAddresses", &serde_json::json!({ "forwardingEmail": email })).await;
+    crate::audit::record("forwarding_address", None, &format!("add={}", email), &result);
+    result
+}
+
+pub async fn update_auto_forwarding(token: &ApiToken, settings: &AutoForwarding) -> Result<AutoForwarding> {
+    let result: Result<AutoForwarding> = put_json(token, "settings/autoForwarding", settings).await;
+    let details = match &settings.email_address {
+        Some(address) if settings.enabled => format!("enable={}", address),
+        _ => "disable".to_string(),
+    };
+    crate::audit::record("auto_forwarding", None, &details, &result);
+    result
+}
@@ -0,0 +1,145 @@
+use super::{get_json, post_json, ApiToken, Format, MessageDetail};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Thread {
+    pub id: String,
+    pub messages: Option<Vec<MessageDetail>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadList {
+    pub threads: Option<Vec<ThreadRef>>,
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ThreadRef {
+    pub id: String,
+}
+
+impl Thread {
+    /// The newest message that isn't an unsent draft, which is what a thread row shows.
+    pub fn latest(&self) -> Option<&MessageDetail> {
+        let messages = self.messages.as_deref()?;
+        let is_draft = |m: &&MessageDetail| m.label_ids.as_ref().is_some_and(|l| l.iter().any(|l| l == "DRAFT"));
+        messages.iter().rev().find(|m| !is_draft(m)).or(messages.last())
+    }
+
+    pub fn message_count(&self) -> usize {
+        self.messages.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn is_unread(&self) -> bool {
+        self.messages.iter().flatten().any(MessageDetail::is_unread)
+    }
+}
+
+/// users.threads.list
+#[derive(Default, Debug, Clone)]
+pub struct ThreadsList {
+    q: Option<String>,
+    max_results: Option<u32>,
+    page_token: Option<String>,
+}
+
+impl ThreadsList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn q(mut self, query: impl Into<String>) -> Self {
+        self.q = Some(query.into());
+        self
+    }
+
+    pub fn max_results(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    pub fn page_token(mut self, page_token: Option<&str>) -> Self {
+        self.page_token = page_token.map(str::to_string);
+        self
+    }
+
+    pub async fn send(&self, token: &ApiToken) -> Result<ThreadList> {
+        let mut params = Vec::new();
+        if let Some(q) = &self.q {
+            params.push(("q", q.clone()));
+        }
+        if let Some(max_results) = self.max_results {
+            params.push(("maxResults", max_results.to_string()));
+        }
+        if let Some(page_token) = &self.page_token {
+            params.push(("pageToken", page_token.clone()));
+        }
+        get_json(token, "threads", &params).await
+    }
+}
+
+/// users.threads.get
+#[derive(Debug, Clone)]
+pub struct ThreadsGet {
+    id: String,
+    format: Format,
+    metadata_headers: Vec<String>,
+}
+
+impl ThreadsGet {
+    pub fn new(id: &str) -> Self {
+        ThreadsGet {
+            id: id.to_string(),
+            format: Format::Full,
+            metadata_headers: Vec::new(),
+        }
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn metadata_headers(mut self, headers: &[&str]) -> Self {
+        self.metadata_headers = headers.iter().map(|h| h.to_string()).collect();
+        self
+    }
+
+    pub async fn send(&self, token: &ApiToken) -> Result<Thread> {
+        let mut params = vec![("format", self.format.as_str().to_string())];
+        for header in &self.metadata_headers {
+            params.push(("metadataHeaders", header.clone()));
+        }
+        get_json(token, &format!("threads/{}", self.id), &params).await
+    }
+}
+
+pub async fn get_thread(token: &ApiToken, thread_id: &str, headers: &[&str]) -> Result<Thread> {
+    ThreadsGet::new(thread_id)
+        .format(Format::Metadata)
+        .metadata_headers(headers)
+        .send(token)
+        .await
+}
+
+/// The whole conversation with every message's body.
+pub async fn get_full_thread(token: &ApiToken, thread_id: &str) -> Result<Thread> {
+    ThreadsGet::new(thread_id).send(token).await
+}
+
+/// Moves every message in the thread to Trash.
+pub async fn trash_thread(token: &ApiToken, thread_id: &str) -> Result<()> {
+    let result: Result<Thread> = post_json(token, &format!("threads/{}/trash", thread_id), &serde_json::json!({})).await;
+    crate::audit::record("trash_thread", Some(thread_id), "", &result);
+    result.map(|_| ())
+}
+
+/// Moves every message in the thread out of Trash.
+pub async fn untrash_thread(token: &ApiToken, thread_id: &str) -> Result<()> {
+    let result: Result<Thread> = post_json(token, &format!("threads/{}/untrash", thread_id), &serde_json::json!({})).await;
+    crate::audit::record("untrash_thread", Some(thread_id), "", &result);
+    result.map(|_| ())
+}
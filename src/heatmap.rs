@@ -0,0 +1,83 @@
+use crate::google_api::MessageDetail;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use std::collections::HashMap;
+
+// Darker means more mail; the first is used for days with none
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const DAY_LABELS: [&str; 7] = ["Mon", "   ", "Wed", "   ", "Fri", "   ", "Sun"];
+
+/// Received messages per local day. Sent mail is left out.
+pub fn daily_counts(messages: &[MessageDetail]) -> HashMap<NaiveDate, usize> {
+    let mut counts = HashMap::new();
+    for message in messages {
+        let sent = message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == "SENT"));
+        let Some(date) = message
+            .received_at()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|utc| utc.with_timezone(&Local).date_naive())
+        else {
+            continue;
+        };
+        if !sent {
+            *counts.entry(date).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+fn shade(count: usize, max: usize) -> char {
+    if count == 0 || max == 0 {
+        return SHADES[0];
+    }
+    // Any mail at all gets at least the lightest shade; the busiest day gets the darkest
+    let level = (count * (SHADES.len() - 1)).div_ceil(max);
+    SHADES[level.min(SHADES.len() - 1)]
+}
+
+/// A GitHub-style grid: one column per week ending with the week of `today`, one row per
+/// weekday, with month names above the columns where a month starts.
+pub fn render(counts: &HashMap<NaiveDate, usize>, weeks: usize, today: NaiveDate) -> Vec<String> {
+    let weeks = weeks.max(1);
+    let this_monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let first_monday = this_monday - Duration::weeks(weeks as i64 - 1);
+    let max = (0..weeks as i64 * 7)
+        .map(|d| counts.get(&(first_monday + Duration::days(d))).copied().unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+
+    let mut months = String::from("    ");
+    let mut column = 0;
+    for week in 0..weeks {
+        let monday = first_monday + Duration::weeks(week as i64);
+        let starts_month = week == 0 || (monday - Duration::weeks(1)).month() != monday.month();
+        if starts_month && column <= week {
+            let label = monday.format("%b").to_string();
+            months.push_str(&label);
+            column = week + label.len();
+        } else if column <= week {
+            months.push(' ');
+            column = week + 1;
+        }
+    }
+
+    let mut lines = vec![months.trim_end().to_string()];
+    for (weekday, label) in DAY_LABELS.iter().enumerate() {
+        let mut row = format!("{} ", label);
+        for week in 0..weeks {
+            let day = first_monday + Duration::weeks(week as i64) + Duration::days(weekday as i64);
+            row.push(if day > today { ' ' } else { shade(counts.get(&day).copied().unwrap_or(0), max) });
+        }
+        lines.push(row.trim_end().to_string());
+    }
+
+    let start = first_monday;
+    let total: usize = counts.iter().filter(|(day, _)| **day >= start && **day <= today).map(|(_, n)| n).sum();
+    lines.push(format!(
+        "{} messages since {}, busiest day {}. Less {} More",
+        total,
+        start.format("%Y-%m-%d"),
+        max,
+        SHADES.iter().collect::<String>()
+    ));
+    lines
+}
@@ -0,0 +1,184 @@
+use crate::address;
+use crate::config::{HtmlConfig, HtmlEngine};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, OnceLock};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_WIDTH: usize = 80;
+// An external renderer that hangs falls back to html2text instead of stalling the viewer
+const PIPE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static SETTINGS: OnceLock<(HtmlConfig, Option<HtmlEngine>)> = OnceLock::new();
+
+/// Sets the `[html]` config and the engine chosen with --html-engine, which wins over both
+/// the default and per-sender choices.
+pub fn configure(config: &HtmlConfig, engine: Option<HtmlEngine>) {
+    let _ = SETTINGS.set((config.clone(), engine));
+}
+
+// Keys are full addresses or "@domain"; the full address is more specific and wins
+fn engine_for(config: &HtmlConfig, from: &str) -> HtmlEngine {
+    let sender = address::extract_address(from);
+    let domain = sender.split_once('@').map(|(_, domain)| format!("@{}", domain));
+    config
+        .senders
+        .get(&sender)
+        .or_else(|| domain.and_then(|d| config.senders.get(&d)))
+        .copied()
+        .unwrap_or(config.engine)
+}
+
+/// Turns an HTML body into text `width` columns wide with the engine configured for its
+/// sender. External commands that are missing or fail fall back to html2text.
+pub fn to_text(html: &str, from: &str, width: usize) -> String {
+    let (config, forced) = SETTINGS.get_or_init(Default::default);
+    let engine = forced.unwrap_or_else(|| engine_for(config, from));
+    let rendered = match engine {
+        HtmlEngine::Html2text => return html2text::from_read(html.as_bytes(), width),
+        HtmlEngine::Readability => return readable(html, width),
+        HtmlEngine::W3m => pipe(&format!("w3m -dump -T text/html -cols {} -O UTF-8", width), html),
+        HtmlEngine::Lynx => pipe(
+            &format!("lynx -dump -stdin -nolist -width={} -assume_charset=UTF-8 -display_charset=UTF-8", width),
+            html,
+        ),
+        HtmlEngine::Command => match &config.command {
+            Some(command) => pipe(command, html),
+            None => Err(anyhow::anyhow!("html.command is not set")),
+        },
+    };
+    rendered.unwrap_or_else(|_| html2text::from_read(html.as_bytes(), width))
+}
+
+// The HTML goes to the command's stdin and its stdout is the text
+fn pipe(command: &str, html: &str) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+    // Both ends run on their own threads: a command that writes while it reads would otherwise
+    // fill one pipe while we block on the other
+    if let Some(mut stdin) = child.stdin.take() {
+        let html = html.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(html.as_bytes());
+        });
+    }
+    let mut stdout = child.stdout.take().context("No output from the command")?;
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        output
+    });
+    let deadline = Instant::now() + PIPE_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{} didn't finish within {}s", command, PIPE_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    if !status.success() {
+        bail!("{} exited with {}", command, status);
+    }
+    let output = reader.join().unwrap_or_default();
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+// Elements that are never the content: page furniture, scripts and forms
+static BOILERPLATE: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    ["head", "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "svg"]
+        .iter()
+        .map(|tag| Regex::new(&format!(r"(?is)<{tag}\b.*?</{tag}\s*>")).unwrap())
+        .collect()
+});
+// Preheader text and other hidden blocks, as long as they don't nest
+static HIDDEN: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    ["div", "span", "p", "td"]
+        .iter()
+        .map(|tag| Regex::new(&format!(r"(?is)<{tag}\b[^>]*display\s*:\s*none[^>]*>.*?</{tag}\s*>")).unwrap())
+        .collect()
+});
+static MAIN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<(?:article|main)\b[^>]*>(.*?)</(?:article|main)\s*>").unwrap());
+// Newsletters lay out with nested tables; as plain blocks they read top to bottom
+static LAYOUT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<(/?)(table|thead|tbody|tfoot|tr|td|th)\b[^>]*>").unwrap());
+static IMAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<img\b[^>]*>").unwrap());
+static IMAGE_SOURCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)<img\b[^>]*?\bsrc\s*=\s*["']?(https?://[^"'\s>]+)"#).unwrap());
+static BLANK_RUNS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n[ \t]*(\n[ \t]*)+\n").unwrap());
+static FOOTER_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)unsubscribe|manage (your )?(email )?(preferences|subscription)|you (are receiving|received) this|update your preferences")
+        .unwrap()
+});
+static BROWSER_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^\s*view (this (email|message) )?(in|on) (a |your )?(web )?browser").unwrap());
+// Footers sit at the end; a match earlier than this share of the text is probably content
+const FOOTER_REGION: f64 = 0.6;
+
+/// Reader mode: the article text of a newsletter without its boilerplate. Uses the HTML part
+/// when there is one, otherwise just trims the plain text's footer.
+pub fn reader(html: Option<&str>, plain: Option<&str>, width: usize) -> Option<String> {
+    match (html, plain) {
+        (Some(html), _) => Some(readable(html, width)),
+        (None, Some(plain)) => Some(strip_footer(plain)),
+        (None, None) => None,
+    }
+}
+
+// Drops a "view in browser" line at the top and the paragraph holding the unsubscribe
+// notice, with everything after it, at the bottom
+fn strip_footer(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    if lines.iter().take(3).any(|l| BROWSER_LINE.is_match(l)) {
+        let skip = lines.iter().position(|l| BROWSER_LINE.is_match(l)).map_or(0, |i| i + 1);
+        lines.drain(..skip);
+    }
+    let start = (lines.len() as f64 * FOOTER_REGION) as usize;
+    if let Some(found) = lines.iter().skip(start).position(|l| FOOTER_LINE.is_match(l)).map(|i| i + start) {
+        let paragraph = lines[..found].iter().rposition(|l| l.trim().is_empty()).unwrap_or(found);
+        lines.truncate(paragraph);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// A readability-style pass: keeps the article or main element when there is one, drops
+/// navigation, hidden preheaders, images and table layout, then renders without link
+/// footnotes.
+fn readable(html: &str, width: usize) -> String {
+    let mut html = html.to_string();
+    for pattern in BOILERPLATE.iter().chain(HIDDEN.iter()) {
+        html = pattern.replace_all(&html, "").into_owned();
+    }
+    if let Some(content) = MAIN.captures(&html).and_then(|c| c.get(1)) {
+        html = content.as_str().to_string();
+    }
+    html = IMAGE.replace_all(&html, "").into_owned();
+    html = LAYOUT.replace_all(&html, "<${1}div>").into_owned();
+    let text = html2text::from_read_with_decorator(
+        html.as_bytes(),
+        width,
+        html2text::render::text_renderer::TrivialDecorator::new(),
+    );
+    strip_footer(&BLANK_RUNS.replace_all(text.trim(), "\n\n"))
+}
+
+/// Remote images the HTML loads, in order and without repeats. Opening them is what tells a
+/// tracker the message was read, so they are only listed for senders allowed to.
+pub fn remote_images(html: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for url in IMAGE_SOURCE.captures_iter(html).filter_map(|c| c.get(1)) {
+        if !urls.iter().any(|u| u == url.as_str()) {
+            urls.push(url.as_str().to_string());
+        }
+    }
+    urls
+}
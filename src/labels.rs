@@ -0,0 +1,54 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::{bail, Result};
+use std::io::Write;
+
+const LIST_PAGE_SIZE: u32 = 500;
+
+// Label names are matched case-insensitively, like Gmail's own label: search operator;
+// system labels can also be given by id, e.g. STARRED
+async fn resolve(token: &ApiToken, label: &str) -> Result<google_api::LabelInfo> {
+    let labels = google_api::list_labels(token).await?;
+    match labels.into_iter().find(|l| l.id == label || l.name.eq_ignore_ascii_case(label)) {
+        Some(found) => Ok(found),
+        None => bail!("No label named '{}'", label),
+    }
+}
+
+async fn matching_ids(token: &ApiToken, query: &str) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    let mut params =
+        google_api::ListParams { query: Some(query.to_string()), max_results: Some(LIST_PAGE_SIZE), ..Default::default() };
+    loop {
+        let page = google_api::list_messages_page(token, &params).await?;
+        ids.extend(page.messages.unwrap_or_default().into_iter().map(|m| m.id));
+        eprint!("\rFound {} message(s)", ids.len());
+        params.page_token = page.next_page_token;
+        if params.page_token.is_none() {
+            eprintln!();
+            return Ok(ids);
+        }
+    }
+}
+
+/// Adds `label` to (or with `remove`, takes it off) every message matching `query`, in
+/// batches, printing progress to stderr. Returns how many messages were changed.
+pub async fn apply(token: &ApiToken, label: &str, query: &str, remove: bool, dry_run: bool) -> Result<usize> {
+    let label = resolve(token, label).await?;
+    let ids = matching_ids(token, query).await?;
+    if dry_run {
+        return Ok(ids.len());
+    }
+    let id = [label.id.as_str()];
+    let (add, take_off): (&[&str], &[&str]) = if remove { (&[], &id) } else { (&id, &[]) };
+    let mut done = 0;
+    for batch in ids.chunks(google_api::BATCH_MODIFY_LIMIT) {
+        google_api::batch_modify_labels(token, batch, add, take_off).await?;
+        done += batch.len();
+        eprint!("\rUpdated {}/{}", done, ids.len());
+        let _ = std::io::stderr().flush();
+    }
+    if done > 0 {
+        eprintln!();
+    }
+    Ok(done)
+}
@@ -0,0 +1,251 @@
+use crate::google_api::{self, ApiToken, Capability, LoopbackAuth};
+use crate::secure;
+use anyhow::{anyhow, bail, Context, Result};
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode},
+    execute,
+    style::{Attribute, Color as TermColor, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Terminal,
+};
+use std::io::stdout;
+use std::time::Duration;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const LINK_LABEL: &str = "» Open the Google authorization page";
+
+enum LoginState {
+    Waiting,
+    Succeeded,
+    Failed(String),
+}
+
+/// Returns the saved token, or runs the first-time authorization.
+pub async fn authenticate() -> Result<ApiToken> {
+    if let Some(token) = google_api::saved_token().await? {
+        return Ok(token);
+    }
+    sign_in().await
+}
+
+/// The saved token, extended through incremental consent when it doesn't cover `capability`
+/// yet. Signing in for the first time only asks for read access.
+pub async fn authorize_for(capability: Capability) -> Result<ApiToken> {
+    let token = authenticate().await?;
+    if token.has(capability) {
+        return Ok(token);
+    }
+    grant(capability).await
+}
+
+/// Signs in again asking for `capability` on top of what was granted before.
+pub async fn grant(capability: Capability) -> Result<ApiToken> {
+    if google_api::service_account().is_some() {
+        bail!("The service account's delegation doesn't cover {}", capability.describe());
+    }
+    google_api::request_capability(capability)?;
+    let token = sign_in().await?;
+    if !token.has(capability) {
+        bail!("Google didn't grant permission for {}", capability.describe());
+    }
+    Ok(token)
+}
+
+/// Runs the authorization on a full-screen page that stays visible until the browser redirect
+/// arrives, replacing any saved token.
+pub async fn sign_in() -> Result<ApiToken> {
+    let auth = LoopbackAuth::start().await?;
+    let url = auth.auth_url.to_string();
+    let forward = google_api::is_headless().then(|| auth.forward_command());
+    let mut pending = tokio::spawn(auth.finish());
+    let opened = google_api::open_in_browser(&url);
+
+    enable_raw_mode()?;
+    let _guard = ScreenGuard;
+    let mut stdout = stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut state = LoginState::Waiting;
+    let mut token = None;
+    let mut tick = 0usize;
+    loop {
+        let mut link_area = Rect::default();
+        terminal.draw(|f| link_area = draw(f, &url, forward.as_deref(), opened, &state, tick))?;
+        // ratatui has no hyperlink support, so the label it drew is overwritten in place with
+        // an OSC 8 link; terminals without OSC 8 just show the same text
+        execute!(
+            terminal.backend_mut(),
+            MoveTo(link_area.x, link_area.y),
+            SetForegroundColor(TermColor::Cyan),
+            SetAttribute(Attribute::Underlined),
+            Print(format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, LINK_LABEL)),
+            SetAttribute(Attribute::Reset),
+            ResetColor
+        )?;
+
+        match state {
+            LoginState::Waiting => {
+                if pending.is_finished() {
+                    match (&mut pending).await {
+                        Ok(Ok(t)) => {
+                            token = Some(t);
+                            state = LoginState::Succeeded;
+                        }
+                        Ok(Err(e)) => state = LoginState::Failed(format!("{:#}", e)),
+                        Err(e) => state = LoginState::Failed(e.to_string()),
+                    }
+                    continue;
+                }
+                if event::poll(Duration::from_millis(100))?
+                    && let Event::Key(key) = event::read()?
+                    && matches!(key.code, KeyCode::Esc | KeyCode::Char('q'))
+                {
+                    pending.abort();
+                    break Err(anyhow!("Authorization cancelled"));
+                }
+                tick += 1;
+            }
+            LoginState::Succeeded => {
+                tokio::time::sleep(Duration::from_millis(800)).await;
+                break token.take().ok_or_else(|| anyhow!("Authorization produced no token"));
+            }
+            LoginState::Failed(ref message) => {
+                if let Event::Key(_) = event::read()? {
+                    break Err(anyhow!("{}", message));
+                }
+            }
+        }
+    }
+}
+
+// Gives the terminal back however the sign-in page exits, including a draw or read error
+struct ScreenGuard;
+
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+// Returns where the link label was drawn so it can be turned into a hyperlink
+fn draw(f: &mut ratatui::Frame, url: &str, forward: Option<&str>, opened: bool, state: &LoginState, tick: usize) -> Rect {
+    let block = Block::default().borders(Borders::ALL).title("Sign in to Gmail");
+    let inner = block.inner(f.area());
+    f.render_widget(block, f.area());
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(2),
+            Constraint::Min(3),
+            Constraint::Length(3),
+        ])
+        .split(inner);
+
+    let intro = if opened {
+        "gmail-cli needs access to your mailbox. Your browser should have opened; if not, use this link:"
+    } else {
+        "gmail-cli needs access to your mailbox. Open this link in your browser:"
+    };
+    f.render_widget(Paragraph::new(intro).wrap(Wrap { trim: true }), chunks[0]);
+    f.render_widget(Paragraph::new(LINK_LABEL).fg(Color::Cyan).underlined(), chunks[1]);
+    let mut lines = vec![Line::from("Or copy the full URL:"), Line::from(url)];
+    // Over SSH the browser runs elsewhere and its redirect only arrives through a forwarded port
+    if let Some(forward) = forward {
+        lines.push(Line::from(""));
+        lines.push(Line::from("From a browser on another machine, forward the redirect port first:"));
+        lines.push(Line::from(format!("  {}", forward)));
+    }
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .style(Style::default().fg(Color::DarkGray)),
+        chunks[2],
+    );
+
+    let status = match state {
+        LoginState::Waiting => Paragraph::new(vec![
+            Line::from(format!("{} Waiting for the browser redirect…", SPINNER[tick % SPINNER.len()])),
+            Line::from("Press Esc to cancel.".dark_gray()),
+        ]),
+        LoginState::Succeeded => Paragraph::new("✓ Authorized. Loading your mail…").fg(Color::Green),
+        LoginState::Failed(message) => Paragraph::new(vec![
+            Line::from(format!("✗ {}", message).red()),
+            Line::from("Press any key to exit.".dark_gray()),
+        ])
+        .wrap(Wrap { trim: true }),
+    };
+    f.render_widget(status, chunks[3]);
+
+    Rect { width: (LINK_LABEL.chars().count() as u16).min(chunks[1].width), height: 1, ..chunks[1] }
+}
+
+/// `auth status`: which mailbox the saved token opens and how long the access token lasts.
+pub async fn print_status() -> Result<()> {
+    let Some(token) = google_api::saved_token().await? else {
+        println!("Not signed in. Run `gmail-cli auth login`.");
+        return Ok(());
+    };
+    // Goes first so an expired access token gets refreshed before tokeninfo sees it
+    let profile = google_api::get_profile(&token).await.context("The saved token was rejected")?;
+    let info = google_api::token_info(&token).await?;
+    println!("Signed in as {}", profile.email_address);
+    println!("Access token expires in {}s", info.expires_in);
+    if let Some(account) = google_api::service_account() {
+        println!("Service account {} impersonating {}", account.key_file.display(), account.subject);
+    } else {
+        println!(
+            "Refresh token: {}",
+            if token.refresh_token.is_some() { "saved" } else { "none (sign in again when it expires)" }
+        );
+    }
+    println!("Scopes: {}", info.scope);
+    Ok(())
+}
+
+/// `auth logout`: forgets the token locally; the grant stays valid at Google.
+pub fn logout() -> Result<()> {
+    if google_api::forget_token()? {
+        println!("Signed out; the saved token was deleted.");
+    } else {
+        println!("Not signed in.");
+    }
+    Ok(())
+}
+
+/// `auth secure`: tightens secret files written by older versions or copied in by hand.
+pub fn secure_files() -> Result<()> {
+    let fixed = secure::migrate()?;
+    if fixed.is_empty() {
+        println!("Saved secrets are already private.");
+    }
+    for path in fixed {
+        println!("Restricted {}", path.display());
+    }
+    Ok(())
+}
+
+/// `auth revoke`: invalidates the grant at Google, then forgets it locally.
+pub async fn revoke() -> Result<()> {
+    let Some(token) = google_api::saved_token().await? else {
+        println!("Not signed in; nothing to revoke.");
+        return Ok(());
+    };
+    google_api::revoke_token(&token).await?;
+    google_api::forget_token()?;
+    println!("Access revoked and the saved token deleted.");
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::collections::{HashMap, VecDeque};
+
+/// A small least-recently-used map keyed by message id. Lookups are linear in the number of
+/// entries, which is fine at the few hundred a session holds.
+pub struct LruCache<V> {
+    capacity: usize,
+    entries: HashMap<String, V>,
+    // Front is least recently used
+    order: VecDeque<String>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(position) = self.order.iter().position(|k| k == key)
+            && let Some(k) = self.order.remove(position)
+        {
+            self.order.push_back(k);
+        }
+    }
+
+    /// Returns a copy of the entry and marks it most recently used; counts the hit or miss.
+    pub fn get(&mut self, key: &str) -> Option<V> {
+        match self.entries.get(key).cloned() {
+            Some(value) => {
+                self.hits += 1;
+                self.touch(key);
+                Some(value)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, value: V) {
+        if self.entries.insert(key.to_string(), value).is_some() {
+            self.touch(key);
+            return;
+        }
+        self.order.push_back(key.to_string());
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+}
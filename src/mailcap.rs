@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+const SYSTEM_MAILCAP_PATHS: [&str; 2] = ["/etc/mailcap", "/usr/local/etc/mailcap"];
+const VIEWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct MailcapEntry {
+    pub mime_type: String,
+    pub command: String,
+    pub copious_output: bool,
+    pub test: Option<String>,
+    pub name_template: Option<String>,
+}
+
+impl MailcapEntry {
+    fn matches(&self, mime_type: &str) -> bool {
+        let entry_type = self.mime_type.to_ascii_lowercase();
+        let mime_type = mime_type.to_ascii_lowercase();
+        if entry_type == mime_type {
+            return true;
+        }
+        // "image/*" and a bare "image" both act as wildcards for the subtype
+        let major = mime_type.split('/').next().unwrap_or("");
+        entry_type == major || entry_type == format!("{}/*", major)
+    }
+
+    fn passes_test(&self) -> bool {
+        match &self.test {
+            Some(test) => Command::new("sh")
+                .arg("-c")
+                .arg(test)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()
+                .and_then(|mut child| wait_with_timeout(&mut child).ok())
+                .is_some_and(|s| s.success()),
+            None => true,
+        }
+    }
+
+    /// Runs a `copiousoutput` viewer over `data` and returns its stdout for display in the pager.
+    pub fn render(&self, data: &[u8]) -> Result<String> {
+        let uses_file = self.command.contains("%s");
+        // Removed when dropped, after the viewer exits
+        let temp_file = if uses_file { Some(self.temp_file(data)?) } else { None };
+        let temp_path = temp_file.as_ref().map(|file| file.path().to_string_lossy().into_owned()).unwrap_or_default();
+        let command = self
+            .command
+            .replace("%s", &shell_quote(&temp_path))
+            .replace("%t", &shell_quote(&self.mime_type));
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .stdin(if uses_file { Stdio::null() } else { Stdio::piped() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run mailcap viewer: {}", command))?;
+
+        // Fed and drained on their own threads so a viewer that writes before it has read
+        // everything can't fill one pipe while we block on the other
+        if let Some(mut stdin) = child.stdin.take() {
+            let data = data.to_vec();
+            std::thread::spawn(move || {
+                // The viewer may exit without reading everything; that's not an error for us
+                let _ = stdin.write_all(&data);
+            });
+        }
+        let mut stdout = child.stdout.take().context("No output from the mailcap viewer")?;
+        let reader = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = stdout.read_to_end(&mut output);
+            output
+        });
+        wait_with_timeout(&mut child).with_context(|| format!("Mailcap viewer failed: {}", command))?;
+        let output = reader.join().unwrap_or_default();
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    // Created exclusively and owner-only under a random name, never through an existing file or
+    // symlink; nametemplate= still supplies the extension some viewers go by
+    fn temp_file(&self, data: &[u8]) -> Result<tempfile::NamedTempFile> {
+        let (before, after) = self.name_template.as_deref().and_then(|t| t.split_once("%s")).unwrap_or_default();
+        let mut file = tempfile::Builder::new()
+            .prefix(&format!("{}gmail-cli-", before))
+            .suffix(after)
+            .tempfile()
+            .context("Failed to create a file for the mailcap viewer")?;
+        file.write_all(data)?;
+        file.flush()?;
+        Ok(file)
+    }
+}
+
+// Kills the command if it hasn't exited by the deadline, so a stuck viewer or test can't hold up
+// the pager
+fn wait_with_timeout(child: &mut Child) -> Result<ExitStatus> {
+    let deadline = Instant::now() + VIEWER_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("didn't finish within {}s", VIEWER_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn mailcap_paths() -> Vec<PathBuf> {
+    if let Ok(paths) = std::env::var("MAILCAPS") {
+        return paths.split(':').filter(|p| !p.is_empty()).map(PathBuf::from).collect();
+    }
+    let mut paths = Vec::new();
+    if let Some(home) = home_dir() {
+        paths.push(home.join(".mailcap"));
+    }
+    paths.extend(SYSTEM_MAILCAP_PATHS.iter().map(PathBuf::from));
+    paths
+}
+
+/// Splits on unescaped `;`, honouring `\;` inside commands.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                current.push(';');
+                chars.next();
+            }
+            ';' => fields.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current.trim().to_string());
+    fields
+}
+
+fn parse_entry(line: &str) -> Option<MailcapEntry> {
+    let fields = split_fields(line);
+    if fields.len() < 2 || fields[0].is_empty() {
+        return None;
+    }
+    let mut entry = MailcapEntry {
+        mime_type: fields[0].clone(),
+        command: fields[1].clone(),
+        copious_output: false,
+        test: None,
+        name_template: None,
+    };
+    for field in &fields[2..] {
+        let (key, value) = match field.split_once('=') {
+            Some((k, v)) => (k.trim().to_ascii_lowercase(), Some(v.trim().to_string())),
+            None => (field.to_ascii_lowercase(), None),
+        };
+        match key.as_str() {
+            "copiousoutput" => entry.copious_output = true,
+            "test" => entry.test = value,
+            "nametemplate" => entry.name_template = value,
+            _ => {}
+        }
+    }
+    Some(entry)
+}
+
+pub fn parse(content: &str) -> Vec<MailcapEntry> {
+    let mut entries = Vec::new();
+    let mut logical_line = String::new();
+    for raw_line in content.lines() {
+        if let Some(continued) = raw_line.strip_suffix('\\') {
+            logical_line.push_str(continued);
+            continue;
+        }
+        logical_line.push_str(raw_line);
+        let line = std::mem::take(&mut logical_line);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(entry) = parse_entry(line) {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+pub fn load_entries() -> Vec<MailcapEntry> {
+    mailcap_paths()
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|content| parse(&content))
+        .collect()
+}
+
+/// Returns the first entry for `mime_type` whose `test=` command (if any) succeeds.
+pub fn find_entry(entries: &[MailcapEntry], mime_type: &str, copious_only: bool) -> Option<MailcapEntry> {
+    entries
+        .iter()
+        .filter(|e| e.matches(mime_type))
+        .filter(|e| !copious_only || e.copious_output)
+        .find(|e| e.passes_test())
+        .cloned()
+}
@@ -1,38 +1,809 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::PathBuf;
+mod actions;
+mod address;
+mod attachments;
+mod audit;
+mod bounces;
+mod cache;
+mod caution;
+mod classifier;
+mod compose;
+mod config;
+mod conversation;
+mod counts;
+mod daemon;
+mod dates;
+mod deeplink;
+mod digest;
+mod doctor;
+mod drafts;
+mod google_api;
+mod heatmap;
+mod html;
+mod labels;
+mod login;
+mod lru;
+mod mailcap;
+mod merge;
+mod mime;
+mod needs_reply;
+mod notify;
+mod offline;
+mod outbox;
+mod paths;
+mod pdf;
+mod plugins;
+mod privacy;
+mod redact;
+mod reminders;
+mod scripting;
+mod secure;
+mod send_as;
+mod senders;
+mod settings;
+mod speech;
+mod stats;
+mod store;
+mod text;
+mod threading;
+mod timeline;
+mod timings;
+mod translate;
+mod trash;
 mod tui;
-mod google_api; // Add this line
+mod update;
+mod vault;
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Use a separate named account, with its own token, state and cache
+    #[arg(long, global = true)]
+    account: Option<String>,
+    /// Never ask for more than the gmail.readonly scope and disable every action that changes mail
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// How to render HTML-only mail for this run, overriding html.engine and html.senders
+    #[arg(long, global = true, value_enum)]
+    html_engine: Option<config::HtmlEngine>,
+    /// How to authenticate: the saved OAuth token, or a Workspace service account
+    #[arg(long, global = true, value_enum, default_value_t = AuthBackend::Oauth)]
+    auth: AuthBackend,
+    /// Mailbox a service account acts as (overrides service_account.subject)
+    #[arg(long, global = true)]
+    impersonate: Option<String>,
+    /// Print the authorization URL instead of opening it in a browser
+    #[arg(long, global = true)]
+    no_browser: bool,
+    /// Print how long each startup step took when the TUI exits
+    #[arg(long, global = true)]
+    timings: bool,
     #[command(subcommand)]
-    command: Commands,
+    command: Command,
+}
+
+// Doctor runs before the config is loaded, so it can report a broken config file; everything
+// else needs the config
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(flatten)]
+    Mail(Commands),
+    /// Check config, authorization, connectivity, local state and the terminal
+    Doctor,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    List {
+        /// Only show mail on or after this date (YYYY, YYYY-MM or YYYY-MM-DD)
+        #[arg(long)]
+        after: Option<String>,
+        /// Only show mail before this date (YYYY, YYYY-MM or YYYY-MM-DD)
+        #[arg(long)]
+        before: Option<String>,
+        /// View to open: inbox, unread, sent, outbox, archive, all, trash, needs-reply, unified, drafts,
+        /// awaiting-me, awaiting-others, last or search:<name>
+        #[arg(long)]
+        view: Option<String>,
+    },
+    /// Open a message by id, Message-ID or Gmail web link straight in the viewer
+    Open {
+        /// Hex message or thread id, <Message-ID>, or a mail.google.com link
+        target: String,
+        /// Print the message instead of starting the TUI
+        #[arg(long)]
+        no_tui: bool,
+    },
+    /// Send a message described by a file with a YAML/JSON front-matter block
+    Send {
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// Send one personalized message per CSV row from a front-matter template
+    Merge {
+        #[arg(long)]
+        template: PathBuf,
+        #[arg(long)]
+        data: PathBuf,
+        /// Print the rendered messages without sending anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Pause between sends to stay under Gmail's rate limits
+        #[arg(long, default_value_t = 2000)]
+        delay_ms: u64,
+        /// Where sent rows are recorded (defaults to <data>.progress)
+        #[arg(long)]
+        progress_log: Option<PathBuf>,
+    },
+    /// List delivery-failure reports for recently sent mail (exits 1 if any are found)
+    Bounces {
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Poll in the background and raise desktop notifications for new mail and watched threads
+    Daemon {
+        #[arg(long, default_value_t = 60)]
+        interval_secs: u64,
+        /// Only alert for watched threads, not for every new inbox message
+        #[arg(long)]
+        watched_only: bool,
+    },
+    /// Summarize read-tracking vendors found in locally cached messages
+    Trackers,
+    /// Reports computed from locally cached messages
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommand,
+    },
+    /// Show received mail per day from the local cache as an activity grid
+    Heatmap {
+        #[arg(long, default_value_t = 26)]
+        weeks: usize,
+    },
+    /// List messages with a respond-by deadline, soonest first
+    Due,
+    /// Save the attachments of every message matching a Gmail query, skipping duplicate content
+    DownloadAttachments {
+        query: String,
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// Hard-link duplicates under their own name instead of skipping them
+        #[arg(long)]
+        link: bool,
+    },
+    /// Render a whole thread, headers and bodies, to a PDF file
+    ExportPdf {
+        thread_id: String,
+        #[arg(long)]
+        out: PathBuf,
+        /// Replace email addresses, phone numbers and [redact] patterns, for sharing
+        #[arg(long)]
+        redact: bool,
+    },
+    /// Write a Markdown report of recent mail grouped by label and sender
+    Digest {
+        /// How far back to look, e.g. 7d or 2w
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Defaults to standard output
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Permanently delete mail that has been in Trash for a while, e.g. from cron
+    Purge {
+        /// Defaults to trash.purge_after_days from the config
+        #[arg(long)]
+        older_than_days: Option<u32>,
+        /// List what would be deleted without deleting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Permanently delete messages that are already in Trash. This can't be undone
+    Delete {
+        /// Message ids, as shown in the Trash view or by `open`
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Required: confirms the messages should be deleted for good
+        #[arg(long)]
+        force: bool,
+    },
+    /// List, send or delete Gmail drafts; save one with `draft: true` in the front matter
+    Drafts {
+        #[command(subcommand)]
+        command: DraftsCommand,
+    },
+    /// Audit account settings: forwarding, IMAP/POP access and display language
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand,
+    },
+    /// List forwarding addresses and turn auto-forwarding on or off. Changes need a Workspace
+    /// service account (--auth service-account) delegated the gmail.settings.sharing scope
+    Forwarding {
+        #[command(subcommand)]
+        command: ForwardingCommand,
+    },
+    /// Add or remove a label on every message matching a Gmail query
+    Labels {
+        #[command(subcommand)]
+        command: LabelsCommand,
+    },
+    /// Sign in, check or remove the saved authorization
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+    /// Inspect the log of sends and label changes made from this machine
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+    /// Any other name runs a `gmail-cli-<name>` plugin from PATH
+    #[command(external_subcommand)]
+    External(Vec<String>),
+    /// Replace this binary with the latest verified GitHub release
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum AuthBackend {
+    Oauth,
+    ServiceAccount,
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthCommand {
+    /// Authorize in the browser, replacing any saved token. Only read access is asked for;
+    /// changing and sending mail ask for their permission when first used
+    Login {
+        /// Sign in on a machine without a browser, such as over SSH: forward the printed redirect
+        /// port with `ssh -L` and open the link on your own machine, or paste the code back.
+        /// Google doesn't allow Gmail access through the short-code device flow
+        #[arg(long)]
+        headless: bool,
+    },
+    /// Show the signed-in address and when the access token expires
+    Status,
+    /// Delete the saved token without revoking it
+    Logout,
+    /// Revoke this app's access at Google and delete the saved token
+    Revoke,
+    /// Make saved tokens, client credentials and cached mail readable by you alone
+    Secure,
+}
+
+#[derive(Subcommand, Debug)]
+enum DraftsCommand {
+    /// Show draft ids with their recipients and subjects, newest first
+    List {
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
+    /// Send a draft as it is
+    Send { id: String },
+    Delete { id: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum SettingsCommand {
+    /// Show forwarding addresses, auto-forwarding, IMAP/POP status and display language
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum ForwardingCommand {
+    /// Show forwarding addresses and their verification status; * marks the one in use
     List,
+    /// Forward new mail to an address, adding it (and starting its verification) if needed
+    Enable {
+        address: String,
+        /// What to do with Gmail's own copy
+        #[arg(long, value_enum, default_value_t = settings::Disposition::LeaveInInbox)]
+        then: settings::Disposition,
+    },
+    /// Stop forwarding; the addresses stay registered
+    Disable,
+}
+
+#[derive(Subcommand, Debug)]
+enum LabelsCommand {
+    /// Add LABEL to every matching message
+    Apply {
+        /// Label name (case-insensitive) or id, e.g. STARRED
+        label: String,
+        /// Gmail search query selecting the messages, e.g. "from:alerts@example.com"
+        #[arg(long)]
+        query: String,
+        /// Only count the matching messages
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove LABEL from every matching message
+    Remove {
+        label: String,
+        #[arg(long)]
+        query: String,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditCommand {
+    /// Show the most recent entries
+    Tail {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsCommand {
+    /// Median reply times per correspondent, mine to them and theirs to me
+    Responsiveness {
+        #[arg(long, default_value_t = 30)]
+        limit: usize,
+    },
+}
+
+async fn authenticate() -> Option<google_api::ApiToken> {
+    match google_api::get_auth_token().await {
+        Ok(token) => Some(token),
+        Err(e) => {
+            eprintln!("Authentication error: {:?}", e);
+            None
+        }
+    }
+}
+
+// For commands that change or send mail: asks for the extra permission the first time
+async fn authorize(capability: google_api::Capability) -> Option<google_api::ApiToken> {
+    match login::authorize_for(capability).await {
+        Ok(token) => Some(token),
+        Err(e) => {
+            eprintln!("Authorization error: {:?}", e);
+            None
+        }
+    }
+}
+
+fn print_timings(enabled: bool) {
+    if enabled {
+        for line in timings::lines() {
+            eprintln!("{}", line);
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
-    
-    match args.command {
-        Commands::List => {
-            // auth token
-            let auth_token = match google_api::get_auth_token().await {
+    timings::start();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // For the per-command network settings in [network.commands]
+    let command_name = matches.subcommand_name().map(str::to_string);
+    if let Some(account) = &args.account
+        && let Err(e) = store::set_account(Some(account))
+    {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+    if args.read_only {
+        google_api::request_read_only();
+    }
+    if args.no_browser {
+        google_api::disable_browser();
+    }
+    let command = match args.command {
+        Command::Mail(command) => command,
+        Command::Doctor => {
+            if !doctor::run().await {
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+    let mut config = match config::Config::load() {
+        Ok(config) => {
+            paths::configure(&config.paths);
+            google_api::encrypt_saved_tokens(config.security.encrypt_token);
+            config
+        }
+        Err(e) => {
+            eprintln!("Config error: {:?}", e);
+            return;
+        }
+    };
+    html::configure(&config.html, args.html_engine);
+    if let Err(e) = google_api::configure_network(&config.network.for_command(command_name.as_deref())) {
+        eprintln!("Config error: {:?}", e);
+        return;
+    }
+    if args.auth == AuthBackend::ServiceAccount {
+        let key_file = config
+            .service_account
+            .key_file
+            .clone()
+            .or_else(|| std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from));
+        let subject = args.impersonate.clone().or_else(|| config.service_account.subject.clone());
+        let (Some(key_file), Some(subject)) = (key_file, subject) else {
+            eprintln!(
+                "--auth service-account needs a key file (service_account.key_file or \
+                 $GOOGLE_APPLICATION_CREDENTIALS) and a mailbox (--impersonate or service_account.subject)"
+            );
+            std::process::exit(2);
+        };
+        google_api::use_service_account(google_api::ServiceAccount { key_file, subject });
+    }
+
+    match command {
+        Commands::List { after, before, view } => {
+            let parse = |value: Option<String>| value.map(|v| dates::parse_date(&v)).transpose();
+            let date_range = match (parse(after), parse(before)) {
+                (Ok(after), Ok(before)) => dates::DateRange { after, before },
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("Invalid date: {:?}", e);
+                    return;
+                }
+            };
+            // Held for the whole session so a second TUI can't interleave writes to the state files
+            let lock = match store::lock(tui::LOCK_FILE) {
+                Ok(lock) => lock,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            // First-time authorization runs in its own screen rather than scrolling away
+            let began = std::time::Instant::now();
+            let auth_token = match login::authenticate().await {
                 Ok(token) => token,
                 Err(e) => {
                     eprintln!("Authentication error: {:?}", e);
                     return;
                 }
             };
-            
-            
-            if let Err(e) = tui::run(auth_token).await {
+            timings::record(timings::AUTH, began);
+            if let Some(view) = view {
+                config.ui.startup = view;
+            }
+            if let Err(e) = tui::run(auth_token, lock, date_range, config, None).await {
+                eprintln!("TUI error: {:?}", e);
+            }
+            print_timings(args.timings);
+        }
+        Commands::Open { target, no_tui } => {
+            let began = std::time::Instant::now();
+            let Some(auth_token) = authenticate().await else { return };
+            timings::record(timings::AUTH, began);
+            let detail = match deeplink::resolve(&auth_token, &target).await {
+                Ok(detail) => detail,
+                Err(e) => {
+                    eprintln!("Open error: {:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            if no_tui {
+                deeplink::print(&detail);
+                return;
+            }
+            let query = match deeplink::query_for(&detail) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("{:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            let lock = match store::lock(tui::LOCK_FILE) {
+                Ok(lock) => lock,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = tui::run(auth_token, lock, dates::DateRange::default(), config, Some(query)).await {
                 eprintln!("TUI error: {:?}", e);
             }
+            print_timings(args.timings);
+        }
+        Commands::Send { file } => {
+            let Some(auth_token) = authorize(google_api::Capability::Send).await else { return };
+            let message = match compose::load_message_file(&file, &config.compose) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("Message file error: {:?}", e);
+                    return;
+                }
+            };
+            let report = match outbox::send_or_queue(&auth_token, &message).await {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Send error: {:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            report.warnings.iter().for_each(|w| eprintln!("Warning: {}", w));
+            match report.outcome {
+                outbox::SendOutcome::Sent(sent) => println!("Sent message {}", sent.id),
+                outbox::SendOutcome::Queued(id) => {
+                    println!("Network unavailable; queued as {} in the outbox for automatic retry", id)
+                }
+                outbox::SendOutcome::Drafted(id) => println!("Saved draft {}", id),
+            }
+        }
+        Commands::Merge { template, data, dry_run, delay_ms, progress_log } => {
+            let options = merge::MergeOptions {
+                template,
+                data,
+                delay: std::time::Duration::from_millis(delay_ms),
+                progress_log,
+                compose: config.compose.clone(),
+            };
+            let result = if dry_run {
+                merge::preview(&options)
+            } else {
+                let Some(auth_token) = authorize(google_api::Capability::Send).await else { return };
+                merge::run(&auth_token, options).await
+            };
+            if let Err(e) = result {
+                eprintln!("Merge error: {:?}", e);
+            }
+        }
+        Commands::Bounces { days } => {
+            let Some(auth_token) = authenticate().await else { return };
+            match bounces::detect(&auth_token, days).await {
+                Ok(found) => {
+                    bounces::print_report(&found);
+                    if !found.is_empty() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => eprintln!("Bounce detection error: {:?}", e),
+            }
+        }
+        Commands::Daemon { interval_secs, watched_only } => {
+            // Purging runs unattended, so its permission has to be granted up front
+            let capability = match config.trash.purge_after_days {
+                Some(_) => google_api::Capability::Delete,
+                None => google_api::Capability::Read,
+            };
+            let Some(auth_token) = authorize(capability).await else { return };
+            let interval = std::time::Duration::from_secs(interval_secs);
+            if let Err(e) = daemon::run(&auth_token, interval, config.inbox_query(), !watched_only, config.trash.purge_after_days).await {
+                eprintln!("Daemon error: {:?}", e);
+            }
+        }
+        Commands::DownloadAttachments { query, dir, link } => {
+            let Some(auth_token) = authenticate().await else { return };
+            match attachments::download(&auth_token, &query, &dir, link).await {
+                Ok(summary) => println!(
+                    "Saved {} attachment(s), {} duplicate(s) {}",
+                    summary.saved,
+                    summary.duplicates,
+                    if link { "linked" } else { "skipped" }
+                ),
+                Err(e) => {
+                    eprintln!("Download error: {:?}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ExportPdf { thread_id, out, redact } => {
+            let redactor = match redact.then(|| redact::Redactor::new(&config.redact)).transpose() {
+                Ok(redactor) => redactor,
+                Err(e) => {
+                    eprintln!("Config error: {:?}", e);
+                    std::process::exit(2);
+                }
+            };
+            let Some(auth_token) = authenticate().await else { return };
+            match pdf::export_thread(&auth_token, &thread_id, &out, redactor).await {
+                Ok(count) => println!("Wrote {} message(s) to {}", count, out.display()),
+                Err(e) => {
+                    eprintln!("Export error: {:?}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Digest { since, out } => {
+            let days = match dates::parse_days(&since) {
+                Ok(days) => days,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            };
+            let Some(auth_token) = authenticate().await else { return };
+            let report = match digest::generate(&auth_token, days).await {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Digest error: {:?}", e);
+                    std::process::exit(1);
+                }
+            };
+            match out {
+                Some(path) => match std::fs::write(&path, report) {
+                    Ok(()) => println!("Wrote digest to {}", path.display()),
+                    Err(e) => {
+                        eprintln!("Failed to write {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }
+                },
+                None => print!("{}", report),
+            }
+        }
+        Commands::Purge { older_than_days, dry_run } => {
+            let Some(days) = older_than_days.or(config.trash.purge_after_days) else {
+                eprintln!("Pass --older-than-days or set purge_after_days under [trash] in the config");
+                std::process::exit(2);
+            };
+            let capability = if dry_run { google_api::Capability::Read } else { google_api::Capability::Delete };
+            let Some(auth_token) = authorize(capability).await else { return };
+            match trash::purge(&auth_token, days, dry_run).await {
+                Ok(count) if dry_run => println!("{} message(s) would be deleted", count),
+                Ok(count) => println!("Permanently deleted {} message(s)", count),
+                Err(e) => {
+                    eprintln!("Purge error: {:?}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Delete { ids, force } => {
+            if !force {
+                eprintln!("Permanent deletion can't be undone; pass --force to delete {} message(s)", ids.len());
+                std::process::exit(2);
+            }
+            let Some(auth_token) = authorize(google_api::Capability::Delete).await else { return };
+            let mut failed = false;
+            for id in &ids {
+                match trash::delete_trashed(&auth_token, id).await {
+                    Ok(subject) => println!("Deleted {}  {}", id, subject),
+                    Err(e) => {
+                        eprintln!("Failed to delete {}: {:#}", id, e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Commands::Drafts { command } => {
+            let Some(auth_token) = authorize(google_api::Capability::Modify).await else { return };
+            let result = match command {
+                DraftsCommand::List { limit } => drafts::print_list(&auth_token, limit).await,
+                DraftsCommand::Send { id } => {
+                    google_api::send_draft(&auth_token, &id).await.map(|sent| println!("Sent message {}", sent.id))
+                }
+                DraftsCommand::Delete { id } => {
+                    google_api::delete_draft(&auth_token, &id).await.map(|_| println!("Deleted draft {}", id))
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Drafts error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Settings { command: SettingsCommand::Show } => {
+            // The settings getters accept gmail.readonly; changing settings would need
+            // gmail.settings.basic, which is never asked for
+            let Some(auth_token) = authorize(google_api::Capability::Read).await else { return };
+            if let Err(e) = settings::show(&auth_token).await {
+                eprintln!("Settings error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Forwarding { command } => {
+            let capability = match command {
+                ForwardingCommand::List => google_api::Capability::Read,
+                ForwardingCommand::Enable { .. } | ForwardingCommand::Disable => google_api::Capability::Forwarding,
+            };
+            if capability == google_api::Capability::Forwarding {
+                // Google only lets the gmail.settings.sharing scope be used through delegation;
+                // asking a personal sign-in for it would grant a scope the API then rejects
+                if google_api::service_account().is_none() {
+                    eprintln!(
+                        "Changing forwarding needs a Workspace service account delegated the gmail.settings.sharing \
+                         scope; run it with --auth service-account"
+                    );
+                    std::process::exit(2);
+                }
+                // A service account's token can't be extended by consent later, so it asks up front
+                if let Err(e) = google_api::request_capability(capability) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            let Some(auth_token) = authorize(capability).await else { return };
+            let result = match command {
+                ForwardingCommand::List => settings::list_forwarding(&auth_token).await,
+                ForwardingCommand::Enable { address, then } => settings::enable_forwarding(&auth_token, &address, then).await,
+                ForwardingCommand::Disable => settings::disable_forwarding(&auth_token).await,
+            };
+            if let Err(e) = result {
+                eprintln!("Forwarding error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Labels { command } => {
+            let (label, query, remove, dry_run) = match command {
+                LabelsCommand::Apply { label, query, dry_run } => (label, query, false, dry_run),
+                LabelsCommand::Remove { label, query, dry_run } => (label, query, true, dry_run),
+            };
+            let capability = if dry_run { google_api::Capability::Read } else { google_api::Capability::Modify };
+            let Some(auth_token) = authorize(capability).await else { return };
+            match labels::apply(&auth_token, &label, &query, remove, dry_run).await {
+                Ok(count) if dry_run => println!("{} message(s) match", count),
+                Ok(count) if remove => println!("Removed {} from {} message(s)", label, count),
+                Ok(count) => println!("Labelled {} message(s) {}", count, label),
+                Err(e) => {
+                    eprintln!("Label error: {:?}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Stats { command: StatsCommand::Responsiveness { limit } } => {
+            stats::print_responsiveness(&cache::cached_messages(), limit);
+        }
+        Commands::Heatmap { weeks } => {
+            let counts = heatmap::daily_counts(&cache::cached_messages());
+            for line in heatmap::render(&counts, weeks, chrono::Local::now().date_naive()) {
+                println!("{}", line);
+            }
+        }
+        Commands::Trackers => {
+            privacy::print_vendor_report(&cache::cached_messages());
+        }
+        Commands::SelfUpdate { check } => {
+            if let Err(e) = update::run(check).await {
+                eprintln!("Update error: {:?}", e);
+            }
+        }
+        Commands::Due => reminders::print_due(&reminders::Deadlines::load()),
+        Commands::Auth { command } => {
+            let result = match command {
+                AuthCommand::Login { headless } => {
+                    let token = if headless { google_api::sign_in_headless().await } else { login::sign_in().await };
+                    match token {
+                        Ok(token) => google_api::get_profile(&token)
+                            .await
+                            .map(|profile| println!("Signed in as {}", profile.email_address)),
+                        Err(e) => Err(e),
+                    }
+                }
+                AuthCommand::Status => login::print_status().await,
+                AuthCommand::Logout => login::logout(),
+                AuthCommand::Revoke => login::revoke().await,
+                AuthCommand::Secure => login::secure_files(),
+            };
+            if let Err(e) = result {
+                eprintln!("Auth error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Audit { command: AuditCommand::Tail { lines } } => {
+            if let Err(e) = audit::print_tail(lines) {
+                eprintln!("Audit error: {:?}", e);
+            }
+        }
+        Commands::External(args) => {
+            let Some((name, args)) = args.split_first() else { return };
+            if plugins::find(name).is_none() {
+                eprintln!("Unknown command '{}' (no {}{} found on PATH)", name, plugins::PREFIX, name);
+                std::process::exit(2);
+            }
+            let Some(auth_token) = authenticate().await else { return };
+            let context = plugins::PluginContext::new(&auth_token, None, args.to_vec()).await;
+            match plugins::run(name, &context).await {
+                Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+                Err(e) => eprintln!("Plugin error: {:?}", e),
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,329 @@
+use crate::compose;
+use crate::config::ComposeConfig;
+use crate::google_api::{self, ApiToken};
+use crate::mime::OutgoingMessage;
+use crate::send_as;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct MergeOptions {
+    pub template: PathBuf,
+    pub data: PathBuf,
+    pub delay: Duration,
+    pub progress_log: Option<PathBuf>,
+    pub compose: ComposeConfig,
+}
+
+// Byte length of the YAML or JSON front-matter block at the start of the template
+fn front_matter_len(template: &str) -> usize {
+    if template.starts_with("---") {
+        let mut offset = 0;
+        for (i, line) in template.split_inclusive('\n').enumerate() {
+            offset += line.len();
+            if i > 0 && line.trim_end() == "---" {
+                return offset;
+            }
+        }
+        return template.len();
+    }
+    if !template.starts_with('{') {
+        return 0;
+    }
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for (i, c) in template.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    template.len()
+}
+
+// A plain YAML value with a placeholder among other text, `subject: Hi {{ name }}`, is turned
+// into a double-quoted one first; unquoted, a `#` in the CSV value would start a comment and an
+// escaped line break would stay a literal `\n`. JSON escaping is valid inside YAML double quotes.
+fn quote_plain_scalars(template: &str) -> String {
+    if !template.starts_with("---") {
+        return template.to_string();
+    }
+    let end = front_matter_len(template);
+    let mut quoted = String::with_capacity(template.len());
+    for line in template[..end].split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let value = body.split_once(':').map(|(_, value)| value.trim()).unwrap_or_default();
+        let whole_placeholder = value.starts_with("{{") && value.find("}}") == Some(value.len() - 2);
+        if !value.contains("{{") || whole_placeholder || value.starts_with(['"', '\'']) {
+            quoted.push_str(line);
+            continue;
+        }
+        let key_end = body.len() - body.split_once(':').map_or(0, |(_, v)| v.len());
+        quoted.push_str(&body[..key_end]);
+        quoted.push_str(" \"");
+        let mut rest = value;
+        while let Some(start) = rest.find("{{") {
+            let Some(close) = rest[start..].find("}}").map(|i| start + i + 2) else { break };
+            quoted.push_str(&json_escaped(&rest[..start]));
+            quoted.push_str(&rest[start..close]);
+            rest = &rest[close..];
+        }
+        quoted.push_str(&json_escaped(rest));
+        quoted.push('"');
+        quoted.push_str(&line[body.len()..]);
+    }
+    quoted.push_str(&template[end..]);
+    quoted
+}
+
+fn json_escaped(text: &str) -> String {
+    let quoted = serde_json::Value::String(text.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+// A CSV value for the front matter, where a line break or quote could otherwise add or change
+// headers. Inside a quoted string it is escaped in place; as a whole `key: {{ x }}` value it is
+// quoted, which YAML and JSON read alike.
+fn front_matter_value(value: &str, line_before: &str, line_after: &str) -> Result<String> {
+    let quoted = serde_json::to_string(value)?;
+    let inside_string = line_before.chars().filter(|&c| c == '"').count() % 2 == 1;
+    let whole_value = line_before.trim_end().ends_with(':') && line_after.trim().is_empty();
+    Ok(if whole_value && !inside_string { quoted } else { json_escaped(value) })
+}
+
+// Replaces `{{ column }}` placeholders; an unknown column is an error so typos don't go out
+pub fn render_template(template: &str, row: &HashMap<String, String>) -> Result<String> {
+    let template = &quote_plain_scalars(template);
+    let front_matter_end = front_matter_len(template);
+    let mut rendered = String::with_capacity(template.len());
+    let mut position = 0;
+    while let Some(start) = template[position..].find("{{").map(|i| position + i) {
+        rendered.push_str(&template[position..start]);
+        let after_open = &template[start + 2..];
+        let end = after_open
+            .find("}}")
+            .context("Unterminated '{{' placeholder in template")?;
+        let key = after_open[..end].trim();
+        let Some(value) = row.get(key) else {
+            bail!("Template placeholder '{{{{{}}}}}' has no matching CSV column", key);
+        };
+        position = start + 2 + end + 2;
+        if start < front_matter_end {
+            let line_before = &template[template[..start].rfind('\n').map_or(0, |i| i + 1)..start];
+            let line_after = template[position..].lines().next().unwrap_or_default();
+            rendered.push_str(&front_matter_value(value, line_before, line_after)?);
+        } else {
+            rendered.push_str(value);
+        }
+    }
+    rendered.push_str(&template[position..]);
+    Ok(rendered)
+}
+
+fn read_rows(path: &Path) -> Result<Vec<HashMap<String, String>>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to open data file {}", path.display()))?;
+    let headers: Vec<String> = reader.headers()?.iter().map(|h| h.trim().to_string()).collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let row = headers
+            .iter()
+            .cloned()
+            .zip(record.iter().map(str::to_string))
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn default_progress_log(data: &Path) -> PathBuf {
+    let mut name = data.file_name().unwrap_or_default().to_os_string();
+    name.push(".progress");
+    data.with_file_name(name)
+}
+
+// Progress lines are "<row>\t<recipients>\t<message id>". Rows are matched by recipients, not
+// position, so editing the CSV between runs doesn't skip or repeat the wrong people.
+fn read_completed(path: &Path) -> HashSet<String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1).map(str::to_string))
+        .collect()
+}
+
+fn render_all(options: &MergeOptions) -> Result<Vec<OutgoingMessage>> {
+    let template = fs::read_to_string(&options.template)
+        .with_context(|| format!("Failed to read template {}", options.template.display()))?;
+    let base_dir = options.template.parent().unwrap_or_else(|| Path::new("."));
+    let rows = read_rows(&options.data)?;
+
+    // Render everything up front so a bad row aborts before anything is sent
+    let mut messages = Vec::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        let rendered = render_template(&template, row).with_context(|| format!("Row {}", i + 1))?;
+        let message = compose::parse_message(&rendered, base_dir, &options.compose).with_context(|| format!("Row {}", i + 1))?;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+pub fn preview(options: &MergeOptions) -> Result<()> {
+    let messages = render_all(options)?;
+    for (i, message) in messages.iter().enumerate() {
+        println!("===== Row {} =====", i + 1);
+        println!("To: {}", message.to.join(", "));
+        if !message.cc.is_empty() {
+            println!("Cc: {}", message.cc.join(", "));
+        }
+        if !message.bcc.is_empty() {
+            println!("Bcc: {}", message.bcc.join(", "));
+        }
+        println!("Subject: {}", message.subject);
+        println!();
+        println!("{}", message.body);
+    }
+    println!("Dry run: {} message(s) rendered, nothing sent.", messages.len());
+    Ok(())
+}
+
+// Every row's From is checked before the first send, so a bad alias can't stop a merge halfway
+async fn check_senders(token: &ApiToken, messages: &[OutgoingMessage]) -> Result<()> {
+    let senders: BTreeSet<&str> = messages.iter().filter(|m| !m.save_as_draft).filter_map(|m| m.from()).collect();
+    if senders.is_empty() {
+        return Ok(());
+    }
+    let identities = send_as::Identities::load(token).await?;
+    for from in senders {
+        for warning in identities.check(from)? {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    Ok(())
+}
+
+pub async fn run(token: &ApiToken, options: MergeOptions) -> Result<()> {
+    let messages = render_all(&options)?;
+    let progress_path = options
+        .progress_log
+        .unwrap_or_else(|| default_progress_log(&options.data));
+    let completed = read_completed(&progress_path);
+    let mut progress = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&progress_path)
+        .with_context(|| format!("Failed to open progress log {}", progress_path.display()))?;
+
+    check_senders(token, &messages).await?;
+
+    let total = messages.len();
+    let mut sent_any = false;
+    for (i, message) in messages.iter().enumerate() {
+        let row = i + 1;
+        if completed.contains(&message.to.join(",")) {
+            println!("[{}/{}] skipped (already sent) {}", row, total, message.to.join(", "));
+            continue;
+        }
+        if sent_any {
+            tokio::time::sleep(options.delay).await;
+        }
+        // `draft: true` in the template fills Drafts for a look before anything goes out
+        let (id, done) = if message.save_as_draft {
+            (google_api::create_draft(token, &message.to_rfc2822()?, None).await.map(|d| d.id), "drafted")
+        } else {
+            (google_api::send_message(token, message).await.map(|m| m.id), "sent")
+        };
+        let id = id.with_context(|| format!("Failed to send row {} (resume by re-running the same command)", row))?;
+        writeln!(progress, "{}\t{}\t{}", row, message.to.join(","), id)?;
+        progress.flush()?;
+        sent_any = true;
+        println!("[{}/{}] {} to {}", row, total, done, message.to.join(", "));
+    }
+
+    println!("Merge complete. Progress log: {}", progress_path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn parse(template: &str, row: &HashMap<String, String>) -> OutgoingMessage {
+        let rendered = render_template(template, row).unwrap();
+        compose::parse_message(&rendered, Path::new("."), &ComposeConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn front_matter_len_covers_yaml_and_json() {
+        assert_eq!(front_matter_len("---\nto: a\n---\nbody"), 14);
+        assert_eq!(front_matter_len(r#"{"subject": "}{"}body"#), 17);
+        assert_eq!(front_matter_len("plain body"), 0);
+    }
+
+    #[test]
+    fn whole_value_cannot_add_headers() {
+        let message = parse(
+            "---\nto: {{ email }}\nsubject: Hi\n---\nBody",
+            &row(&[("email", "a@example.com\r\nbcc: evil@example.com")]),
+        );
+        assert_eq!(message.to, ["a@example.com\r\nbcc: evil@example.com"]);
+        assert!(message.bcc.is_empty());
+    }
+
+    #[test]
+    fn quoted_value_is_escaped_in_place() {
+        let message = parse(
+            "---\nto: a@example.com\nsubject: \"Hello {{ name }}\"\n---\nBody",
+            &row(&[("name", "Bob\"\nbcc: evil@example.com")]),
+        );
+        assert_eq!(message.subject, "Hello Bob\"\nbcc: evil@example.com");
+        assert!(message.bcc.is_empty());
+    }
+
+    #[test]
+    fn plain_value_with_placeholder_is_quoted_whole() {
+        let message = parse(
+            "---\nto: a@example.com\nsubject: Hello {{ name }}, \"welcome\"\n---\nBody",
+            &row(&[("name", "Bob #1\r\nbcc: evil@example.com")]),
+        );
+        assert_eq!(message.subject, "Hello Bob #1\r\nbcc: evil@example.com, \"welcome\"");
+        assert!(message.bcc.is_empty());
+    }
+
+    #[test]
+    fn json_front_matter_values_stay_strings() {
+        let message = parse(
+            "{\"to\": \"{{ email }}\", \"subject\": \"{{ subject }}\"}\nBody",
+            &row(&[("email", "a@example.com"), ("subject", "\"}, \"bcc\": \"evil@example.com")]),
+        );
+        assert_eq!(message.subject, "\"}, \"bcc\": \"evil@example.com");
+        assert!(message.bcc.is_empty());
+    }
+
+    #[test]
+    fn body_values_are_inserted_as_is() {
+        let message = parse("---\nto: a@example.com\n---\nDear {{ name }},", &row(&[("name", "\"Bob\"\nSmith")]));
+        assert_eq!(message.body, "Dear \"Bob\"\nSmith,");
+    }
+
+    #[test]
+    fn unknown_column_is_an_error() {
+        assert!(render_template("---\nto: {{ email }}\n---\n", &row(&[("name", "Bob")])).is_err());
+    }
+}
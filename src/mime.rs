@@ -0,0 +1,382 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LINE_LENGTH: usize = 76;
+// Gmail rejects messages whose attachments add up to more than this
+const ATTACHMENT_LIMIT: u64 = 25 * 1024 * 1024;
+
+// Content types by lowercase extension; anything else goes out as application/octet-stream
+const CONTENT_TYPES: [(&str, &str); 33] = [
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("7z", "application/x-7z-compressed"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("doc", "application/msword"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("ics", "text/calendar"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("heic", "image/heic"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("mov", "video/quicktime"),
+    ("eml", "message/rfc822"),
+];
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    CONTENT_TYPES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map_or("application/octet-stream", |(_, content_type)| content_type)
+}
+
+/// Fails, naming the largest files, when the attachments can't be sent at all, so the
+/// message is never built or queued.
+fn check_attachments(paths: &[PathBuf]) -> Result<()> {
+    let mut sizes = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(path).with_context(|| format!("Failed to read attachment {}", path.display()))?;
+        if !metadata.is_file() {
+            bail!("Attachment {} is not a file", path.display());
+        }
+        // The name goes into a quoted header parameter
+        if path.file_name().is_some_and(|name| name.to_string_lossy().contains(['\r', '\n', '"'])) {
+            bail!("Attachment name {:?} can't contain line breaks or quotes", path.display());
+        }
+        sizes.push((metadata.len(), path));
+    }
+    let total: u64 = sizes.iter().map(|(size, _)| size).sum();
+    if total > ATTACHMENT_LIMIT {
+        sizes.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+        let largest: Vec<String> = sizes.iter().take(3).map(|(size, path)| format!("{} ({})", path.display(), megabytes(*size))).collect();
+        bail!(
+            "Attachments total {}, over Gmail's {} limit; largest: {}",
+            megabytes(total),
+            megabytes(ATTACHMENT_LIMIT),
+            largest.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct OutgoingMessage {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<PathBuf>,
+    pub extra_headers: Vec<(String, String)>,
+    // When set, the plain-text body is sent as format=flowed wrapped at this column
+    pub wrap_column: Option<usize>,
+    // Also send the body rendered from Markdown as a text/html alternative
+    pub html_alternative: bool,
+    // Gmail thread a reply belongs in; Gmail also wants In-Reply-To/References to match
+    pub thread_id: Option<String>,
+    // Save to Gmail Drafts instead of sending
+    pub save_as_draft: bool,
+    // The draft this was resumed from: saved over, or deleted once the message is sent
+    pub draft_id: Option<String>,
+}
+
+impl OutgoingMessage {
+    // Only set when sending from an alias; Gmail fills in the primary address otherwise
+    pub fn from(&self) -> Option<&str> {
+        self.extra_headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("From")).map(|(_, value)| value.as_str())
+    }
+
+    // A line break in a value would start a header of its own, e.g. a Bcc smuggled in through
+    // a subject from front matter or a merge row
+    fn check_headers(&self) -> Result<()> {
+        let values = self.to.iter().chain(&self.cc).chain(&self.bcc).map(|v| ("address", v.as_str()));
+        let extra = self.extra_headers.iter().flat_map(|(name, value)| [("header name", name.as_str()), (name.as_str(), value.as_str())]);
+        for (name, value) in values.chain([("Subject", self.subject.as_str())]).chain(extra) {
+            if value.contains(['\r', '\n']) {
+                bail!("Line break in {} {:?}; header values must be a single line", name, value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_rfc2822(&self) -> Result<String> {
+        self.check_headers()?;
+        check_attachments(&self.attachments)?;
+        let mut message = String::new();
+        push_header(&mut message, "MIME-Version", "1.0");
+        push_header(&mut message, "To", &self.to.join(", "));
+        if !self.cc.is_empty() {
+            push_header(&mut message, "Cc", &self.cc.join(", "));
+        }
+        // Gmail delivers to Bcc recipients and strips the header before sending
+        if !self.bcc.is_empty() {
+            push_header(&mut message, "Bcc", &self.bcc.join(", "));
+        }
+        push_header(&mut message, "Subject", &encode_header_value(&self.subject));
+        for (name, value) in &self.extra_headers {
+            push_header(&mut message, name, &encode_header_value(value));
+        }
+
+        if self.attachments.is_empty() {
+            message.push_str(&self.body_part());
+            return Ok(message);
+        }
+
+        let boundary = make_boundary();
+        push_header(
+            &mut message,
+            "Content-Type",
+            &format!("multipart/mixed; boundary=\"{}\"", boundary),
+        );
+        message.push_str("\r\n");
+        message.push_str(&format!("--{}\r\n", boundary));
+        message.push_str(&self.body_part());
+        for path in &self.attachments {
+            let data = fs::read(path)
+                .with_context(|| format!("Failed to read attachment {}", path.display()))?;
+            let filename = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "attachment".to_string());
+            message.push_str(&format!("\r\n--{}\r\n", boundary));
+            message.push_str(&attachment_part(&filename, content_type(path), &data));
+        }
+        message.push_str(&format!("\r\n--{}--\r\n", boundary));
+        Ok(message)
+    }
+
+    // The plain part stays canonical; the HTML alternative is derived from it
+    fn body_part(&self) -> String {
+        let plain = text_part(&self.body, self.wrap_column);
+        if !self.html_alternative {
+            return plain;
+        }
+
+        let boundary = format!("{}-alt", make_boundary());
+        let mut part = String::new();
+        push_header(
+            &mut part,
+            "Content-Type",
+            &format!("multipart/alternative; boundary=\"{}\"", boundary),
+        );
+        part.push_str("\r\n");
+        part.push_str(&format!("--{}\r\n", boundary));
+        part.push_str(&plain);
+        part.push_str(&format!("\r\n--{}\r\n", boundary));
+        part.push_str(&html_part(&markdown_to_html(&self.body)));
+        part.push_str(&format!("\r\n--{}--\r\n", boundary));
+        part
+    }
+}
+
+fn markdown_to_html(markdown: &str) -> String {
+    let mut options = comrak::Options::default();
+    options.extension.strikethrough = true;
+    options.extension.table = true;
+    options.extension.autolink = true;
+    let body = comrak::markdown_to_html(markdown, &options);
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head><body>\n{}</body></html>\n",
+        body
+    )
+}
+
+fn html_part(html: &str) -> String {
+    let mut part = String::new();
+    push_header(&mut part, "Content-Type", "text/html; charset=\"UTF-8\"");
+    push_header(&mut part, "Content-Transfer-Encoding", "base64");
+    part.push_str("\r\n");
+    part.push_str(&wrap_base64(html.as_bytes()));
+    part.push_str("\r\n");
+    part
+}
+
+fn push_header(message: &mut String, name: &str, value: &str) {
+    message.push_str(name);
+    message.push_str(": ");
+    message.push_str(value);
+    message.push_str("\r\n");
+}
+
+// RFC 2047 encoded-word for non-ASCII header values
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn wrap_base64(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    encoded
+        .as_bytes()
+        .chunks(LINE_LENGTH)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+fn text_part(body: &str, wrap_column: Option<usize>) -> String {
+    let mut part = String::new();
+    let flowed;
+    let body = match wrap_column {
+        Some(width) => {
+            flowed = flow_text(body, width);
+            push_header(&mut part, "Content-Type", "text/plain; charset=\"UTF-8\"; format=flowed; delsp=no");
+            flowed.as_str()
+        }
+        None => {
+            push_header(&mut part, "Content-Type", "text/plain; charset=\"UTF-8\"");
+            body
+        }
+    };
+    if body.is_ascii() && body.lines().all(|l| l.len() <= 998) {
+        push_header(&mut part, "Content-Transfer-Encoding", "7bit");
+        part.push_str("\r\n");
+        part.push_str(&body.replace("\r\n", "\n").replace('\n', "\r\n"));
+    } else {
+        push_header(&mut part, "Content-Transfer-Encoding", "base64");
+        part.push_str("\r\n");
+        part.push_str(&wrap_base64(body.as_bytes()));
+    }
+    part.push_str("\r\n");
+    part
+}
+
+/// Encodes `body` as RFC 3676 format=flowed: long lines are split at spaces with a trailing
+/// space marking each soft break, quote depth is carried onto continuation lines, and lines
+/// that would be misread (leading space, "From ", ">") are space-stuffed.
+pub fn flow_text(body: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    for line in body.lines() {
+        // The signature separator is the one line that keeps its trailing space
+        if line == "-- " {
+            out.push(line.to_string());
+            continue;
+        }
+        let line = line.trim_end();
+        let depth = line.chars().take_while(|c| *c == '>').count();
+        let content = if depth > 0 { line[depth..].trim_start() } else { line };
+        let prefix = if depth > 0 { format!("{} ", ">".repeat(depth)) } else { String::new() };
+
+        let available = width.saturating_sub(prefix.chars().count()).max(20);
+        let mut wrapped: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for (i, word) in content.split(' ').enumerate() {
+            let needed = current.chars().count() + word.chars().count() + 1;
+            if !current.is_empty() && needed > available {
+                wrapped.push(std::mem::take(&mut current));
+            } else if i > 0 {
+                // Also keeps leading spaces, which get stuffed below
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        wrapped.push(current);
+
+        let last = wrapped.len() - 1;
+        for (i, segment) in wrapped.into_iter().enumerate() {
+            let mut flowed_line = prefix.clone();
+            let needs_stuffing = segment.starts_with(' ') || segment.starts_with("From ") || segment.starts_with('>');
+            if depth == 0 && needs_stuffing {
+                flowed_line.push(' ');
+            }
+            flowed_line.push_str(&segment);
+            if i < last {
+                flowed_line.push(' ');
+            }
+            out.push(flowed_line);
+        }
+    }
+    out.join("\n")
+}
+
+fn attachment_part(filename: &str, content_type: &str, data: &[u8]) -> String {
+    let filename = encode_header_value(filename);
+    let mut part = String::new();
+    push_header(
+        &mut part,
+        "Content-Type",
+        &format!("{}; name=\"{}\"", content_type, filename),
+    );
+    push_header(
+        &mut part,
+        "Content-Disposition",
+        &format!("attachment; filename=\"{}\"", filename),
+    );
+    push_header(&mut part, "Content-Transfer-Encoding", "base64");
+    part.push_str("\r\n");
+    part.push_str(&wrap_base64(data));
+    part.push_str("\r\n");
+    part
+}
+
+fn make_boundary() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("gmail-cli-{:x}-{:x}", std::process::id(), nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_break_with_a_trailing_space() {
+        assert_eq!(flow_text("aaaa bbbb cccc dddd eeee ffff", 20), "aaaa bbbb cccc dddd \neeee ffff");
+    }
+
+    #[test]
+    fn quote_depth_carries_onto_continuations() {
+        assert_eq!(
+            flow_text(">> aaaa bbbb cccc dddd eeee ffff", 20),
+            ">> aaaa bbbb cccc dddd \n>> eeee ffff"
+        );
+    }
+
+    #[test]
+    fn lines_that_would_be_misread_are_space_stuffed() {
+        assert_eq!(flow_text(" indented", 72), "  indented");
+        assert_eq!(flow_text("From here on", 72), " From here on");
+        // A continuation starting with "From " is stuffed too
+        assert_eq!(flow_text("aaaa bbbb cccc dddd From eeee", 20), "aaaa bbbb cccc dddd \n From eeee");
+        // Inside a quote the prefix already protects the line
+        assert_eq!(flow_text("> From here", 72), "> From here");
+    }
+
+    #[test]
+    fn signature_separator_keeps_its_trailing_space() {
+        assert_eq!(flow_text("Thanks\n-- \nAlice", 72), "Thanks\n-- \nAlice");
+        // Any other trailing space would read as a soft break
+        assert_eq!(flow_text("Thanks  \n--  ", 72), "Thanks\n--");
+    }
+}
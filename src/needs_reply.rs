@@ -0,0 +1,118 @@
+use crate::address;
+use crate::config::Sensitivity;
+use crate::google_api::{self, ApiToken, MessageDetail};
+use crate::text;
+use std::collections::{HashMap, HashSet};
+
+fn has_label(message: &MessageDetail, label: &str) -> bool {
+    message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == label))
+}
+
+fn addresses(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(address::extract_address)
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// The account's own address and its send-as aliases, so mail to an alias I haven't sent
+/// from yet still counts as addressed to me. Empty when Gmail can't be reached; the sent mail
+/// in the cache is all `candidates` goes on then.
+pub async fn account_addresses(token: &ApiToken) -> HashSet<String> {
+    let (profile, aliases) = tokio::join!(google_api::get_profile(token), google_api::list_send_as(token));
+    let profile = profile.into_iter().map(|p| p.email_address);
+    let aliases = aliases.into_iter().flatten().map(|a| a.send_as_email);
+    profile.chain(aliases).map(|a| address::extract_address(&a)).collect()
+}
+
+// The account's addresses plus whatever my cached sent mail came from
+fn my_addresses(messages: &[MessageDetail], known: &HashSet<String>) -> HashSet<String> {
+    messages
+        .iter()
+        .filter(|m| has_label(m, "SENT"))
+        .map(|m| address::extract_address(&m.get_header("From")))
+        .chain(known.iter().cloned())
+        .collect()
+}
+
+/// How strongly a message seems to expect an answer from me. Positive signals: addressed to
+/// me directly (more so as the only recipient) and asking a question in its new text.
+/// Negative ones: mailing lists, bulk mail and no-reply senders.
+fn score(message: &MessageDetail, mine: &HashSet<String>) -> i32 {
+    let mut score = 0;
+    let to = addresses(&message.get_header("To"));
+    if to.iter().any(|a| mine.contains(a)) {
+        score += 2;
+        if to.len() == 1 {
+            score += 1;
+        }
+    }
+    let body = text::new_content(&google_api::decode_email_body(message));
+    if body.contains('?') || message.get_header("Subject").contains('?') {
+        score += 2;
+    }
+    if !message.get_header("List-Id").is_empty() || !message.get_header("List-Unsubscribe").is_empty() {
+        score -= 3;
+    }
+    if message.get_header("Precedence").eq_ignore_ascii_case("bulk") {
+        score -= 3;
+    }
+    let from = address::extract_address(&message.get_header("From"));
+    if from.contains("noreply") || from.contains("no-reply") || from.contains("donotreply") {
+        score -= 5;
+    }
+    score
+}
+
+fn threshold(sensitivity: Sensitivity) -> i32 {
+    match sensitivity {
+        Sensitivity::Low => 5,
+        Sensitivity::Medium => 4,
+        Sensitivity::High => 2,
+    }
+}
+
+// The newest message of each cached thread, drafts aside
+fn latest_by_thread(messages: &[MessageDetail]) -> HashMap<&str, &MessageDetail> {
+    let mut latest: HashMap<&str, &MessageDetail> = HashMap::new();
+    for message in messages.iter().filter(|m| !has_label(m, "DRAFT")) {
+        let entry = latest.entry(message.thread_id.as_str()).or_insert(message);
+        if message.received_at() > entry.received_at() {
+            *entry = message;
+        }
+    }
+    latest
+}
+
+/// Inbox messages that are the latest in their cached thread (so I haven't replied since)
+/// and score at or above the threshold, newest first.
+pub fn candidates<'a>(
+    messages: &'a [MessageDetail],
+    known: &HashSet<String>,
+    sensitivity: Sensitivity,
+) -> Vec<&'a MessageDetail> {
+    let mine = my_addresses(messages, known);
+    let mut found: Vec<&MessageDetail> = latest_by_thread(messages)
+        .into_values()
+        .filter(|m| has_label(m, "INBOX") && !has_label(m, "SENT"))
+        .filter(|m| score(m, &mine) >= threshold(sensitivity))
+        .collect();
+    found.sort_by_key(|m| std::cmp::Reverse(m.received_at()));
+    found
+}
+
+/// The latest message of every cached thread I've written in, newest first: mine when
+/// `my_move` is false (waiting on the others), theirs when it is true (waiting on me).
+/// Unlike `candidates` nothing is scored; having taken part is what counts.
+pub fn awaiting(messages: &[MessageDetail], my_move: bool) -> Vec<&MessageDetail> {
+    let joined: HashSet<&str> = messages.iter().filter(|m| has_label(m, "SENT")).map(|m| m.thread_id.as_str()).collect();
+    let mut found: Vec<&MessageDetail> = latest_by_thread(messages)
+        .into_values()
+        .filter(|m| joined.contains(m.thread_id.as_str()))
+        .filter(|m| !has_label(m, "TRASH") && !has_label(m, "SPAM"))
+        .filter(|m| has_label(m, "SENT") != my_move)
+        .collect();
+    found.sort_by_key(|m| std::cmp::Reverse(m.received_at()));
+    found
+}
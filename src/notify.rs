@@ -0,0 +1,36 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Urgency {
+    Normal,
+    Critical,
+}
+
+fn desktop_notification(title: &str, body: &str, urgency: Urgency) -> bool {
+    let status = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            body, title
+        );
+        Command::new("osascript").arg("-e").arg(script).stderr(Stdio::null()).status()
+    } else {
+        let level = match urgency {
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        };
+        Command::new("notify-send")
+            .args(["-a", "gmail-cli", "-u", level, title, body])
+            .stderr(Stdio::null())
+            .status()
+    };
+    status.map(|s| s.success()).unwrap_or(false)
+}
+
+// Always echo to the terminal so headless/SSH sessions still see the alert
+pub fn send(title: &str, body: &str, urgency: Urgency) {
+    let _ = desktop_notification(title, body, urgency);
+    let bell = if urgency == Urgency::Critical { "\x07" } else { "" };
+    println!("{}[{}] {}", bell, title, body);
+    let _ = std::io::stdout().flush();
+}
@@ -0,0 +1,203 @@
+use crate::google_api::{self, ApiToken, MessageDetail};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+pub const PENDING_FILE: &str = "pending_changes.json";
+// Held only while the file is loaded, changed and saved, never across a request
+const PENDING_LOCK: &str = "pending_changes.lock";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Target {
+    Message(String),
+    // Conversation rows change every message in the thread
+    Thread(String),
+}
+
+/// Why a queued change wasn't applied when the network came back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Conflict {
+    // Deleted, or moved out of reach, since the change was queued
+    Gone,
+    // Already done elsewhere, e.g. the label was removed from another device
+    AlreadyApplied,
+    // Gmail refused it, e.g. for a label that no longer exists
+    Rejected(String),
+}
+
+impl Conflict {
+    pub fn describe(&self) -> String {
+        match self {
+            Conflict::Gone => "no longer exists".to_string(),
+            Conflict::AlreadyApplied => "already done on the server".to_string(),
+            Conflict::Rejected(error) => format!("rejected: {}", error),
+        }
+    }
+}
+
+/// A label change made while offline, applied when Gmail can be reached again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingChange {
+    pub id: String,
+    pub target: Target,
+    pub add: Vec<String>,
+    pub remove: Vec<String>,
+    // What the user did, e.g. "Archive: <subject>"
+    pub description: String,
+    // Set at sync time; the change then waits for the user to skip or force it
+    #[serde(default)]
+    pub conflict: Option<Conflict>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PendingChanges {
+    pub entries: Vec<PendingChange>,
+}
+
+impl PendingChanges {
+    pub fn load() -> Self {
+        crate::store::load(PENDING_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(PENDING_FILE, self)
+    }
+
+    pub fn conflicts(&self) -> Vec<&PendingChange> {
+        self.entries.iter().filter(|e| e.conflict.is_some()).collect()
+    }
+}
+
+fn new_change_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("change-{:x}", nanos)
+}
+
+async fn apply(token: &ApiToken, target: &Target, add: &[String], remove: &[String]) -> Result<()> {
+    let add: Vec<&str> = add.iter().map(String::as_str).collect();
+    let remove: Vec<&str> = remove.iter().map(String::as_str).collect();
+    match target {
+        Target::Message(id) => google_api::modify_labels(token, id, &add, &remove).await,
+        Target::Thread(id) => google_api::modify_thread_labels(token, id, &add, &remove).await,
+    }
+}
+
+/// Applies the change now, or queues it when the network is unreachable. Returns whether it
+/// was applied; API rejections are still returned as errors.
+pub async fn apply_or_queue(token: &ApiToken, target: Target, add: &[&str], remove: &[&str], description: &str) -> Result<bool> {
+    let add: Vec<String> = add.iter().map(|l| l.to_string()).collect();
+    let remove: Vec<String> = remove.iter().map(|l| l.to_string()).collect();
+    match apply(token, &target, &add, &remove).await {
+        Ok(()) => Ok(true),
+        Err(e) if google_api::is_network_error(&e) => {
+            let _lock = crate::store::wait_for_lock(PENDING_LOCK).await?;
+            let mut pending = PendingChanges::load();
+            pending.entries.push(PendingChange {
+                id: new_change_id(),
+                target,
+                add,
+                remove,
+                description: description.to_string(),
+                conflict: None,
+            });
+            pending.save()?;
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn has_labels(message: &MessageDetail, add: &[String], remove: &[String]) -> bool {
+    let labels = message.label_ids.as_deref().unwrap_or_default();
+    add.iter().all(|l| labels.contains(l)) && !remove.iter().any(|l| labels.contains(l))
+}
+
+// Compares the change with the server's current labels
+async fn check(token: &ApiToken, change: &PendingChange) -> Result<Option<Conflict>> {
+    let messages = match &change.target {
+        Target::Message(id) => google_api::get_message_headers(token, id, &[]).await.map(|m| vec![m]),
+        Target::Thread(id) => google_api::get_thread(token, id, &[]).await.map(|t| t.messages.unwrap_or_default()),
+    };
+    match messages {
+        Err(e) if google_api::is_not_found(&e) => Ok(Some(Conflict::Gone)),
+        Err(e) => Err(e),
+        Ok(messages) if messages.is_empty() => Ok(Some(Conflict::Gone)),
+        Ok(messages) if messages.iter().all(|m| has_labels(m, &change.add, &change.remove)) => {
+            Ok(Some(Conflict::AlreadyApplied))
+        }
+        Ok(_) => Ok(None),
+    }
+}
+
+#[derive(Default)]
+pub struct SyncReport {
+    pub applied: usize,
+    // Found in this sync; earlier ones are still waiting in the queue
+    pub new_conflicts: usize,
+}
+
+/// Applies queued changes that still make sense and flags the rest for the user, instead of
+/// giving up on the whole queue. Stops early, keeping everything left, while still offline.
+pub async fn sync(token: &ApiToken) -> Result<SyncReport> {
+    // Works on a snapshot; changes queued or resolved meanwhile are kept when merging back
+    let snapshot = PendingChanges::load();
+    let mut applied = HashSet::new();
+    let mut conflicts = HashMap::new();
+    for change in snapshot.entries.iter().filter(|e| e.conflict.is_none()) {
+        let outcome = match check(token, change).await {
+            Ok(None) => apply(token, &change.target, &change.add, &change.remove).await.map(|_| None),
+            other => other,
+        };
+        match outcome {
+            Ok(None) => {
+                applied.insert(change.id.clone());
+            }
+            Ok(Some(conflict)) => {
+                conflicts.insert(change.id.clone(), conflict);
+            }
+            Err(e) if google_api::is_network_error(&e) => break,
+            Err(e) => {
+                conflicts.insert(change.id.clone(), Conflict::Rejected(format!("{:#}", e)));
+            }
+        }
+    }
+    if applied.is_empty() && conflicts.is_empty() {
+        return Ok(SyncReport::default());
+    }
+
+    let _lock = crate::store::wait_for_lock(PENDING_LOCK).await?;
+    let mut pending = PendingChanges::load();
+    let mut report = SyncReport { applied: applied.len(), new_conflicts: 0 };
+    pending.entries.retain(|e| !applied.contains(&e.id));
+    for change in pending.entries.iter_mut().filter(|e| e.conflict.is_none()) {
+        if let Some(conflict) = conflicts.remove(&change.id) {
+            change.conflict = Some(conflict);
+            report.new_conflicts += 1;
+        }
+    }
+    pending.save()?;
+    Ok(report)
+}
+
+pub enum Resolution {
+    // Drop the change
+    Skip,
+    // Apply it anyway
+    Force,
+}
+
+/// Settles one conflicting change. A forced change that fails stays queued.
+pub async fn resolve(token: &ApiToken, id: &str, resolution: Resolution) -> Result<()> {
+    let snapshot = PendingChanges::load();
+    let change = snapshot.entries.iter().find(|e| e.id == id).context("That change is no longer queued")?;
+    if let Resolution::Force = resolution {
+        apply(token, &change.target, &change.add, &change.remove).await?;
+    }
+    let _lock = crate::store::wait_for_lock(PENDING_LOCK).await?;
+    let mut pending = PendingChanges::load();
+    pending.entries.retain(|e| e.id != id);
+    pending.save()
+}
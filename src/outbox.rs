@@ -0,0 +1,186 @@
+use crate::google_api::{self, ApiToken, Message};
+use crate::mime::OutgoingMessage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+pub const OUTBOX_FILE: &str = "outbox.json";
+// Held for every change to the outbox, across the send itself, so two retriers never send the
+// same entry
+const OUTBOX_LOCK: &str = "outbox.lock";
+const MAX_AUTO_ATTEMPTS: u32 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum OutboxStatus {
+    Pending,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    // Rendered once at queue time so retries don't depend on attachment files still existing
+    pub rfc2822: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    // Deleted from Gmail Drafts once sent
+    #[serde(default)]
+    pub draft_id: Option<String>,
+    pub attempts: u32,
+    pub status: OutboxStatus,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Outbox {
+    pub entries: Vec<OutboxEntry>,
+}
+
+pub enum SendOutcome {
+    Sent(Message),
+    Queued(String),
+    // Saved to Gmail Drafts, with the draft id
+    Drafted(String),
+}
+
+/// What `send_or_queue` did, with the send-as warnings about the message's From address.
+pub struct SendReport {
+    pub outcome: SendOutcome,
+    pub warnings: Vec<String>,
+}
+
+impl Outbox {
+    pub fn load() -> Self {
+        crate::store::load(OUTBOX_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(OUTBOX_FILE, self)
+    }
+
+    /// Drops an entry without sending it.
+    pub async fn discard(id: &str) -> Result<()> {
+        let _lock = crate::store::wait_for_lock(OUTBOX_LOCK).await?;
+        let mut outbox = Outbox::load();
+        outbox.entries.retain(|e| e.id != id);
+        outbox.save()
+    }
+}
+
+fn new_entry_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("outbox-{:x}", nanos)
+}
+
+/// Sends immediately, or parks the message in the outbox when the network is unreachable.
+/// API rejections (bad address, auth) are still returned as errors. Messages marked
+/// `draft: true` are saved to Gmail Drafts instead. A From address that isn't one of the
+/// account's send-as aliases is refused before anything is sent.
+pub async fn send_or_queue(token: &ApiToken, message: &OutgoingMessage) -> Result<SendReport> {
+    let rfc2822 = message.to_rfc2822()?;
+    let thread_id = message.thread_id.as_deref();
+    if message.save_as_draft {
+        let draft = match &message.draft_id {
+            Some(id) => google_api::update_draft(token, id, &rfc2822, thread_id).await?,
+            None => google_api::create_draft(token, &rfc2822, thread_id).await?,
+        };
+        return Ok(SendReport { outcome: SendOutcome::Drafted(draft.id), warnings: Vec::new() });
+    }
+    let warnings = crate::send_as::check_message(token, message).await?;
+    let outcome = match google_api::send_raw(token, &rfc2822, thread_id).await {
+        Ok(sent) => {
+            discard_draft(token, message.draft_id.as_deref()).await;
+            SendOutcome::Sent(sent)
+        }
+        Err(e) if google_api::is_network_error(&e) => {
+            let id = new_entry_id();
+            let _lock = crate::store::wait_for_lock(OUTBOX_LOCK).await?;
+            let mut outbox = Outbox::load();
+            outbox.entries.push(OutboxEntry {
+                id: id.clone(),
+                to: message.to.clone(),
+                subject: message.subject.clone(),
+                body: message.body.clone(),
+                rfc2822,
+                thread_id: message.thread_id.clone(),
+                draft_id: message.draft_id.clone(),
+                attempts: 1,
+                status: OutboxStatus::Pending,
+                last_error: Some(e.to_string()),
+            });
+            outbox.save()?;
+            SendOutcome::Queued(id)
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(SendReport { outcome, warnings })
+}
+
+// Once sent, the draft it was written in is only a stale copy; failing to delete it isn't
+// worth failing the send over
+async fn discard_draft(token: &ApiToken, draft_id: Option<&str>) {
+    if let Some(id) = draft_id {
+        let _ = google_api::delete_draft(token, id).await;
+    }
+}
+
+async fn attempt(token: &ApiToken, entry: &mut OutboxEntry) -> bool {
+    match google_api::send_raw(token, &entry.rfc2822, entry.thread_id.as_deref()).await {
+        Ok(_) => {
+            discard_draft(token, entry.draft_id.as_deref()).await;
+            true
+        }
+        Err(e) => {
+            entry.attempts += 1;
+            // Still offline: keep waiting. Anything else won't fix itself, so stop auto-retrying
+            if !google_api::is_network_error(&e) || entry.attempts >= MAX_AUTO_ATTEMPTS {
+                entry.status = OutboxStatus::Failed;
+            }
+            entry.last_error = Some(e.to_string());
+            false
+        }
+    }
+}
+
+/// Retries every pending entry, returning how many were sent.
+pub async fn retry_pending(token: &ApiToken) -> Result<usize> {
+    let _lock = crate::store::wait_for_lock(OUTBOX_LOCK).await?;
+    let mut outbox = Outbox::load();
+    if outbox.entries.is_empty() {
+        return Ok(0);
+    }
+    let mut sent = 0;
+    let mut remaining = Vec::new();
+    for mut entry in outbox.entries.drain(..) {
+        if entry.status == OutboxStatus::Pending && attempt(token, &mut entry).await {
+            sent += 1;
+        } else {
+            remaining.push(entry);
+        }
+    }
+    outbox.entries = remaining;
+    outbox.save()?;
+    Ok(sent)
+}
+
+/// Manual retry of a single entry regardless of its status.
+pub async fn retry_one(token: &ApiToken, id: &str) -> Result<bool> {
+    let _lock = crate::store::wait_for_lock(OUTBOX_LOCK).await?;
+    let mut outbox = Outbox::load();
+    let Some(index) = outbox.entries.iter().position(|e| e.id == id) else {
+        return Ok(false);
+    };
+    let mut entry = outbox.entries.remove(index);
+    entry.status = OutboxStatus::Pending;
+    let sent = attempt(token, &mut entry).await;
+    if !sent {
+        outbox.entries.insert(index, entry);
+    }
+    outbox.save()?;
+    Ok(sent)
+}
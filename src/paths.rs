@@ -0,0 +1,55 @@
+use crate::config::PathsConfig;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const APP_DIR: &str = "gmail-cli";
+// Used as the app's directories as-is, without a gmail-cli subdirectory, so tests and
+// sandboxes can point everything at a scratch directory
+pub const CONFIG_DIR_ENV: &str = "GMAIL_CLI_CONFIG_DIR";
+pub const CACHE_DIR_ENV: &str = "GMAIL_CLI_CACHE_DIR";
+
+static OVERRIDES: OnceLock<PathsConfig> = OnceLock::new();
+
+/// Applies the [paths] section once the config file has been read. The config file itself is
+/// always looked up in `config_dir()`.
+pub fn configure(paths: &PathsConfig) {
+    let _ = OVERRIDES.set(paths.clone());
+}
+
+fn from_env(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+fn overrides() -> Option<&'static PathsConfig> {
+    OVERRIDES.get()
+}
+
+/// Config file, OAuth client credentials and scripts, shared by all accounts.
+pub fn config_dir() -> Option<PathBuf> {
+    from_env(CONFIG_DIR_ENV).or_else(|| dirs::config_dir().map(|dir| dir.join(APP_DIR)))
+}
+
+/// Tokens and per-account state files; the config directory unless `paths.state_dir` is set.
+pub fn state_dir() -> Option<PathBuf> {
+    overrides().and_then(|p| p.state_dir.clone()).or_else(config_dir)
+}
+
+/// The local message cache, which can always be deleted.
+pub fn cache_dir() -> Option<PathBuf> {
+    from_env(CACHE_DIR_ENV)
+        .or_else(|| overrides().and_then(|p| p.cache_dir.clone()))
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join(APP_DIR)))
+}
+
+/// Attachments saved from the viewer; the working directory if there is no Downloads folder.
+pub fn download_dir() -> PathBuf {
+    overrides()
+        .and_then(|p| p.download_dir.clone())
+        .or_else(dirs::download_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The audit log; next to the state files unless `paths.log_dir` is set.
+pub fn log_dir() -> Option<PathBuf> {
+    overrides().and_then(|p| p.log_dir.clone()).or_else(state_dir)
+}
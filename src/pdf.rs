@@ -0,0 +1,221 @@
+use crate::google_api::{self, ApiToken};
+use crate::redact::Redactor;
+use crate::text;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+// A4 in points, with one-inch-ish margins
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const BODY_SIZE: f32 = 9.5;
+const HEADING_SIZE: f32 = 13.0;
+const LEADING: f32 = 1.35;
+// Courier is monospaced at 0.6em, which makes wrapping exact; headings use the same estimate
+const CHAR_WIDTH: f32 = 0.6;
+
+#[derive(Clone, Copy)]
+enum Font {
+    Body,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Body => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// A minimal PDF writer: text only, in the standard Courier and Helvetica-Bold fonts, which
+/// every reader has built in so nothing needs embedding.
+struct Document {
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+}
+
+impl Document {
+    fn new() -> Self {
+        Document { pages: Vec::new(), current: String::new(), y: PAGE_HEIGHT - MARGIN }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.current));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn columns(size: f32) -> usize {
+        ((PAGE_WIDTH - 2.0 * MARGIN) / (size * CHAR_WIDTH)) as usize
+    }
+
+    fn line(&mut self, content: &str, font: Font, size: f32) {
+        if self.y - size * LEADING < MARGIN {
+            self.new_page();
+        }
+        self.y -= size * LEADING;
+        let _ = writeln!(
+            self.current,
+            "BT /{} {} Tf {} {} Td ({}) Tj ET",
+            font.resource(),
+            size,
+            MARGIN,
+            self.y,
+            escape(content)
+        );
+    }
+
+    fn paragraph(&mut self, content: &str, font: Font, size: f32) {
+        let width = Self::columns(size);
+        for source_line in content.lines() {
+            for line in text::wrap_to_width(source_line, width) {
+                // Long URLs and the like are hard-broken so they stay on the page
+                let chars: Vec<char> = line.chars().collect();
+                for chunk in chars.chunks(width.max(1)) {
+                    self.line(&chunk.iter().collect::<String>(), font, size);
+                }
+                if chars.is_empty() {
+                    self.space(size);
+                }
+            }
+        }
+    }
+
+    fn space(&mut self, size: f32) {
+        self.y -= size * LEADING;
+    }
+
+    fn rule(&mut self) {
+        self.space(BODY_SIZE / 2.0);
+        let _ = writeln!(
+            self.current,
+            "0.6 G {} {} m {} {} l S 0 G",
+            MARGIN,
+            self.y,
+            PAGE_WIDTH - MARGIN,
+            self.y
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if !self.current.is_empty() || self.pages.is_empty() {
+            self.pages.push(std::mem::take(&mut self.current));
+        }
+
+        // Objects 1-4 are the catalog, page tree and fonts; each page then takes two
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            String::new(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+        ];
+        let mut kids = Vec::new();
+        for content in &self.pages {
+            let page_id = objects.len() + 1;
+            kids.push(format!("{} 0 R", page_id));
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}endstream", encode(content).len(), content));
+        }
+        objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len());
+
+        let mut out: Vec<u8> = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n", i + 1).bytes());
+            out.extend(encode(object));
+            out.extend(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        out.extend(trailer.bytes());
+        out
+    }
+}
+
+fn escape(content: &str) -> String {
+    content.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)")
+}
+
+// Maps to WinAnsiEncoding, which the standard fonts use. Characters outside it become '?',
+// since covering them would mean embedding a font.
+fn encode(content: &str) -> Vec<u8> {
+    content
+        .chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7e}' | '\n' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\u{20ac}' => 0x80,
+            '\t' => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Renders every message of a thread, oldest first, with its headers and plain-text body.
+/// With a `redactor`, addresses, phone numbers and configured patterns are replaced in
+/// every header and body, for a copy that can be shared.
+pub async fn export_thread(
+    token: &ApiToken,
+    thread_id: &str,
+    out: &Path,
+    mut redactor: Option<Redactor>,
+) -> Result<usize> {
+    let thread = google_api::ThreadsGet::new(thread_id)
+        .send(token)
+        .await
+        .with_context(|| format!("Failed to fetch thread {}", thread_id))?;
+    let messages = thread.messages.unwrap_or_default();
+    let mut clean = |value: &str| match &mut redactor {
+        Some(redactor) => redactor.apply(value),
+        None => value.to_string(),
+    };
+
+    let mut document = Document::new();
+    let subject = messages.first().map(|m| m.get_header("Subject")).unwrap_or_default();
+    document.paragraph(&text::single_line(&clean(&subject)), Font::Bold, HEADING_SIZE);
+    document.paragraph(&format!("{} message(s), thread {}", messages.len(), thread_id), Font::Body, BODY_SIZE);
+
+    for message in &messages {
+        document.rule();
+        document.space(BODY_SIZE / 2.0);
+        for header in ["From", "To", "Cc", "Date", "Subject"] {
+            let value = message.get_header(header);
+            if !value.is_empty() {
+                document.paragraph(&format!("{}: {}", header, text::single_line(&clean(&value))), Font::Bold, BODY_SIZE);
+            }
+        }
+        document.space(BODY_SIZE);
+        let body = text::sanitize_multiline(&clean(&google_api::decode_email_body(message)));
+        document.paragraph(&body, Font::Body, BODY_SIZE);
+    }
+
+    fs::write(out, document.finish()).with_context(|| format!("Failed to write {}", out.display()))?;
+    Ok(messages.len())
+}
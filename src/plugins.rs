@@ -0,0 +1,80 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Executables named `gmail-cli-<name>` on PATH are run as `gmail-cli <name>`.
+pub const PREFIX: &str = "gmail-cli-";
+// Bumped when fields are removed or change meaning; additions keep the version
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Written as JSON to the plugin's stdin, which is then closed.
+#[derive(Serialize, Debug)]
+pub struct PluginContext {
+    pub protocol: u32,
+    pub account: Account,
+    pub selected_message: Option<SelectedMessage>,
+    pub args: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Account {
+    // The --account name, when one was given
+    pub name: Option<String>,
+    pub email: Option<String>,
+    // Lets plugins call the Gmail API with the same authorization
+    pub access_token: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SelectedMessage {
+    pub id: String,
+    pub thread_id: String,
+    pub from: String,
+    pub subject: String,
+}
+
+impl PluginContext {
+    pub async fn new(token: &ApiToken, selected_message: Option<SelectedMessage>, args: Vec<String>) -> Self {
+        // The address is a convenience; an offline lookup failure shouldn't block the plugin
+        let email = google_api::get_profile(token).await.ok().map(|p| p.email_address);
+        PluginContext {
+            protocol: PROTOCOL_VERSION,
+            account: Account {
+                name: crate::store::account(),
+                email,
+                access_token: token.bearer(),
+            },
+            selected_message,
+            args,
+        }
+    }
+}
+
+pub fn find(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}{}", PREFIX, name, std::env::consts::EXE_SUFFIX);
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Runs a plugin attached to the current terminal and waits for it to exit.
+pub async fn run(name: &str, context: &PluginContext) -> Result<ExitStatus> {
+    let path = find(name).with_context(|| format!("No plugin named {}{} found on PATH", PREFIX, name))?;
+    let mut child = Command::new(&path)
+        .args(&context.args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", path.display()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let payload = serde_json::to_vec(context)?;
+        // A plugin that ignores its context may exit before reading it
+        stdin.write_all(&payload).await.ok();
+    }
+    Ok(child.wait().await?)
+}
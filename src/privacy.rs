@@ -0,0 +1,301 @@
+use crate::address;
+use crate::google_api::{self, MessageDetail};
+use std::collections::{BTreeSet, HashMap};
+
+const LONG_LINK_LENGTH: usize = 200;
+
+// Host suffix -> vendor, checked against image, stylesheet and link hosts. A path after the
+// host limits the match to that endpoint, for domains that mostly serve ordinary pages
+const KNOWN_VENDORS: [(&str, &str); 31] = [
+    ("list-manage.com", "Mailchimp"),
+    ("mailchimp.com", "Mailchimp"),
+    ("mcsv.net", "Mailchimp"),
+    ("sendgrid.net", "SendGrid"),
+    ("mandrillapp.com", "Mandrill"),
+    ("hubspot.com", "HubSpot"),
+    ("hubspotemail.net", "HubSpot"),
+    ("hs-analytics.net", "HubSpot"),
+    ("mailtrack.io", "Mailtrack"),
+    ("yesware.com", "Yesware"),
+    ("mixmax.com", "Mixmax"),
+    ("superhuman.com", "Superhuman"),
+    ("streak.com", "Streak"),
+    ("bananatag.com", "Bananatag"),
+    ("exct.net", "Salesforce Marketing Cloud"),
+    ("exacttarget.com", "Salesforce Marketing Cloud"),
+    ("google-analytics.com", "Google Analytics"),
+    ("doubleclick.net", "Google Ads"),
+    // The Meta pixel and the outbound link redirector, not links to Facebook pages
+    ("facebook.com/tr", "Meta"),
+    ("l.facebook.com", "Meta"),
+    ("mktoresp.com", "Marketo"),
+    ("mktdns.com", "Marketo"),
+    ("intercom.io", "Intercom"),
+    ("intercom-mail.com", "Intercom"),
+    ("customer.io", "Customer.io"),
+    ("sparkpostmail.com", "SparkPost"),
+    ("mailgun.org", "Mailgun"),
+    ("rs6.net", "Constant Contact"),
+    ("createsend.com", "Campaign Monitor"),
+    ("klaviyo.com", "Klaviyo"),
+    ("substack.com", "Substack"),
+];
+
+// Path fragments typical of click-tracking redirect endpoints
+const REDIRECT_MARKERS: [&str; 6] = ["/track/click", "/ls/click", "/click?", "/redirect", "/r/?", "/c/"];
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub url: String,
+    pub vendor: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PrivacyReport {
+    pub tracking_pixels: Vec<Finding>,
+    pub remote_resources: Vec<String>,
+    pub redirect_links: Vec<Finding>,
+}
+
+impl PrivacyReport {
+    pub fn vendors(&self) -> BTreeSet<String> {
+        self.tracking_pixels
+            .iter()
+            .chain(&self.redirect_links)
+            .filter_map(|f| f.vendor.clone())
+            .collect()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.tracking_pixels.is_empty() && self.remote_resources.is_empty() && self.redirect_links.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        if self.is_clean() {
+            return "No trackers, remote resources or link redirectors detected.".to_string();
+        }
+        let mut out = String::new();
+        let vendors = self.vendors();
+        if !vendors.is_empty() {
+            out.push_str(&format!(
+                "Vendors: {}\n\n",
+                vendors.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        let pixels: Vec<&str> = self.tracking_pixels.iter().map(|f| f.url.as_str()).collect();
+        let resources: Vec<&str> = self.remote_resources.iter().map(String::as_str).collect();
+        let links: Vec<&str> = self.redirect_links.iter().map(|f| f.url.as_str()).collect();
+        let sections = [
+            ("Tracking pixels", pixels),
+            ("Remote resources", resources),
+            ("Tracked / oversized links", links),
+        ];
+        for (title, urls) in sections {
+            out.push_str(&format!("{} ({})\n", title, urls.len()));
+            for url in urls {
+                out.push_str(&format!("  {}\n", url));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn parse_remote(url: &str) -> Option<url::Url> {
+    let absolute = match url.strip_prefix("//") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    };
+    url::Url::parse(&absolute).ok()
+}
+
+fn host_of(url: &str) -> Option<String> {
+    parse_remote(url)?.host_str().map(|h| h.to_ascii_lowercase())
+}
+
+fn vendor_for(url: &str) -> Option<&'static str> {
+    let parsed = parse_remote(url)?;
+    let host = parsed.host_str()?.to_ascii_lowercase();
+    let path = parsed.path().trim_end_matches('/');
+    KNOWN_VENDORS
+        .iter()
+        .find(|(pattern, _)| {
+            let (suffix, endpoint) = match pattern.split_once('/') {
+                Some((suffix, endpoint)) => (suffix, Some(endpoint)),
+                None => (*pattern, None),
+            };
+            let host_matches = host == suffix || host.ends_with(&format!(".{}", suffix));
+            host_matches && endpoint.is_none_or(|endpoint| path.strip_prefix('/') == Some(endpoint))
+        })
+        .map(|(_, vendor)| *vendor)
+}
+
+// Minimal tag scanner: yields the attribute map of every `<tag ...>` occurrence
+fn tags<'a>(html: &'a str, tag: &'a str) -> impl Iterator<Item = HashMap<String, String>> + 'a {
+    let lower = html.to_ascii_lowercase();
+    let needle = format!("<{}", tag);
+    let mut positions = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(&needle) {
+        let start = from + pos + needle.len();
+        let end = lower[start..].find('>').map_or(lower.len(), |e| start + e);
+        // Make sure we matched "<img" and not "<imgfoo"
+        if lower[start..].starts_with(|c: char| c.is_whitespace() || c == '/') {
+            positions.push((start, end));
+        }
+        from = end.min(lower.len());
+        if from >= lower.len() {
+            break;
+        }
+    }
+    positions.into_iter().map(move |(start, end)| parse_attributes(&html[start..end]))
+}
+
+fn parse_attributes(raw: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = raw.trim_start_matches('/').trim();
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let mut value = String::new();
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (v, remaining) = match after_eq.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after_eq[1..];
+                    let close = body.find(quote).unwrap_or(body.len());
+                    (&body[..close], body.get(close + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let close = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    (&after_eq[..close], &after_eq[close..])
+                }
+            };
+            value = v.replace("&amp;", "&");
+            rest = remaining.trim_start();
+        }
+        if !name.is_empty() {
+            attributes.insert(name, value);
+        }
+        rest = rest.trim_start_matches('/').trim_start();
+    }
+    attributes
+}
+
+fn is_tiny(attributes: &HashMap<String, String>) -> bool {
+    let tiny = |key: &str| {
+        attributes
+            .get(key)
+            .map(|v| v.trim_end_matches("px").trim())
+            .is_some_and(|v| v == "0" || v == "1")
+    };
+    let style = attributes.get("style").map(|s| s.replace(' ', "").to_ascii_lowercase()).unwrap_or_default();
+    (tiny("width") && tiny("height"))
+        || style.contains("display:none")
+        || (style.contains("width:1px") && style.contains("height:1px"))
+}
+
+fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+pub fn analyze_html(html: &str) -> PrivacyReport {
+    let mut report = PrivacyReport::default();
+
+    for img in tags(html, "img") {
+        let Some(src) = img.get("src").filter(|s| is_remote(s)) else { continue };
+        let host = host_of(src);
+        let vendor = vendor_for(src);
+        if is_tiny(&img) || vendor.is_some() {
+            // Unknown pixel hosts are reported under their own hostname
+            report.tracking_pixels.push(Finding {
+                url: src.clone(),
+                vendor: vendor.map(str::to_string).or(host),
+            });
+        } else {
+            report.remote_resources.push(src.clone());
+        }
+    }
+
+    for link in tags(html, "link") {
+        if let Some(href) = link.get("href").filter(|h| is_remote(h)) {
+            report.remote_resources.push(href.clone());
+        }
+    }
+
+    for anchor in tags(html, "a") {
+        let Some(href) = anchor.get("href").filter(|h| is_remote(h)) else { continue };
+        let vendor = vendor_for(href);
+        let lower = href.to_ascii_lowercase();
+        let looks_tracked = REDIRECT_MARKERS.iter().any(|m| lower.contains(m));
+        if vendor.is_some() || looks_tracked || href.len() > LONG_LINK_LENGTH {
+            report.redirect_links.push(Finding {
+                url: href.clone(),
+                vendor: vendor.map(str::to_string),
+            });
+        }
+    }
+
+    report
+}
+
+pub fn analyze(detail: &MessageDetail) -> PrivacyReport {
+    google_api::find_part_text(detail, "text/html")
+        .map(|html| analyze_html(&html))
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct VendorStats {
+    messages: usize,
+    pixels: usize,
+    links: usize,
+    senders: BTreeSet<String>,
+}
+
+pub fn print_vendor_report(messages: &[MessageDetail]) {
+    let mut stats: HashMap<String, VendorStats> = HashMap::new();
+    let mut tracked_messages = 0;
+
+    for detail in messages {
+        let report = analyze(detail);
+        let vendors = report.vendors();
+        if vendors.is_empty() {
+            continue;
+        }
+        tracked_messages += 1;
+        let sender = address::extract_address(&detail.get_header("From"));
+        for vendor in vendors {
+            let matches = |f: &&Finding| f.vendor.as_deref() == Some(vendor.as_str());
+            let entry = stats.entry(vendor.clone()).or_default();
+            entry.messages += 1;
+            entry.pixels += report.tracking_pixels.iter().filter(matches).count();
+            entry.links += report.redirect_links.iter().filter(matches).count();
+            entry.senders.insert(sender.clone());
+        }
+    }
+
+    println!(
+        "{} of {} cached messages contain trackers\n",
+        tracked_messages,
+        messages.len()
+    );
+    if stats.is_empty() {
+        return;
+    }
+
+    let mut rows: Vec<_> = stats.into_iter().collect();
+    rows.sort_by(|a, b| b.1.messages.cmp(&a.1.messages).then(a.0.cmp(&b.0)));
+    println!("{:<32} {:>8} {:>8} {:>8} {:>8}", "Vendor", "Messages", "Pixels", "Links", "Senders");
+    for (vendor, s) in rows {
+        println!(
+            "{:<32} {:>8} {:>8} {:>8} {:>8}",
+            vendor,
+            s.messages,
+            s.pixels,
+            s.links,
+            s.senders.len()
+        );
+    }
+}
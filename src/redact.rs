@@ -0,0 +1,48 @@
+use crate::config::RedactConfig;
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+// Groups of digits as phone numbers are usually written; dates and times don't have enough
+// digits per group to match
+const PHONE_PATTERN: &str = r"(?:\+\d{1,3}[\s.-]?\(?\d{2,4}\)?|\(\d{2,4}\)|\b\d{2,4})[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b";
+
+/// Strips personal details from text meant to be shared. Each distinct address becomes a
+/// numbered placeholder, so a reader can still tell who wrote to whom.
+pub struct Redactor {
+    email: Regex,
+    phone: Regex,
+    custom: Vec<Regex>,
+    addresses: HashMap<String, usize>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig) -> Result<Self> {
+        let custom = config
+            .patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid redact pattern '{}'", pattern)))
+            .collect::<Result<_>>()?;
+        Ok(Redactor {
+            email: Regex::new(EMAIL_PATTERN)?,
+            phone: Regex::new(PHONE_PATTERN)?,
+            custom,
+            addresses: HashMap::new(),
+        })
+    }
+
+    pub fn apply(&mut self, text: &str) -> String {
+        let addresses = &mut self.addresses;
+        let text = self.email.replace_all(text, |caps: &Captures| {
+            let next = addresses.len() + 1;
+            let n = *addresses.entry(caps[0].to_ascii_lowercase()).or_insert(next);
+            format!("[email {}]", n)
+        });
+        let mut text = self.phone.replace_all(&text, "[phone]").into_owned();
+        for pattern in &self.custom {
+            text = pattern.replace_all(&text, "[redacted]").into_owned();
+        }
+        text
+    }
+}
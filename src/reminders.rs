@@ -0,0 +1,166 @@
+use crate::dates;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEADLINES_FILE: &str = "deadlines.json";
+// The TUI sets deadlines while the daemon marks reminders sent in the same file
+const DEADLINES_LOCK: &str = "deadlines.lock";
+// How far ahead of a deadline the first reminder goes out
+const REMIND_BEFORE_HOURS: i64 = 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deadline {
+    pub subject: String,
+    pub from: String,
+    pub due: DateTime<Local>,
+    #[serde(default)]
+    pub reminded_soon: bool,
+    #[serde(default)]
+    pub reminded_overdue: bool,
+}
+
+impl Deadline {
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        self.due <= now
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Deadlines {
+    // Keyed by message id
+    pub items: HashMap<String, Deadline>,
+}
+
+impl Deadlines {
+    pub fn load() -> Self {
+        crate::store::load(DEADLINES_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(DEADLINES_FILE, self)
+    }
+
+    pub fn get(&self, message_id: &str) -> Option<&Deadline> {
+        self.items.get(message_id)
+    }
+
+    /// Reloads the file under its lock, applies `change` and saves, so changes made meanwhile
+    /// by another process are kept.
+    pub fn update<R>(&mut self, change: impl FnOnce(&mut Self) -> R) -> Result<R> {
+        let _lock = crate::store::wait_for_lock_blocking(DEADLINES_LOCK)?;
+        *self = Self::load();
+        let result = change(self);
+        self.save()?;
+        Ok(result)
+    }
+
+    pub fn set(&mut self, message_id: &str, subject: &str, from: &str, due: DateTime<Local>) -> Result<()> {
+        let deadline = Deadline {
+            subject: subject.to_string(),
+            from: from.to_string(),
+            due,
+            reminded_soon: false,
+            reminded_overdue: false,
+        };
+        self.update(|deadlines| {
+            deadlines.items.insert(message_id.to_string(), deadline);
+        })
+    }
+
+    pub fn remove(&mut self, message_id: &str) -> Result<bool> {
+        self.update(|deadlines| deadlines.items.remove(message_id).is_some())
+    }
+
+    /// Deadlines sorted soonest first.
+    pub fn sorted(&self) -> Vec<(&String, &Deadline)> {
+        let mut items: Vec<_> = self.items.iter().collect();
+        items.sort_by_key(|(_, deadline)| deadline.due);
+        items
+    }
+
+    /// Marks and returns the deadlines that need a reminder now: one when a deadline comes
+    /// within a day, and one more once it has passed.
+    pub fn take_reminders(&mut self, now: DateTime<Local>) -> Vec<(Deadline, bool)> {
+        let mut reminders = Vec::new();
+        for deadline in self.items.values_mut() {
+            if deadline.is_overdue(now) {
+                if !deadline.reminded_overdue {
+                    deadline.reminded_overdue = true;
+                    deadline.reminded_soon = true;
+                    reminders.push((deadline.clone(), true));
+                }
+            } else if deadline.due - now <= Duration::hours(REMIND_BEFORE_HOURS) && !deadline.reminded_soon {
+                deadline.reminded_soon = true;
+                reminders.push((deadline.clone(), false));
+            }
+        }
+        reminders
+    }
+}
+
+/// Parses a deadline: a relative offset ("3d", "12h", "tomorrow") or a date, which means the
+/// end of that day.
+pub fn parse_deadline(value: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let value = value.trim();
+    let end_of_day = |date: chrono::NaiveDate| {
+        let time = NaiveTime::from_hms_opt(23, 59, 0).unwrap_or_default();
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .context("That time does not exist in the local timezone")
+    };
+
+    match value {
+        "today" => return end_of_day(now.date_naive()),
+        "tomorrow" => return end_of_day(now.date_naive() + Duration::days(1)),
+        _ => {}
+    }
+    if let Some(number) = value.strip_suffix('d').and_then(|n| n.parse::<i64>().ok()) {
+        return end_of_day(now.date_naive() + Duration::days(number));
+    }
+    if let Some(number) = value.strip_suffix('h').and_then(|n| n.parse::<i64>().ok()) {
+        return Ok(now + Duration::hours(number));
+    }
+    let date = dates::parse_date(value)
+        .with_context(|| format!("Unrecognised deadline '{}' (try 3d, 12h, tomorrow or YYYY-MM-DD)", value))?;
+    let due = end_of_day(date)?;
+    if due <= now {
+        bail!("Deadline {} is already in the past", value);
+    }
+    Ok(due)
+}
+
+/// Short relative form for list badges: "3d", "5h", "-2d" when overdue.
+pub fn describe_remaining(due: DateTime<Local>, now: DateTime<Local>) -> String {
+    let remaining = due - now;
+    let (sign, remaining) = if remaining < Duration::zero() { ("-", -remaining) } else { ("", remaining) };
+    if remaining >= Duration::days(1) {
+        format!("{}{}d", sign, remaining.num_days())
+    } else if remaining >= Duration::hours(1) {
+        format!("{}{}h", sign, remaining.num_hours())
+    } else {
+        format!("{}{}m", sign, remaining.num_minutes())
+    }
+}
+
+pub fn print_due(deadlines: &Deadlines) {
+    let now = Local::now();
+    let items = deadlines.sorted();
+    if items.is_empty() {
+        println!("No respond-by deadlines set.");
+        return;
+    }
+    for (id, deadline) in items {
+        let marker = if deadline.is_overdue(now) { "OVERDUE" } else { "due" };
+        println!(
+            "{:<7} {} ({})  {}",
+            marker,
+            deadline.due.format("%Y-%m-%d %H:%M"),
+            describe_remaining(deadline.due, now),
+            deadline.subject
+        );
+        println!("        from {}  [{}]", deadline.from, id);
+    }
+}
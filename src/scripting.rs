@@ -0,0 +1,106 @@
+use crate::plugins::SelectedMessage;
+use anyhow::{anyhow, Context, Result};
+use rhai::{Dynamic, Engine, Map};
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const SCRIPTS_DIR: &str = "scripts";
+// Keeps a runaway loop in a key binding from hanging the TUI
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a script asked for. Scripts run synchronously and only queue these; the TUI performs
+/// them afterwards so the API calls stay async.
+pub enum ScriptAction {
+    ModifyLabels { message_id: String, add: Vec<String>, remove: Vec<String> },
+    Compose { to: String, subject: String, body: String },
+    Status(String),
+}
+
+pub fn script_path(binding: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(binding);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        crate::store::data_path(SCRIPTS_DIR).map(|dir| dir.join(path))
+    }
+}
+
+fn selection_map(selected: Option<&SelectedMessage>) -> Dynamic {
+    let Some(message) = selected else { return Dynamic::UNIT };
+    let mut map = Map::new();
+    map.insert("id".into(), message.id.clone().into());
+    map.insert("thread_id".into(), message.thread_id.clone().into());
+    map.insert("from".into(), message.from.clone().into());
+    map.insert("subject".into(), message.subject.clone().into());
+    map.into()
+}
+
+/// Runs the script bound to a key. Exposed functions:
+/// `selection()` (a map with id, thread_id, from, subject, or `()` when nothing is selected),
+/// `add_label(id, label)`, `remove_label(id, label)`, `archive(id)`, `mark_read(id)`,
+/// `compose(to, subject, body)` and `status(text)`. Labels are Gmail label ids such as
+/// "STARRED" or "Label_12".
+pub fn run(binding: &str, selected: Option<&SelectedMessage>) -> Result<Vec<ScriptAction>> {
+    let path = script_path(binding).context("No config directory for scripts")?;
+    let source = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read script {}", path.display()))?;
+
+    let actions = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let selection = selection_map(selected);
+    engine.register_fn("selection", move || selection.clone());
+
+    let queue = actions.clone();
+    engine.register_fn("add_label", move |id: &str, label: &str| {
+        queue.borrow_mut().push(ScriptAction::ModifyLabels {
+            message_id: id.to_string(),
+            add: vec![label.to_string()],
+            remove: Vec::new(),
+        });
+    });
+    let queue = actions.clone();
+    engine.register_fn("remove_label", move |id: &str, label: &str| {
+        queue.borrow_mut().push(ScriptAction::ModifyLabels {
+            message_id: id.to_string(),
+            add: Vec::new(),
+            remove: vec![label.to_string()],
+        });
+    });
+    let queue = actions.clone();
+    engine.register_fn("archive", move |id: &str| {
+        queue.borrow_mut().push(ScriptAction::ModifyLabels {
+            message_id: id.to_string(),
+            add: Vec::new(),
+            remove: vec!["INBOX".to_string()],
+        });
+    });
+    let queue = actions.clone();
+    engine.register_fn("mark_read", move |id: &str| {
+        queue.borrow_mut().push(ScriptAction::ModifyLabels {
+            message_id: id.to_string(),
+            add: Vec::new(),
+            remove: vec!["UNREAD".to_string()],
+        });
+    });
+    let queue = actions.clone();
+    engine.register_fn("compose", move |to: &str, subject: &str, body: &str| {
+        queue.borrow_mut().push(ScriptAction::Compose {
+            to: to.to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+        });
+    });
+    let queue = actions.clone();
+    engine.register_fn("status", move |text: &str| {
+        queue.borrow_mut().push(ScriptAction::Status(text.to_string()));
+    });
+
+    engine
+        .run(&source)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    Ok(actions.take())
+}
@@ -0,0 +1,116 @@
+use crate::google_api;
+use crate::store;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes a file only the current user can read: tokens, credentials and cached mail.
+/// The file is created private rather than tightened afterwards, so it is never briefly
+/// readable by others, and an existing file is tightened before it is overwritten.
+pub fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)?;
+    }
+    if path.exists() {
+        restrict(path)?;
+    }
+    write_new(path, contents.as_ref()).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(unix)]
+fn write_new(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_new(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let existed = path.exists();
+    fs::write(path, contents)?;
+    if !existed {
+        restrict(path).map_err(std::io::Error::other)?;
+    }
+    Ok(())
+}
+
+pub fn create_private_dir(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        restrict(dir)?;
+    }
+    Ok(())
+}
+
+/// Whether users other than the owner can read or change `path`. Windows ACLs aren't
+/// inspected; `restrict` still tightens them.
+#[cfg(unix)]
+pub fn is_too_open(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o077 != 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_too_open(_path: &Path) -> bool {
+    false
+}
+
+/// Limits `path` to the current user: 0600 for files and 0700 for directories on Unix, and an
+/// ACL granting only the current user on Windows.
+#[cfg(unix)]
+pub fn restrict(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = if path.is_dir() { 0o700 } else { 0o600 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to restrict permissions of {}", path.display()))
+}
+
+#[cfg(windows)]
+pub fn restrict(path: &Path) -> Result<()> {
+    let user = std::env::var("USERNAME").context("USERNAME is not set")?;
+    // Drop inherited entries and grant full control to the current user alone
+    let status = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{}:(OI)(CI)F", user))
+        .stdout(std::process::Stdio::null())
+        .status()
+        .context("Failed to run icacls")?;
+    anyhow::ensure!(status.success(), "icacls could not restrict {}", path.display());
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn restrict(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// Every account's token, the OAuth client secret and the message cache directory
+fn secret_paths() -> Vec<PathBuf> {
+    let accounts = std::iter::once(None).chain(store::accounts().into_iter().map(Some));
+    let mut paths: Vec<PathBuf> = accounts.filter_map(|account| google_api::token_path_for(account.as_deref())).collect();
+    paths.extend(google_api::credentials_path());
+    paths.extend(crate::cache::messages_dir());
+    paths.into_iter().filter(|p| p.exists()).collect()
+}
+
+/// Secret files and directories that others can currently read.
+pub fn exposed() -> Vec<PathBuf> {
+    let mut exposed: Vec<PathBuf> = secret_paths().into_iter().filter(|p| is_too_open(p)).collect();
+    // Files cached before the cache was made private are checked one by one
+    if let Some(entries) = crate::cache::messages_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        exposed.extend(entries.flatten().map(|e| e.path()).filter(|p| is_too_open(p)));
+    }
+    exposed
+}
+
+/// Tightens everything `exposed` reports, returning what was changed. On Windows, where
+/// nothing is reported, every secret path is restricted.
+pub fn migrate() -> Result<Vec<PathBuf>> {
+    let targets = if cfg!(windows) { secret_paths() } else { exposed() };
+    for path in &targets {
+        restrict(path)?;
+    }
+    Ok(targets)
+}
@@ -0,0 +1,78 @@
+use crate::address;
+use crate::google_api::{self, ApiToken, SendAs};
+use crate::mime::OutgoingMessage;
+use anyhow::{bail, Context, Result};
+
+fn domain(address: &str) -> &str {
+    address.rsplit_once('@').map(|(_, domain)| domain).unwrap_or_default()
+}
+
+/// The account's send-as identities, fetched once and checked against each From header.
+pub struct Identities {
+    aliases: Vec<SendAs>,
+}
+
+impl Identities {
+    pub async fn load(token: &ApiToken) -> Result<Self> {
+        let aliases = google_api::list_send_as(token)
+            .await
+            .context("Couldn't list the account's send-as aliases to check the From address")?;
+        Ok(Identities { aliases })
+    }
+
+    /// Refuses a From Gmail would silently rewrite or reject, and returns warnings for ones that
+    /// may fail DMARC alignment at the receiving end.
+    pub fn check(&self, from: &str) -> Result<Vec<String>> {
+        let addresses = address::split_list(from);
+        if addresses.len() != 1 {
+            bail!("From must be exactly one address, not {:?}", from);
+        }
+        let sender = address::extract_address(&addresses[0]);
+        let Some(alias) = self.aliases.iter().find(|a| a.send_as_email.eq_ignore_ascii_case(&sender)) else {
+            let primary = self.aliases.iter().find(|a| a.is_primary).map(|a| a.send_as_email.as_str()).unwrap_or("the account's address");
+            bail!(
+                "{} isn't a send-as alias of this account; Gmail would send from {} instead. Add it under Settings > Accounts > Send mail as",
+                sender,
+                primary
+            );
+        };
+        if let Some(status) = &alias.verification_status
+            && status != "accepted"
+        {
+            bail!("The send-as alias {} isn't verified yet ({})", sender, status);
+        }
+
+        let mut warnings = Vec::new();
+        let name = address::display_name(&addresses[0]);
+        if name != sender && !alias.display_name.is_empty() && name != alias.display_name {
+            warnings.push(format!(
+                "From name {:?} differs from the alias's configured name {:?}; receivers may show either",
+                name, alias.display_name
+            ));
+        }
+        // Gmail's servers only sign and pass SPF for its own domains; a custom domain sent without
+        // its own SMTP server needs Google in that domain's SPF/DKIM records to align
+        let primary_domain = self.aliases.iter().find(|a| a.is_primary).map(|a| domain(&a.send_as_email)).unwrap_or_default();
+        let alias_domain = domain(&alias.send_as_email);
+        if !alias.is_primary && alias.smtp_msa.is_none() && !alias_domain.eq_ignore_ascii_case(primary_domain) {
+            warnings.push(format!(
+                "Mail from {} goes out through Gmail's servers; unless {} authorizes Google in its SPF/DKIM records, receivers enforcing DMARC may reject it or mark it as spam",
+                sender, alias_domain
+            ));
+        }
+        Ok(warnings)
+    }
+}
+
+/// Checks the message's From, if it has one. Offline, the check is skipped with a warning so
+/// the message can still be queued.
+pub async fn check_message(token: &ApiToken, message: &OutgoingMessage) -> Result<Vec<String>> {
+    let Some(from) = message.from() else {
+        return Ok(Vec::new());
+    };
+    match Identities::load(token).await {
+        Ok(identities) => identities.check(from),
+        Err(e) if google_api::is_network_error(&e) => Ok(vec![format!("Offline; {} wasn't checked against the send-as aliases", from)]),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,102 @@
+use crate::address;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const SENDERS_FILE: &str = "senders.json";
+
+/// Addresses marked from the viewer's address menu. VIPs get a star in the list; mail from
+/// blocked senders is hidden from list views. Both are local to this machine.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SenderLists {
+    #[serde(default)]
+    pub vips: BTreeSet<String>,
+    #[serde(default)]
+    pub blocked: BTreeSet<String>,
+    // How to show mail from an address, set with :prefs in the viewer
+    #[serde(default)]
+    pub render: BTreeMap<String, RenderPrefs>,
+}
+
+/// Remembered per sender and applied whenever one of their messages is opened.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct RenderPrefs {
+    // Show the HTML part even when there is a plain-text one
+    pub prefer_html: bool,
+    pub reader: bool,
+    // List the remote images the message loads under the body
+    pub remote_images: bool,
+    // Columns to render HTML at, instead of 80
+    pub width: Option<usize>,
+}
+
+impl RenderPrefs {
+    pub fn describe(&self) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        format!(
+            "html {} | reader {} | images {} | width {}",
+            on_off(self.prefer_html),
+            on_off(self.reader),
+            on_off(self.remote_images),
+            self.width.map_or("auto".to_string(), |w| w.to_string())
+        )
+    }
+}
+
+impl SenderLists {
+    pub fn load() -> Self {
+        crate::store::load(SENDERS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(SENDERS_FILE, self)
+    }
+
+    // Accepts a whole header value such as "Jane <jane@example.com>"
+    pub fn is_vip(&self, from: &str) -> bool {
+        self.vips.contains(&address::extract_address(from))
+    }
+
+    pub fn is_blocked(&self, from: &str) -> bool {
+        self.blocked.contains(&address::extract_address(from))
+    }
+
+    pub fn render_prefs(&self, from: &str) -> RenderPrefs {
+        self.render.get(&address::extract_address(from)).cloned().unwrap_or_default()
+    }
+
+    /// Stores the preferences for `from`; defaults remove the entry.
+    pub fn set_render_prefs(&mut self, from: &str, prefs: RenderPrefs) -> Result<()> {
+        let address = address::extract_address(from);
+        if prefs == RenderPrefs::default() {
+            self.render.remove(&address);
+        } else {
+            self.render.insert(address, prefs);
+        }
+        self.save()
+    }
+
+    /// Adds or removes a VIP, returning whether the address is now one.
+    pub fn toggle_vip(&mut self, address: &str) -> Result<bool> {
+        let added = toggle(&mut self.vips, address);
+        self.save()?;
+        Ok(added)
+    }
+
+    /// Blocks or unblocks an address, returning whether it is now blocked.
+    pub fn toggle_blocked(&mut self, address: &str) -> Result<bool> {
+        let added = toggle(&mut self.blocked, address);
+        self.save()?;
+        Ok(added)
+    }
+}
+
+fn toggle(set: &mut BTreeSet<String>, address: &str) -> bool {
+    if set.remove(address) {
+        false
+    } else {
+        set.insert(address.to_string());
+        true
+    }
+}
@@ -0,0 +1,144 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::{bail, Result};
+
+/// What Gmail does with its own copy of forwarded mail.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Disposition {
+    LeaveInInbox,
+    Archive,
+    Trash,
+    MarkRead,
+}
+
+impl Disposition {
+    fn api_value(self) -> &'static str {
+        match self {
+            Disposition::LeaveInInbox => "leaveInInbox",
+            Disposition::Archive => "archive",
+            Disposition::Trash => "trash",
+            Disposition::MarkRead => "markRead",
+        }
+    }
+}
+
+// Gmail's enum values read as words, e.g. "leaveInInbox" -> "leave in inbox"
+fn humanize(value: &str) -> String {
+    let mut words = String::new();
+    for c in value.chars() {
+        if c.is_ascii_uppercase() {
+            words.push(' ');
+        }
+        words.push(c.to_ascii_lowercase());
+    }
+    words
+}
+
+/// Prints the account settings that decide where mail ends up besides this mailbox.
+pub async fn show(token: &ApiToken) -> Result<()> {
+    let (language, imap, pop, forwarding, addresses) = tokio::try_join!(
+        google_api::get_language(token),
+        google_api::get_imap(token),
+        google_api::get_pop(token),
+        google_api::get_auto_forwarding(token),
+        google_api::list_forwarding_addresses(token),
+    )?;
+
+    println!("Display language:     {}", language.display_language);
+
+    let mut imap_line = if imap.enabled { "enabled".to_string() } else { "disabled".to_string() };
+    if imap.enabled {
+        let mut details = Vec::new();
+        if imap.auto_expunge == Some(true) {
+            details.push("auto-expunge".to_string());
+        }
+        if let Some(behavior) = &imap.expunge_behavior {
+            details.push(format!("expunged mail: {}", humanize(behavior)));
+        }
+        if let Some(size) = imap.max_folder_size.filter(|size| *size > 0) {
+            details.push(format!("folders limited to {} messages", size));
+        }
+        if !details.is_empty() {
+            imap_line = format!("{} ({})", imap_line, details.join(", "));
+        }
+    }
+    println!("IMAP:                 {}", imap_line);
+
+    let pop_line = match pop.access_window.as_deref() {
+        None | Some("disabled") => "disabled".to_string(),
+        Some(window) => {
+            let after = pop.disposition.as_deref().map(humanize).unwrap_or_else(|| "unchanged".to_string());
+            format!("enabled for {} (then: {})", humanize(window), after)
+        }
+    };
+    println!("POP:                  {}", pop_line);
+
+    let forwarding_line = match (&forwarding.email_address, forwarding.enabled) {
+        (Some(address), true) => {
+            let after = forwarding.disposition.as_deref().map(humanize).unwrap_or_else(|| "unchanged".to_string());
+            format!("on, to {} (then: {})", address, after)
+        }
+        _ => "off".to_string(),
+    };
+    println!("Auto-forwarding:      {}", forwarding_line);
+
+    if addresses.is_empty() {
+        println!("Forwarding addresses: none");
+    } else {
+        println!("Forwarding addresses:");
+        for address in addresses {
+            let status = address.verification_status.as_deref().map(humanize).unwrap_or_default();
+            println!("  {}  {}", address.forwarding_email, status);
+        }
+    }
+    Ok(())
+}
+
+/// Lists forwarding addresses with their verification status, marking the one in use.
+pub async fn list_forwarding(token: &ApiToken) -> Result<()> {
+    let (forwarding, addresses) =
+        tokio::try_join!(google_api::get_auto_forwarding(token), google_api::list_forwarding_addresses(token))?;
+    if addresses.is_empty() {
+        println!("No forwarding addresses");
+    }
+    for address in addresses {
+        let active = forwarding.enabled && forwarding.email_address.as_deref() == Some(address.forwarding_email.as_str());
+        let status = address.verification_status.as_deref().map(humanize).unwrap_or_default();
+        println!("{} {}  {}", if active { "*" } else { " " }, address.forwarding_email, status);
+    }
+    Ok(())
+}
+
+/// Forwards all new mail to `address`, adding it first if needed. Google only forwards to
+/// confirmed addresses, so a new one stops here until its confirmation link has been clicked.
+pub async fn enable_forwarding(token: &ApiToken, address: &str, then: Disposition) -> Result<()> {
+    let addresses = google_api::list_forwarding_addresses(token).await?;
+    let status = match addresses.into_iter().find(|a| a.forwarding_email.eq_ignore_ascii_case(address)) {
+        Some(existing) => existing.verification_status,
+        None => {
+            let created = google_api::create_forwarding_address(token, address).await?;
+            println!("Added {} as a forwarding address", created.forwarding_email);
+            created.verification_status
+        }
+    };
+    if status.as_deref() != Some("accepted") {
+        bail!(
+            "{} isn't confirmed yet. Google mailed it a confirmation link; run this again once it has been clicked",
+            address
+        );
+    }
+    let settings = google_api::AutoForwarding {
+        enabled: true,
+        email_address: Some(address.to_string()),
+        disposition: Some(then.api_value().to_string()),
+    };
+    google_api::update_auto_forwarding(token, &settings).await?;
+    println!("Forwarding new mail to {} (then: {})", address, humanize(then.api_value()));
+    Ok(())
+}
+
+pub async fn disable_forwarding(token: &ApiToken) -> Result<()> {
+    let settings = google_api::AutoForwarding { enabled: false, email_address: None, disposition: None };
+    google_api::update_auto_forwarding(token, &settings).await?;
+    println!("Auto-forwarding is off");
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::config::SpeechConfig;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+/// A message being read aloud. The speech command is stopped when this is dropped, so
+/// replacing or clearing it is enough to silence it.
+pub struct Speech {
+    child: Child,
+}
+
+impl Speech {
+    /// Starts the configured command (or `say` on macOS, `espeak --stdin` elsewhere) with
+    /// `text` on its stdin.
+    pub fn start(text: &str, config: &SpeechConfig) -> Result<Self> {
+        let default = if cfg!(target_os = "macos") { "say" } else { "espeak --stdin" };
+        let command = config.command.as_deref().unwrap_or(default);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", command))?;
+        // Speech engines read as they speak, so a long message would block the UI on a full pipe
+        if let Some(mut stdin) = child.stdin.take() {
+            let text = text.to_string();
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(text.as_bytes());
+            });
+        }
+        Ok(Speech { child })
+    }
+
+    /// Whether the command is still speaking; false once it finished or failed.
+    pub fn is_playing(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Speech {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
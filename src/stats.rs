@@ -0,0 +1,107 @@
+use crate::address;
+use crate::google_api::MessageDetail;
+use std::collections::HashMap;
+
+/// Reply delays with one correspondent, in seconds.
+#[derive(Default, Debug)]
+pub struct ResponseTimes {
+    // How long I took to answer them
+    pub mine: Vec<i64>,
+    // How long they took to answer me
+    pub theirs: Vec<i64>,
+}
+
+fn is_sent(message: &MessageDetail) -> bool {
+    message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == "SENT"))
+}
+
+/// Walks each cached thread in order and times every switch between them and me: their
+/// message followed by my reply, or mine followed by theirs. Keyed by their address.
+pub fn response_times(messages: &[MessageDetail]) -> HashMap<String, ResponseTimes> {
+    let mut threads: HashMap<&str, Vec<(i64, &MessageDetail)>> = HashMap::new();
+    for message in messages {
+        if let Some(at) = message.received_at() {
+            threads.entry(message.thread_id.as_str()).or_default().push((at, message));
+        }
+    }
+
+    let mut times: HashMap<String, ResponseTimes> = HashMap::new();
+    for thread in threads.values_mut() {
+        thread.sort_by_key(|(at, _)| *at);
+        for pair in thread.windows(2) {
+            let ((asked_at, asked), (answered_at, answered)) = (pair[0], pair[1]);
+            let delay = answered_at - asked_at;
+            match (is_sent(asked), is_sent(answered)) {
+                (false, true) => {
+                    let them = address::extract_address(&asked.get_header("From"));
+                    times.entry(them).or_default().mine.push(delay);
+                }
+                (true, false) => {
+                    let them = address::extract_address(&answered.get_header("From"));
+                    times.entry(them).or_default().theirs.push(delay);
+                }
+                _ => {}
+            }
+        }
+    }
+    times
+}
+
+pub fn median(values: &[i64]) -> Option<i64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(sorted[n / 2]),
+        n => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2),
+    }
+}
+
+/// "45m", "3h", "2d".
+pub fn describe_delay(seconds: i64) -> String {
+    match seconds {
+        s if s < 3600 => format!("{}m", (s / 60).max(1)),
+        s if s < 86_400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86_400),
+    }
+}
+
+fn describe_median(values: &[i64]) -> String {
+    match median(values) {
+        Some(delay) => format!("{} ({})", describe_delay(delay), values.len()),
+        None => "-".to_string(),
+    }
+}
+
+/// Lines for the sender panel in the viewer.
+pub fn describe_correspondent(times: Option<&ResponseTimes>) -> String {
+    let Some(times) = times else {
+        return "No replies either way in cached threads.".to_string();
+    };
+    format!(
+        "I reply in: {}\nThey reply in: {}\n(median, number of replies)",
+        describe_median(&times.mine),
+        describe_median(&times.theirs)
+    )
+}
+
+/// Correspondents with the most exchanges first.
+pub fn print_responsiveness(messages: &[MessageDetail], limit: usize) {
+    let times = response_times(messages);
+    if times.is_empty() {
+        println!("No replies found in cached threads. Open some conversations in the TUI first.");
+        return;
+    }
+    let mut rows: Vec<_> = times.iter().collect();
+    rows.sort_by_key(|(address, t)| (std::cmp::Reverse(t.mine.len() + t.theirs.len()), address.to_string()));
+    println!("{:<40} {:>14} {:>14}", "Correspondent", "I reply in", "They reply in");
+    for (address, times) in rows.into_iter().take(limit) {
+        println!(
+            "{:<40} {:>14} {:>14}",
+            crate::text::truncate_to_width(address, 40),
+            describe_median(&times.mine),
+            describe_median(&times.theirs)
+        );
+    }
+    println!("\nMedians from cached messages; the count of replies is in parentheses.");
+}
@@ -0,0 +1,194 @@
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+const ACCOUNTS_DIR: &str = "accounts";
+
+static ACCOUNT: RwLock<Option<String>> = RwLock::new(None);
+
+/// Selects the account whose token, state files and cache are used from now on. `None` is
+/// the original single-account location.
+pub fn set_account(name: Option<&str>) -> Result<()> {
+    if let Some(name) = name
+        && (name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']))
+    {
+        bail!("Invalid account name '{}'", name);
+    }
+    if let Ok(mut account) = ACCOUNT.write() {
+        *account = name.map(str::to_string);
+    }
+    Ok(())
+}
+
+pub fn account() -> Option<String> {
+    ACCOUNT.read().ok().and_then(|account| account.clone())
+}
+
+/// Named accounts that have state on this machine, sorted.
+pub fn accounts() -> Vec<String> {
+    let Some(dir) = paths::state_dir().map(|dir| dir.join(ACCOUNTS_DIR)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// `base`, or the selected account's directory inside it.
+pub fn app_dir(base: PathBuf) -> PathBuf {
+    account_dir(base, account().as_deref())
+}
+
+fn account_dir(base: PathBuf, account: Option<&str>) -> PathBuf {
+    match account {
+        Some(name) => base.join(ACCOUNTS_DIR).join(name),
+        None => base,
+    }
+}
+
+/// Shared by all accounts, like the config file and scripts.
+pub fn data_path(name: &str) -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join(name))
+}
+
+/// Belongs to the selected account.
+pub fn state_path(name: &str) -> Option<PathBuf> {
+    paths::state_dir().map(|dir| app_dir(dir).join(name))
+}
+
+/// Belongs to `account`, whichever account is selected.
+pub fn account_state_path(account: Option<&str>, name: &str) -> Option<PathBuf> {
+    paths::state_dir().map(|dir| account_dir(dir, account).join(name))
+}
+
+/// A log file of the selected account.
+pub fn log_path(name: &str) -> Option<PathBuf> {
+    paths::log_dir().map(|dir| app_dir(dir).join(name))
+}
+
+// Missing or unreadable state files fall back to the default rather than failing startup
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    load_path(state_path(name))
+}
+
+/// Like `load`, for a file of `account` rather than the selected account.
+pub fn load_for<T: DeserializeOwned + Default>(account: Option<&str>, name: &str) -> T {
+    load_path(account_state_path(account, name))
+}
+
+fn load_path<T: DeserializeOwned + Default>(path: Option<PathBuf>) -> T {
+    path
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Reports whether an existing state file still parses; `load` would silently reset it.
+pub fn verify<T: DeserializeOwned>(name: &str) -> Result<()> {
+    let Some(path) = state_path(name).filter(|path| path.exists()) else {
+        return Ok(());
+    };
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str::<T>(&content).with_context(|| format!("{} is not valid", path.display()))?;
+    Ok(())
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    if let Some(path) = state_path(name) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(value)?)?;
+    }
+    Ok(())
+}
+
+/// An advisory lock on a profile's state, held until dropped. The OS releases it if the
+/// process dies, so a crash never leaves a stale lock behind.
+pub struct Lock {
+    _file: File,
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    if error.kind() == std::io::ErrorKind::WouldBlock { Ok(false) } else { Err(error) }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+fn open_lock_file(name: &str) -> Result<File> {
+    let Some(path) = state_path(name) else {
+        bail!("No config directory to keep state in");
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Waits for the lock file `name`. Guards a load, change, save cycle on a state file that the
+/// TUI, the daemon and their background tasks all write, so none of them saves over another's
+/// changes. Separate opens conflict even within one process.
+pub async fn wait_for_lock(name: &str) -> Result<Lock> {
+    let file = open_lock_file(name)?;
+    while !try_lock(&file)? {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    Ok(Lock { _file: file })
+}
+
+/// `wait_for_lock` for code that isn't async. It blocks the thread while waiting, which is
+/// only ever as long as another load, change, save cycle takes.
+pub fn wait_for_lock_blocking(name: &str) -> Result<Lock> {
+    let file = open_lock_file(name)?;
+    while !try_lock(&file)? {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    Ok(Lock { _file: file })
+}
+
+/// Takes the lock file `name`, failing with the holder's pid when another process has it.
+pub fn lock(name: &str) -> Result<Lock> {
+    let mut file = open_lock_file(name)?;
+
+    if !try_lock(&file)? {
+        let mut holder = String::new();
+        file.read_to_string(&mut holder).ok();
+        let holder = holder.trim();
+        let holder = if holder.is_empty() { String::new() } else { format!(" (pid {})", holder) };
+        bail!(
+            "Another gmail-cli session{} is already using this profile. Close it first; \
+             running both would overwrite each other's outbox and state files.",
+            holder
+        );
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(Lock { _file: file })
+}
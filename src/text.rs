@@ -0,0 +1,147 @@
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+const TAB_WIDTH: usize = 4;
+
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Cuts `s` to at most `max_width` terminal columns on grapheme boundaries, ending with an
+/// ellipsis when anything was dropped, so wide emoji/CJK are never split or overflow a column.
+pub fn truncate_to_width(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let budget = max_width - display_width(ELLIPSIS);
+    let mut out = String::new();
+    let mut used = 0;
+    for grapheme in s.graphemes(true) {
+        let width = display_width(grapheme);
+        if used + width > budget {
+            break;
+        }
+        out.push_str(grapheme);
+        used += width;
+    }
+    out.push_str(ELLIPSIS);
+    out
+}
+
+/// Single-line form for table cells: newlines, tabs and other control characters become
+/// spaces (terminals render them unpredictably), and runs of whitespace collapse.
+pub fn single_line(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Multi-line form for the preview and viewer: keeps newlines, expands tabs and drops the
+/// remaining control characters (e.g. stray \r or escape sequences).
+pub fn sanitize_multiline(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for line in s.lines() {
+        let mut column = 0;
+        for c in line.chars() {
+            match c {
+                '\t' => {
+                    let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                    out.push_str(&" ".repeat(spaces));
+                    column += spaces;
+                }
+                c if c.is_control() => {}
+                c => {
+                    out.push(c);
+                    column += unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn starts_quoted_trail(lines: &[&str]) -> bool {
+    let line = lines[0].trim();
+    if line.starts_with("-----Original Message-----") || line.starts_with("________________________________") {
+        return true;
+    }
+    // "On Mon, 3 Jun 2024 at 10:00, Alice <a@example.com> wrote:", possibly wrapped
+    if line.starts_with("On ") {
+        return lines.iter().take(3).any(|l| l.trim_end().ends_with("wrote:"));
+    }
+    // A quote that runs to the end; quotes between replies are kept
+    line.starts_with('>') && lines.iter().all(|l| l.trim().is_empty() || l.trim_start().starts_with('>'))
+}
+
+/// The new part of a message for previews: cut at a "-- " signature delimiter or where a
+/// quoted trail of earlier messages begins. Returns the whole body if nothing would be left.
+pub fn new_content(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let cut = (0..lines.len())
+        .find(|&i| matches!(lines[i].trim_end_matches('\r'), "-- " | "--") || starts_quoted_trail(&lines[i..]))
+        .unwrap_or(lines.len());
+    let content = lines[..cut].join("\n");
+    let content = content.trim();
+    if content.is_empty() { body.trim().to_string() } else { content.to_string() }
+}
+
+fn is_rtl_char(c: char) -> bool {
+    matches!(c as u32, 0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF)
+}
+
+/// True when strong right-to-left letters (Hebrew, Arabic, ...) outnumber left-to-right ones.
+pub fn is_rtl_dominant(s: &str) -> bool {
+    let (mut rtl, mut ltr) = (0usize, 0usize);
+    for c in s.chars().filter(|c| c.is_alphabetic()) {
+        if is_rtl_char(c) {
+            rtl += 1;
+        } else {
+            ltr += 1;
+        }
+    }
+    rtl > ltr
+}
+
+/// Word-wraps one line to `width` columns. Words longer than the width are left whole.
+pub fn wrap_to_width(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split(' ') {
+        let needed = display_width(&current) + display_width(word) + usize::from(!current.is_empty());
+        if !current.is_empty() && needed > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    lines.push(current);
+    lines
+}
+
+/// Wraps RTL text to `width` and reorders each resulting line into visual (left-to-right
+/// screen) order. Wrapping has to happen before reordering, otherwise the terminal would
+/// break lines in the wrong place.
+pub fn bidi_visual_lines(s: &str, width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in s.lines() {
+        for wrapped in wrap_to_width(line, width.max(1)) {
+            let info = BidiInfo::new(&wrapped, Some(Level::rtl()));
+            match info.paragraphs.first() {
+                Some(paragraph) => out.push(info.reorder_line(paragraph, paragraph.range.clone()).into_owned()),
+                None => out.push(String::new()),
+            }
+        }
+    }
+    out
+}
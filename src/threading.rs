@@ -0,0 +1,84 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+pub const THREAD_OVERRIDES_FILE: &str = "thread_overrides.json";
+
+/// Local corrections to Gmail's threading, for when its subject-based grouping gets a
+/// conversation wrong. Only changes what the list and timeline show; nothing is sent to Gmail.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ThreadOverrides {
+    // Thread id -> the thread it is shown together with
+    #[serde(default)]
+    pub merged: HashMap<String, String>,
+    // Message ids shown on their own, outside their Gmail thread
+    #[serde(default)]
+    pub split: HashSet<String>,
+}
+
+impl ThreadOverrides {
+    pub fn load() -> Self {
+        crate::store::load(THREAD_OVERRIDES_FILE)
+    }
+
+    pub fn load_for(account: Option<&str>) -> Self {
+        crate::store::load_for(account, THREAD_OVERRIDES_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(THREAD_OVERRIDES_FILE, self)
+    }
+
+    /// The thread a conversation is grouped under: itself unless it was merged into another.
+    pub fn group<'a>(&'a self, thread_id: &'a str) -> &'a str {
+        let mut group = thread_id;
+        // Merges always point at a group root, but a hand-edited file could still loop
+        for _ in 0..self.merged.len() {
+            match self.merged.get(group) {
+                Some(next) => group = next,
+                None => break,
+            }
+        }
+        group
+    }
+
+    /// Every thread shown as part of `thread_id`'s group, the root included.
+    pub fn members(&self, thread_id: &str) -> Vec<String> {
+        let group = self.group(thread_id);
+        let mut members = vec![group.to_string()];
+        members.extend(self.merged.keys().filter(|t| self.group(t) == group).cloned());
+        members
+    }
+
+    pub fn is_split(&self, message_id: &str) -> bool {
+        self.split.contains(message_id)
+    }
+
+    pub fn merge(&mut self, thread_id: &str, into: &str) -> Result<()> {
+        let (from, to) = (self.group(thread_id).to_string(), self.group(into).to_string());
+        if from == to {
+            bail!("These conversations are already shown together");
+        }
+        self.merged.insert(from, to);
+        self.save()
+    }
+
+    /// Undoes every merge in the thread's group, returning how many threads were separated.
+    pub fn unmerge(&mut self, thread_id: &str) -> Result<usize> {
+        let members = self.members(thread_id);
+        let before = self.merged.len();
+        self.merged.retain(|t, _| !members.contains(t));
+        self.save()?;
+        Ok(before - self.merged.len())
+    }
+
+    /// Takes a message out of its thread, or puts it back, returning whether it is now split.
+    pub fn toggle_split(&mut self, message_id: &str) -> Result<bool> {
+        let now_split = !self.split.remove(message_id);
+        if now_split {
+            self.split.insert(message_id.to_string());
+        }
+        self.save()?;
+        Ok(now_split)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::google_api::{self, ApiToken};
+use crate::threading::ThreadOverrides;
+use anyhow::Result;
+
+/// One message on a thread's timeline.
+pub struct Point {
+    pub id: String,
+    pub from: String,
+    pub at: i64,
+}
+
+/// The conversation's messages, oldest first, following any local merge or split
+/// overrides. Drafts are left out.
+pub async fn load(token: &ApiToken, thread_id: &str, overrides: &ThreadOverrides) -> Result<Vec<Point>> {
+    let members = overrides.members(thread_id);
+    let threads = futures::future::join_all(members.iter().map(|t| google_api::get_thread(token, t, &["From"]))).await;
+    let mut points: Vec<Point> = threads
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|t| t.messages.unwrap_or_default())
+        .filter(|m| !m.label_ids.as_ref().is_some_and(|l| l.iter().any(|l| l == "DRAFT")))
+        .filter(|m| !overrides.is_split(&m.id))
+        .filter_map(|m| Some(Point { at: m.received_at()?, from: m.get_header("From"), id: m.id }))
+        .collect();
+    points.sort_by_key(|p| p.at);
+    Ok(points)
+}
+
+/// Where each point goes on an axis `width` columns wide, in proportion to its time: the
+/// first message at column 0 and the last at the end.
+pub fn columns(points: &[Point], width: usize) -> Vec<usize> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return Vec::new();
+    };
+    let span = (last.at - first.at).max(1) as f64;
+    let end = width.saturating_sub(1) as f64;
+    points.iter().map(|p| ((p.at - first.at) as f64 / span * end).round() as usize).collect()
+}
@@ -0,0 +1,56 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// The steps between launch and a readable message, printed with --timings and shown in the
+// debug overlay (F12)
+pub const AUTH: &str = "auth";
+pub const LIST: &str = "list";
+pub const HEADERS: &str = "header batch";
+pub const FIRST_ROW: &str = "first row rendered";
+pub const FIRST_BODY: &str = "first body displayed";
+
+static START: OnceLock<Instant> = OnceLock::new();
+static RECORDED: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+struct Timing {
+    name: &'static str,
+    // None for milestones, which are only a point in time
+    took: Option<Duration>,
+    // Since launch, when the step finished
+    at: Duration,
+}
+
+/// Marks launch; milestones are measured from here.
+pub fn start() {
+    START.get_or_init(Instant::now);
+}
+
+/// Records a step that began at `began`. Only the first of each name is kept: later pages and
+/// view switches aren't part of startup.
+pub fn record(name: &'static str, began: Instant) {
+    push(name, Some(began.elapsed()));
+}
+
+/// Records reaching a milestone, the first time only.
+pub fn mark(name: &'static str) {
+    push(name, None);
+}
+
+fn push(name: &'static str, took: Option<Duration>) {
+    let at = START.get_or_init(Instant::now).elapsed();
+    let mut recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    if !recorded.iter().any(|t| t.name == name) {
+        recorded.push(Timing { name, took, at });
+    }
+}
+
+pub fn lines() -> Vec<String> {
+    let recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    recorded
+        .iter()
+        .map(|t| match t.took {
+            Some(took) => format!("{:<21} {:>6} ms, done at {} ms", t.name, took.as_millis(), t.at.as_millis()),
+            None => format!("{:<21} at {} ms", t.name, t.at.as_millis()),
+        })
+        .collect()
+}
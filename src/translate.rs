@@ -0,0 +1,65 @@
+use crate::config::TranslateConfig;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Deserialize)]
+struct DeeplResponse {
+    translations: Vec<DeeplTranslation>,
+}
+
+#[derive(Deserialize)]
+struct DeeplTranslation {
+    detected_source_language: Option<String>,
+    text: String,
+}
+
+/// Translates `text` into `translate.target_lang` with the configured command, or DeepL when
+/// only an API key is set. Returns the translation and, when known, the source language.
+pub async fn translate(text: &str, config: &TranslateConfig) -> Result<(String, Option<String>)> {
+    if let Some(command) = &config.command {
+        return Ok((run_command(&command.replace("{lang}", &config.target_lang), text).await?, None));
+    }
+    let key = config.deepl_api_key.clone().or_else(|| std::env::var("DEEPL_AUTH_KEY").ok());
+    let Some(key) = key else {
+        bail!("Set translate.command (e.g. \"trans -b :{{lang}}\") or translate.deepl_api_key to translate");
+    };
+    let res = crate::google_api::http_client()
+        .post(&config.deepl_url)
+        .header("Authorization", format!("DeepL-Auth-Key {}", key))
+        .form(&[("text", text), ("target_lang", config.target_lang.as_str())])
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        bail!("DeepL refused the request ({}): {}", res.status(), res.text().await.unwrap_or_default());
+    }
+    let response: DeeplResponse = res.json().await?;
+    let translation = response.translations.into_iter().next().context("DeepL returned no translation")?;
+    Ok((translation.text, translation.detected_source_language))
+}
+
+// The text goes to the command's stdin and its stdout is the translation
+async fn run_command(command: &str, text: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+    // Written alongside reading the output; a command that streams as it reads would otherwise
+    // fill its output pipe while we are still writing
+    if let Some(mut stdin) = child.stdin.take() {
+        let text = text.to_string();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(text.as_bytes()).await;
+        });
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!("{} failed: {}", command, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
@@ -0,0 +1,154 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+pub const TRASH_FILE: &str = "trashed.json";
+// Gmail deletes trashed mail permanently after this long
+pub const RETENTION_DAYS: i64 = 30;
+const LIST_PAGE_SIZE: u32 = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashedMessage {
+    pub trashed_at: DateTime<Local>,
+    // Gmail doesn't say when a message was trashed. Messages trashed elsewhere are dated from
+    // when they were first seen in Trash, so the real date may be earlier.
+    pub observed: bool,
+}
+
+/// When each message went to Trash, keyed by message id.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TrashLog {
+    pub items: HashMap<String, TrashedMessage>,
+}
+
+impl TrashLog {
+    pub fn load() -> Self {
+        crate::store::load(TRASH_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(TRASH_FILE, self)
+    }
+
+    pub fn record(&mut self, message_id: &str) -> Result<()> {
+        self.items.insert(
+            message_id.to_string(),
+            TrashedMessage { trashed_at: Local::now(), observed: false },
+        );
+        self.save()
+    }
+
+    // For a message taken back out of Trash
+    pub fn forget(&mut self, message_id: &str) -> Result<()> {
+        if self.items.remove(message_id).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Notes a message seen in Trash; returns whether it was new to the log.
+    pub fn observe(&mut self, message_id: &str) -> bool {
+        if self.items.contains_key(message_id) {
+            return false;
+        }
+        self.items.insert(
+            message_id.to_string(),
+            TrashedMessage { trashed_at: Local::now(), observed: true },
+        );
+        true
+    }
+
+    /// Days until Gmail deletes the message, and whether that is only an upper bound.
+    pub fn days_left(&self, message_id: &str, now: DateTime<Local>) -> Option<(i64, bool)> {
+        let entry = self.items.get(message_id)?;
+        let deleted_at = entry.trashed_at + Duration::days(RETENTION_DAYS);
+        Some(((deleted_at - now).num_days().max(0), entry.observed))
+    }
+}
+
+/// Permanently deletes one message, refusing unless it is already in Trash. Returns its subject.
+pub async fn delete_trashed(token: &ApiToken, message_id: &str) -> Result<String> {
+    let detail = google_api::get_message_headers(token, message_id, &["Subject"]).await?;
+    if !detail.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == "TRASH")) {
+        bail!("{} isn't in Trash; move it there first", message_id);
+    }
+    google_api::delete_message(token, message_id).await?;
+    let mut log = TrashLog::load();
+    log.forget(message_id)?;
+    Ok(detail.get_header("Subject"))
+}
+
+async fn list_trash(token: &ApiToken) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    let mut page_token = None;
+    loop {
+        let page = google_api::MessagesList::new()
+            .label_ids(&["TRASH"])
+            .include_spam_trash(true)
+            .max_results(LIST_PAGE_SIZE)
+            .page_token(page_token.as_deref())
+            .send(token)
+            .await?;
+        ids.extend(page.messages.unwrap_or_default().into_iter().map(|m| m.id));
+        page_token = page.next_page_token;
+        if page_token.is_none() {
+            return Ok(ids);
+        }
+    }
+}
+
+/// Permanently deletes trashed messages that have been in Trash at least `older_than_days`.
+/// Messages first seen now start their clock now, so nothing is purged earlier than asked.
+pub async fn purge(token: &ApiToken, older_than_days: u32, dry_run: bool) -> Result<usize> {
+    if !dry_run {
+        let info = google_api::token_info(token).await?;
+        if !info.scope.split_whitespace().any(|s| s == google_api::FULL_ACCESS_SCOPE) {
+            bail!(
+                "Permanent deletion needs the {} scope, which this authorization doesn't include",
+                google_api::FULL_ACCESS_SCOPE
+            );
+        }
+    }
+
+    let in_trash = list_trash(token).await?;
+    let mut log = TrashLog::load();
+    // Restored or already deleted messages no longer need tracking
+    let present: HashSet<&String> = in_trash.iter().collect();
+    log.items.retain(|id, _| present.contains(id));
+    for id in &in_trash {
+        log.observe(id);
+    }
+
+    let cutoff = Local::now() - Duration::days(older_than_days as i64);
+    let expired: Vec<String> = log
+        .items
+        .iter()
+        .filter(|(_, entry)| entry.trashed_at <= cutoff)
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    let mut purged = 0;
+    for id in expired {
+        let subject = google_api::get_message_headers(token, &id, &["Subject"])
+            .await
+            .map(|detail| detail.get_header("Subject"))
+            .unwrap_or_default();
+        if dry_run {
+            println!("Would delete {}  {}", id, subject);
+            purged += 1;
+            continue;
+        }
+        match google_api::delete_message(token, &id).await {
+            Ok(()) => {
+                println!("Deleted {}  {}", id, subject);
+                log.items.remove(&id);
+                purged += 1;
+            }
+            Err(e) => eprintln!("Failed to delete {}: {:?}", id, e),
+        }
+    }
+    log.save()?;
+    Ok(purged)
+}
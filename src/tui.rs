@@ -1,44 +1,509 @@
-use crate::google_api;
-use anyhow::Result;
+use crate::actions::{Action, ActionLog};
+use crate::address;
+use crate::attachments;
+use crate::bounces;
+use crate::cache;
+use crate::caution::{self, KnownSenders, Verdict};
+use crate::classifier::Classifier;
+use crate::compose;
+use crate::conversation::Conversation;
+use crate::config::{Config, ConfigWatcher, Density, Sensitivity, TranslateConfig, UiConfig};
+use crate::counts::{self, CountSource, Counts};
+use crate::dates::DateRange;
+use crate::google_api::{self, Capability};
+use crate::needs_reply;
+use crate::heatmap;
+use crate::login;
+use crate::lru::LruCache;
+use crate::mime::OutgoingMessage;
+use crate::offline::{self, PendingChanges, Resolution, Target};
+use crate::outbox::{self, Outbox, OutboxStatus};
+use crate::paths;
+use crate::plugins;
+use crate::privacy::{self, PrivacyReport};
+use crate::reminders::{self, Deadlines};
+use crate::scripting::{self, ScriptAction};
+use crate::senders::{RenderPrefs, SenderLists};
+use crate::speech::Speech;
+use crate::stats;
+use crate::store;
+use crate::text;
+use crate::threading::ThreadOverrides;
+use crate::timeline;
+use crate::timings;
+use crate::translate;
+use crate::trash::{self, TrashLog};
+use crate::watch::WatchedThreads;
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style, Stylize},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap},
     Terminal,
 };
-use std::io::stdout;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{stdout, Stdout};
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::mpsc;
 
 enum AppMode {
     List,
     Viewing,
+    Command,
+}
+
+// The pane that arrow keys act on, cycled with Tab and drawn with a highlighted border
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    List,
+    Preview,
+    // The action log in the list's triage layout, or the viewer's side panels
+    Sidebar,
+    Viewer,
+}
+
+enum CommandEffect {
+    None,
+    Reload,
+    RunPlugin { name: String, args: Vec<String> },
+    TrashSelected,
+    // Display settings changed; load the selected message again
+    Rerender,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum View {
+    Inbox,
+    Sent,
+    Outbox,
+    Archive,
+    AllMail,
+    Trash,
+    NeedsReply,
+    Search,
+    // The inbox of every signed-in account, merged by date
+    Unified,
+    Drafts,
+    // Threads I've written in, by who has the last word; both on 0
+    AwaitingMe,
+    AwaitingOthers,
+}
+
+impl View {
+    // None for views backed by local state rather than a Gmail query
+    fn query(self) -> Option<&'static str> {
+        match self {
+            View::Sent => Some("in:sent newer_than:30d"),
+            View::Outbox => None,
+            // Gmail has no archive label: archived mail is everything outside the system folders
+            View::Archive => Some("-in:inbox -in:sent -in:drafts -in:spam -in:trash"),
+            View::AllMail => Some(""),
+            View::Trash => Some("in:trash"),
+            View::Drafts => Some("in:drafts"),
+            // Scored or sorted locally from cached threads
+            View::NeedsReply | View::AwaitingMe | View::AwaitingOthers => None,
+            // The configured inbox query and the search text live on App, see App::view_query
+            View::Inbox | View::Unified | View::Search => Some(""),
+        }
+    }
+
+    // Only these headers are requested per row; Sent lists recipients instead of the sender
+    fn list_headers(self) -> &'static [&'static str] {
+        match self {
+            View::Sent | View::Drafts => &["To", "Cc", "Subject", "Date"],
+            _ => &["From", "Reply-To", "Subject", "Date"],
+        }
+    }
+
+    fn correspondent_column(self) -> &'static str {
+        match self {
+            View::Sent | View::Outbox | View::Drafts | View::AwaitingOthers => "To",
+            _ => "From",
+        }
+    }
+
+    // Views listed by conversation (threads.list) rather than by message
+    fn threaded(self) -> bool {
+        matches!(self, View::Inbox | View::Unified)
+    }
+
+    // Folder-like views leave out blocked senders; searches and sent mail show everything
+    fn hides_blocked(self) -> bool {
+        matches!(self, View::Inbox | View::Archive | View::AllMail | View::NeedsReply | View::Unified | View::AwaitingMe)
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            View::Inbox => "Primary Inbox",
+            View::Sent => "Sent",
+            View::Outbox => "Outbox",
+            View::Archive => "Archive",
+            View::AllMail => "All Mail",
+            View::Trash => "Trash",
+            View::NeedsReply => "Needs Reply",
+            View::Search => "Search",
+            View::Unified => "All Inboxes",
+            View::Drafts => "Drafts",
+            View::AwaitingMe => "Awaiting Me",
+            View::AwaitingOthers => "Awaiting Others",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AddressAction {
+    Compose,
+    ShowMail,
+    Copy,
+    Vip,
+    Block,
+}
+
+const ADDRESS_ACTIONS: [AddressAction; 5] =
+    [AddressAction::Compose, AddressAction::ShowMail, AddressAction::Copy, AddressAction::Vip, AddressAction::Block];
+
+// The pop-up opened with Enter on the viewer's sender
+struct AddressMenu {
+    address: String,
+    selected: usize,
+}
+
+// The views on number keys 1-9, in order, for the folder bar
+const NUMBERED_VIEWS: [View; 9] = [
+    View::Inbox,
+    View::Sent,
+    View::Outbox,
+    View::Archive,
+    View::AllMail,
+    View::Trash,
+    View::NeedsReply,
+    View::Unified,
+    View::Drafts,
+];
+// Width of the account column in the unified inbox
+const ACCOUNT_COLUMN_WIDTH: u16 = 12;
+
+// The pop-up opened with A; None is the default account
+struct AccountPicker {
+    accounts: Vec<Option<String>>,
+    selected: usize,
+}
+
+// The pop-up for queued offline changes that no longer fit the server, opened after a sync
+// finds some or with :conflicts
+struct ConflictPicker {
+    changes: Vec<offline::PendingChange>,
+    selected: usize,
+}
+
+impl ConflictPicker {
+    fn open() -> Option<Self> {
+        let changes: Vec<_> = PendingChanges::load().conflicts().into_iter().cloned().collect();
+        (!changes.is_empty()).then_some(ConflictPicker { changes, selected: 0 })
+    }
+}
+
+// Opened with X in Trash; nothing is deleted until the word is typed out
+struct DeleteConfirm {
+    message_id: String,
+    subject: String,
+    typed: String,
+}
+
+const DELETE_CONFIRMATION: &str = "delete";
+
+struct PendingTranslation {
+    // The body being translated, restored by the next `T`
+    body: String,
+    rx: tokio::sync::oneshot::Receiver<Result<(String, Option<String>)>>,
+}
+
+pub const LOCK_FILE: &str = "session.lock";
+const SESSION_FILE: &str = "session.json";
+const PAGE_SIZE: u32 = 50;
+// Drafts searched for the one behind a row of the Drafts view
+const DRAFTS_LOOKUP_LIMIT: u32 = 500;
+const BODY_CACHE_SIZE: usize = 200;
+const LIST_SNAPSHOTS_KEPT: usize = 8;
+const HEATMAP_WEEKS: usize = 26;
+// Shown above the body in the viewer; the full fetch already carries every header
+const PREVIEW_HEADERS: [&str; 6] = ["From", "To", "Cc", "Reply-To", "List-Id", "Date"];
+
+// What the previous run was showing, restored by the "last" startup view
+#[derive(Serialize, Deserialize, Default)]
+struct Session {
+    view: Option<View>,
+    search_query: String,
+}
+
+// Returns the view to open on launch and, for search-backed views, its query
+fn resolve_startup_view(spec: &str, config: &Config) -> Result<(View, String)> {
+    let spec = spec.trim();
+    if let Some(name) = spec.strip_prefix("search:") {
+        let query = config
+            .searches
+            .get(name)
+            .with_context(|| format!("No saved search named '{}' in [searches]", name))?;
+        return Ok((View::Search, query.clone()));
+    }
+    Ok(match spec {
+        "inbox" => (View::Inbox, String::new()),
+        "unread" => (View::Search, "in:inbox is:unread".to_string()),
+        "sent" => (View::Sent, String::new()),
+        "outbox" => (View::Outbox, String::new()),
+        "archive" => (View::Archive, String::new()),
+        "all" => (View::AllMail, String::new()),
+        "trash" => (View::Trash, String::new()),
+        "needs-reply" => (View::NeedsReply, String::new()),
+        "unified" => (View::Unified, String::new()),
+        "drafts" => (View::Drafts, String::new()),
+        "awaiting-me" => (View::AwaitingMe, String::new()),
+        "awaiting-others" => (View::AwaitingOthers, String::new()),
+        "last" => {
+            let session: Session = store::load(SESSION_FILE);
+            (session.view.unwrap_or(View::Inbox), session.search_query)
+        }
+        other => bail!(
+            "Unknown startup view '{}' (expected inbox, unread, sent, outbox, archive, all, trash, needs-reply, \
+             unified, drafts, awaiting-me, awaiting-others, last or search:<name>)",
+            other
+        ),
+    })
+}
+
+#[derive(Clone)]
+struct BodyResult {
+    id: String,
+    body: String,
+    // Just the new text, without signature or quoted trail, for the list's preview pane
+    preview: String,
+    privacy: PrivacyReport,
+    attachments: Vec<attachments::Attachment>,
+    // The body holds the error instead; not cached, so selecting the message again retries
+    failed: bool,
+}
+
+enum HeaderEvent {
+    Email(Box<EmailInfo>),
+    PageEnd { next_page_token: Option<String> },
+    // Shown in the footer, e.g. accounts the unified inbox had to leave out
+    Notice(String),
+}
+
+// Which account a unified inbox row came from, with the token to read it through
+struct RowAccount {
+    name: Option<String>,
+    token: google_api::ApiToken,
+}
+
+impl RowAccount {
+    fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("default")
+    }
+}
+
+// Asks the body worker for a message; `token` is set for rows of another account
+struct BodyRequest {
+    id: String,
+    token: Option<google_api::ApiToken>,
+    reader: bool,
+    prefs: RenderPrefs,
 }
 
 struct EmailInfo {
     id: String,
+    thread_id: String,
+    from: String,
+    subject: String,
+    is_unread: bool,
+    snippet: String,
+    delivery_failed: bool,
+    received_at: Option<i64>,
+    // Messages in the conversation; always 1 for per-message views
+    message_count: usize,
+    label_ids: Vec<String>,
+    // A user label the classifier thinks fits, shown dimmed and applied with `l`
+    suggested_label: Option<String>,
+    // Set in the unified inbox only; shared by the rows of one account
+    account: Option<Arc<RowAccount>>,
+    reply_to: String,
+    caution: Verdict,
+}
+
+impl EmailInfo {
+    // The account's token when the row belongs to an account other than the selected one
+    fn foreign_token(&self) -> Option<&google_api::ApiToken> {
+        self.account.as_ref().filter(|a| a.name != store::account()).map(|a| &a.token)
+    }
+
+    fn to_cached(&self) -> CachedRow {
+        CachedRow {
+            id: self.id.clone(),
+            thread_id: self.thread_id.clone(),
+            from: self.from.clone(),
+            subject: self.subject.clone(),
+            is_unread: self.is_unread,
+            snippet: self.snippet.clone(),
+            delivery_failed: self.delivery_failed,
+            received_at: self.received_at,
+            message_count: self.message_count,
+            label_ids: self.label_ids.clone(),
+            reply_to: self.reply_to.clone(),
+        }
+    }
+}
+
+// The first page of the lists opened at startup, kept in the cache so the next launch can
+// show them before the network answers
+#[derive(Default, Serialize, Deserialize)]
+struct ListSnapshots {
+    // Most recently loaded first
+    lists: Vec<ListSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ListSnapshot {
+    account: Option<String>,
+    view: View,
+    query: String,
+    rows: Vec<CachedRow>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRow {
+    id: String,
+    thread_id: String,
     from: String,
     subject: String,
     is_unread: bool,
     snippet: String,
+    delivery_failed: bool,
+    received_at: Option<i64>,
+    message_count: usize,
+    label_ids: Vec<String>,
+    reply_to: String,
+}
+
+impl From<CachedRow> for EmailInfo {
+    fn from(row: CachedRow) -> Self {
+        EmailInfo {
+            id: row.id,
+            thread_id: row.thread_id,
+            from: row.from,
+            subject: row.subject,
+            is_unread: row.is_unread,
+            snippet: row.snippet,
+            delivery_failed: row.delivery_failed,
+            received_at: row.received_at,
+            message_count: row.message_count,
+            label_ids: row.label_ids,
+            suggested_label: None,
+            account: None,
+            reply_to: row.reply_to,
+            caution: Verdict::default(),
+        }
+    }
 }
 
 struct App {
     mode: AppMode,
+    view: View,
     is_loading: bool,
     emails: Vec<EmailInfo>,
+    next_page_token: Option<String>,
+    // While the startup list refreshes: how many rows at the top are fresh. Rows below that
+    // may be from the cached snapshot and are dropped if the first page doesn't bring them back
+    startup_refresh: Option<usize>,
     selected_index: usize,
     current_email_body: String,
+    // Set once the selected message's body has loaded; until then the snippet is shown
+    current_preview: Option<String>,
+    // The body as loaded while a translation is shown; `T` brings it back
+    untranslated_body: Option<String>,
+    // Started with `T`; dropped, and its result ignored, when another message is selected
+    translation: Option<PendingTranslation>,
+    // The selected message being read aloud, toggled with `P`
+    speech: Option<Speech>,
+    // The open message's whole thread, shown instead of the message alone; toggled with `c`
+    conversation: Option<Conversation>,
+    // Started with `c`; dropped, and its result ignored, when another message is selected
+    conversation_loading: Option<tokio::sync::oneshot::Receiver<Result<Conversation>>>,
+    current_privacy: PrivacyReport,
+    current_attachments: Vec<attachments::Attachment>,
+    // The pop-up opened with `a` when the message has several attachments; the selected row
+    attachment_picker: Option<usize>,
+    delete_confirm: Option<DeleteConfirm>,
+    // Decoded bodies seen this session, so revisiting a message doesn't refetch it
+    body_cache: LruCache<BodyResult>,
+    show_privacy: bool,
+    focus: Focus,
+    preview_scroll: u16,
+    sidebar_scroll: u16,
+    // Response-time summary for the open message's sender, when toggled on
+    sender_panel: Option<String>,
+    show_debug: bool,
+    // The open message's thread, when it has more than one message
+    timeline: Option<Vec<timeline::Point>>,
+    // Fetched in the background when a message is opened; dropped when it's closed
+    timeline_loading: Option<tokio::sync::oneshot::Receiver<Option<Vec<timeline::Point>>>>,
+    // Sent messages with a bounce in the last 30 days, looked up when the Sent view is opened or
+    // reloaded rather than for every page
+    bounced: HashSet<String>,
+    bounces_loading: Option<tokio::sync::oneshot::Receiver<HashSet<String>>>,
+    // Set by --read-only; blocks every mutating action
+    read_only: bool,
+    // What the token allows; anything else is offered through incremental consent with G
+    granted: Vec<Capability>,
+    // The capability the last refused action needed
+    wanted: std::cell::Cell<Option<Capability>>,
+    // Badges for the folder bar, keyed "inbox", "trash" or "search:<name>"
+    label_counts: HashMap<String, Counts>,
+    // Trained in the background from the message cache; None until ready
+    classifier: Option<Classifier>,
+    label_names: HashMap<String, String>,
+    // For the first-time sender signal of the caution score
+    known_senders: KnownSenders,
+    // Rendered when toggled on, from the local message cache
+    heatmap: Option<Vec<String>>,
     scroll_offset: u16,
+    watched: WatchedThreads,
+    thread_overrides: ThreadOverrides,
+    // Conversation picked with :merge, waiting for the one it belongs with
+    merge_mark: Option<String>,
+    deadlines: Deadlines,
+    trash: TrashLog,
+    senders: SenderLists,
+    address_menu: Option<AddressMenu>,
+    account_picker: Option<AccountPicker>,
+    conflict_picker: Option<ConflictPicker>,
+    triage_layout: bool,
+    density: Density,
+    needs_reply_sensitivity: Sensitivity,
+    // Bodies are shown through the readability extraction
+    reader_mode: bool,
+    // Kept across frames so the list only scrolls when the selection leaves the viewport
+    table_state: TableState,
+    action_log: ActionLog,
+    date_range: DateRange,
+    inbox_query: String,
+    search_query: String,
+    command_input: String,
+    status_message: Option<String>,
 }
 
 impl App {
-    fn previous(&mut self, body_request_tx: mpsc::Sender<String>) {
+    fn previous(&mut self, body_request_tx: mpsc::Sender<BodyRequest>) {
         if !self.emails.is_empty() {
             let new_index = if self.selected_index > 0 {
                 self.selected_index - 1
@@ -49,7 +514,7 @@ impl App {
         }
     }
 
-    fn next(&mut self, body_request_tx: mpsc::Sender<String>) {
+    fn next(&mut self, body_request_tx: mpsc::Sender<BodyRequest>) {
         if !self.emails.is_empty() {
             let new_index = if self.selected_index < self.emails.len() - 1 {
                 self.selected_index + 1
@@ -60,75 +525,1486 @@ impl App {
         }
     }
 
-    fn select(&mut self, index: usize, body_request_tx: mpsc::Sender<String>) {
-        if self.selected_index != index || self.current_email_body.is_empty() {
-            self.selected_index = index;
-            self.scroll_offset = 0;
-            self.current_email_body = "Loading...".to_string();
-            if let Some(email) = self.emails.get(index) {
-                let _ = body_request_tx.try_send(email.id.clone());
+    // Cached bodies were rendered with the old display settings
+    fn rerender(&mut self, body_request_tx: mpsc::Sender<BodyRequest>) {
+        self.body_cache = LruCache::new(BODY_CACHE_SIZE);
+        self.current_email_body.clear();
+        self.select(self.selected_index, body_request_tx);
+    }
+
+    fn select(&mut self, index: usize, body_request_tx: mpsc::Sender<BodyRequest>) {
+        if self.selected_index != index || self.current_email_body.is_empty() {
+            self.selected_index = index;
+            self.scroll_offset = 0;
+            self.preview_scroll = 0;
+            self.sidebar_scroll = 0;
+            self.current_email_body = "Loading...".to_string();
+            self.untranslated_body = None;
+            self.translation = None;
+            self.speech = None;
+            self.conversation = None;
+            self.conversation_loading = None;
+            self.current_preview = None;
+            self.current_privacy = PrivacyReport::default();
+            self.current_attachments = Vec::new();
+            // The picker lists the previous message's attachments
+            self.attachment_picker = None;
+            if let Some(email) = self.emails.get(index) {
+                // Outbox entries only exist locally; their preview already holds the body
+                if self.view == View::Outbox {
+                    self.current_email_body = email.snippet.clone();
+                } else if let Some(cached) = self.body_cache.get(&email.id) {
+                    self.current_email_body = cached.body;
+                    self.current_preview = Some(cached.preview);
+                    self.current_privacy = cached.privacy;
+                    self.current_attachments = cached.attachments;
+                } else {
+                    let token = email.foreign_token().cloned();
+                    let _ = body_request_tx.try_send(BodyRequest {
+                        id: email.id.clone(),
+                        token,
+                        reader: self.reader_mode,
+                        prefs: self.senders.render_prefs(&email.from),
+                    });
+                }
+            }
+        }
+    }
+
+    fn switch_view(&mut self, view: View, token: &google_api::ApiToken) -> mpsc::Receiver<HeaderEvent> {
+        self.view = view;
+        self.emails.clear();
+        self.next_page_token = None;
+        self.startup_refresh = None;
+        self.selected_index = 0;
+        self.table_state = TableState::default();
+        self.current_email_body.clear();
+        self.is_loading = true;
+        self.load_bounces(token);
+        spawn_header_loader(token.clone(), view, self.view_query(), None, self.needs_reply_sensitivity)
+    }
+
+    fn load_bounces(&mut self, token: &google_api::ApiToken) {
+        self.bounced.clear();
+        self.bounces_loading = None;
+        if self.view != View::Sent {
+            return;
+        }
+        let token = token.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let bounces = bounces::detect(&token, 30).await.unwrap_or_default();
+            let _ = tx.send(bounces.into_iter().filter_map(|b| b.original_id).collect());
+        });
+        self.bounces_loading = Some(rx);
+    }
+
+    fn snapshot_key(&self) -> Option<(Option<String>, View, String)> {
+        // Unified rows belong to several accounts, and local views have nothing to refresh
+        if self.view == View::Unified {
+            return None;
+        }
+        Some((store::account(), self.view, self.view_query()?))
+    }
+
+    // Fills the list from the last snapshot of this view, if there is one
+    fn show_snapshot(&mut self, config: &Config) -> bool {
+        let Some((account, view, query)) = self.snapshot_key() else {
+            return false;
+        };
+        let snapshots: ListSnapshots = cache::cached_lists();
+        let Some(snapshot) = snapshots
+            .lists
+            .into_iter()
+            .find(|list| list.account == account && list.view == view && list.query == query)
+        else {
+            return false;
+        };
+        for row in snapshot.rows {
+            let mut email = EmailInfo::from(row);
+            if self.view.hides_blocked() && self.senders.is_blocked(&email.from) {
+                continue;
+            }
+            email.caution = self.assess(&email, None, config);
+            self.emails.push(email);
+        }
+        !self.emails.is_empty()
+    }
+
+    fn save_snapshot(&self) {
+        let Some((account, view, query)) = self.snapshot_key() else {
+            return;
+        };
+        let mut snapshots: ListSnapshots = cache::cached_lists();
+        snapshots.lists.retain(|list| !(list.account == account && list.view == view && list.query == query));
+        snapshots.lists.insert(0, ListSnapshot { account, view, query, rows: self.emails.iter().map(EmailInfo::to_cached).collect() });
+        snapshots.lists.truncate(LIST_SNAPSHOTS_KEPT);
+        // Only a startup nicety; the screen belongs to the TUI
+        let _ = cache::store_lists(&snapshots);
+    }
+
+    // Rows are the same conversation, or the same message in per-message views
+    fn same_row(&self, a: &EmailInfo, b: &EmailInfo) -> bool {
+        if self.view.threaded() { a.thread_id == b.thread_id } else { a.id == b.id }
+    }
+
+    // Puts a freshly loaded row in place of its snapshot copy, keeping the selection on the
+    // same conversation as rows move around it
+    fn reconcile(&mut self, email: EmailInfo, fresh: usize, body_request_tx: mpsc::Sender<BodyRequest>) {
+        let selected = self.emails.get(self.selected_index).map(|e| (e.id.clone(), e.thread_id.clone()));
+        if let Some(index) = self.emails.iter().skip(fresh).position(|e| self.same_row(e, &email)) {
+            self.emails.remove(fresh + index);
+        }
+        self.emails.insert(fresh.min(self.emails.len()), email);
+        self.startup_refresh = Some(fresh + 1);
+        self.restore_selection(selected, body_request_tx);
+    }
+
+    // The first page is in: whatever is left of the snapshot is gone from the mailbox
+    fn finish_startup_refresh(&mut self, body_request_tx: mpsc::Sender<BodyRequest>) {
+        let Some(fresh) = self.startup_refresh.take() else {
+            return;
+        };
+        let selected = self.emails.get(self.selected_index).map(|e| (e.id.clone(), e.thread_id.clone()));
+        self.emails.truncate(fresh);
+        self.restore_selection(selected, body_request_tx);
+        self.save_snapshot();
+    }
+
+    fn restore_selection(&mut self, selected: Option<(String, String)>, body_request_tx: mpsc::Sender<BodyRequest>) {
+        let Some((id, thread_id)) = selected else {
+            return;
+        };
+        let threaded = self.view.threaded();
+        let index = self
+            .emails
+            .iter()
+            .position(|e| if threaded { e.thread_id == thread_id } else { e.id == id })
+            .unwrap_or_else(|| self.selected_index.min(self.emails.len().saturating_sub(1)));
+        // A conversation with a newer message, or a different row altogether, needs its body loaded
+        if self.emails.get(index).is_some_and(|e| e.id == id) {
+            self.selected_index = index;
+        } else {
+            self.current_email_body.clear();
+            self.select(index, body_request_tx);
+        }
+    }
+
+    fn view_query(&self) -> Option<String> {
+        let base = match self.view {
+            View::Inbox | View::Unified => Some(self.inbox_query.as_str()),
+            View::Search => Some(self.search_query.as_str()),
+            view => view.query(),
+        };
+        base.map(|q| self.date_range.apply(q))
+    }
+
+    // Shows a finished translation of `body`, the message body as it was when `T` was pressed
+    fn show_translation(&mut self, body: String, result: Result<(String, Option<String>)>, config: &TranslateConfig) {
+        let (translation, source) = match result {
+            Ok(translated) => translated,
+            Err(e) => {
+                self.status_message = Some(format!("Translation failed: {:#}", e));
+                return;
+            }
+        };
+        let (headers, text) = body.split_once("\n\n").unwrap_or(("", body.as_str()));
+        let label = match source {
+            Some(source) => format!("Translated from {} to {}", source, config.target_lang),
+            None => format!("Translated to {}", config.target_lang),
+        };
+        self.current_email_body = if config.replace {
+            format!("{}\n\n[{}]\n\n{}", headers, label, translation)
+        } else {
+            format!("{}\n\n{}\n\n── {} ──\n\n{}", headers, text.trim_end(), label, translation)
+        };
+        self.untranslated_body = Some(body);
+        self.scroll_offset = 0;
+        self.status_message = Some(format!("{}; T shows the original", label));
+    }
+
+    // Takes the selected row out of the list and moves on to the one that took its place
+    fn remove_selected(&mut self, body_request_tx: mpsc::Sender<BodyRequest>) -> EmailInfo {
+        let email = self.emails.remove(self.selected_index);
+        let next = self.selected_index.min(self.emails.len().saturating_sub(1));
+        self.current_email_body.clear();
+        self.select(next, body_request_tx);
+        email
+    }
+
+    fn at_end_with_more_pages(&self) -> bool {
+        !self.is_loading && self.next_page_token.is_some() && self.selected_index + 1 >= self.emails.len()
+    }
+
+    fn load_next_page(&mut self, token: &google_api::ApiToken) -> Option<mpsc::Receiver<HeaderEvent>> {
+        let page_token = self.next_page_token.take()?;
+        self.is_loading = true;
+        Some(spawn_header_loader(token.clone(), self.view, self.view_query(), Some(page_token), self.needs_reply_sensitivity))
+    }
+
+    fn execute_command(&mut self, input: &str) -> Result<CommandEffect> {
+        let mut words = input.split_whitespace();
+        match words.next() {
+            None => Ok(CommandEffect::None),
+            Some("goto") => {
+                self.date_range = match words.next() {
+                    None | Some("all") => DateRange::default(),
+                    Some(period) => DateRange::period(period)?,
+                };
+                Ok(CommandEffect::Reload)
+            }
+            Some("search") => {
+                let query = words.collect::<Vec<_>>().join(" ");
+                if query.is_empty() {
+                    bail!("Usage: search <gmail query>");
+                }
+                self.search_query = query;
+                self.view = View::Search;
+                Ok(CommandEffect::Reload)
+            }
+            Some("run") => {
+                let Some(name) = words.next() else {
+                    bail!("Usage: run <plugin> [args...]");
+                };
+                if plugins::find(name).is_none() {
+                    bail!("No plugin named {}{} found on PATH", plugins::PREFIX, name);
+                }
+                Ok(CommandEffect::RunPlugin {
+                    name: name.to_string(),
+                    args: words.map(str::to_string).collect(),
+                })
+            }
+            Some("conflicts") => {
+                self.conflict_picker = ConflictPicker::open();
+                if self.conflict_picker.is_none() {
+                    bail!("No offline changes are waiting on a conflict");
+                }
+                Ok(CommandEffect::None)
+            }
+            Some("trash") => {
+                self.ensure_writable()?;
+                self.ensure_local()?;
+                if self.view == View::Outbox || self.emails.get(self.selected_index).is_none() {
+                    bail!("No message selected");
+                }
+                Ok(CommandEffect::TrashSelected)
+            }
+            Some("due") => {
+                self.ensure_local()?;
+                let Some(email) = self.emails.get(self.selected_index) else {
+                    bail!("No message selected");
+                };
+                let when = words.collect::<Vec<_>>().join(" ");
+                if when.is_empty() {
+                    bail!("Usage: due <3d|12h|tomorrow|YYYY-MM-DD|clear>");
+                }
+                if when == "clear" {
+                    self.deadlines.remove(&email.id)?;
+                    self.status_message = Some("Deadline cleared".to_string());
+                } else {
+                    let due = reminders::parse_deadline(&when, chrono::Local::now())?;
+                    self.deadlines.set(&email.id, &email.subject, &email.from, due)?;
+                    self.status_message = Some(format!("Respond by {}", due.format("%a %Y-%m-%d %H:%M")));
+                }
+                Ok(CommandEffect::None)
+            }
+            Some("merge") => {
+                self.ensure_local()?;
+                let Some(email) = self.emails.get(self.selected_index).filter(|_| self.view.threaded()) else {
+                    bail!("Select a conversation to merge");
+                };
+                match self.merge_mark.take() {
+                    None => {
+                        self.merge_mark = Some(email.thread_id.clone());
+                        self.status_message = Some("Marked; run :merge on the conversation it belongs with".to_string());
+                        Ok(CommandEffect::None)
+                    }
+                    Some(mark) if mark == email.thread_id => {
+                        self.status_message = Some("Merge cancelled".to_string());
+                        Ok(CommandEffect::None)
+                    }
+                    Some(mark) => {
+                        let into = email.thread_id.clone();
+                        self.thread_overrides.merge(&mark, &into)?;
+                        Ok(CommandEffect::Reload)
+                    }
+                }
+            }
+            Some("unmerge") => {
+                self.ensure_local()?;
+                let Some(email) = self.emails.get(self.selected_index) else {
+                    bail!("No message selected");
+                };
+                let thread_id = email.thread_id.clone();
+                if self.thread_overrides.unmerge(&thread_id)? == 0 {
+                    bail!("This conversation isn't merged with another");
+                }
+                Ok(CommandEffect::Reload)
+            }
+            Some("split") => {
+                self.ensure_local()?;
+                let Some(email) = self.emails.get(self.selected_index).filter(|_| self.view.threaded()) else {
+                    bail!("Select a conversation to split");
+                };
+                if email.message_count <= 1 && !self.thread_overrides.is_split(&email.id) {
+                    bail!("There is nothing to split this message from");
+                }
+                let id = email.id.clone();
+                self.thread_overrides.toggle_split(&id)?;
+                Ok(CommandEffect::Reload)
+            }
+            Some("prefs") => {
+                let Some(email) = self.emails.get(self.selected_index).filter(|_| self.view != View::Outbox) else {
+                    bail!("No message selected");
+                };
+                let from = email.from.clone();
+                let mut prefs = self.senders.render_prefs(&from);
+                let switch = |value: Option<&str>| match value {
+                    Some("on") => Ok(true),
+                    Some("off") => Ok(false),
+                    _ => Err(anyhow::anyhow!("Usage: prefs <html|reader|images> <on|off>")),
+                };
+                match (words.next(), words.next()) {
+                    (None, _) => {}
+                    (Some("html"), value) => prefs.prefer_html = switch(value)?,
+                    (Some("reader"), value) => prefs.reader = switch(value)?,
+                    (Some("images"), value) => prefs.remote_images = switch(value)?,
+                    (Some("width"), Some("auto")) => prefs.width = None,
+                    (Some("width"), Some(columns)) => {
+                        prefs.width = Some(columns.parse().ok().filter(|c| (20..=400).contains(c)).context("Width must be 20-400 or auto")?)
+                    }
+                    (Some("clear"), None) => prefs = RenderPrefs::default(),
+                    _ => bail!("Usage: prefs [html|reader|images on|off | width <columns>|auto | clear]"),
+                }
+                self.senders.set_render_prefs(&from, prefs.clone())?;
+                self.status_message = Some(format!("{}: {}", address::extract_address(&from), prefs.describe()));
+                Ok(CommandEffect::Rerender)
+            }
+            Some(other) => bail!("Unknown command: {}", other),
+        }
+    }
+
+    // Folds a conversation locally merged with one already listed into that row instead
+    // of giving it a row of its own
+    fn absorb_merged(&mut self, email: &EmailInfo) -> bool {
+        let overrides = &self.thread_overrides;
+        let groupable = |e: &EmailInfo| e.foreign_token().is_none() && !overrides.is_split(&e.id);
+        if !self.view.threaded() || !groupable(email) {
+            return false;
+        }
+        let group = overrides.group(&email.thread_id);
+        let Some(row) = self.emails.iter_mut().find(|e| groupable(e) && overrides.group(&e.thread_id) == group) else {
+            return false;
+        };
+        row.message_count += email.message_count;
+        row.is_unread |= email.is_unread;
+        true
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        self.ensure_capability(Capability::Modify)
+    }
+
+    fn ensure_capability(&self, capability: Capability) -> Result<()> {
+        if self.read_only {
+            bail!("Read-only mode: {} is disabled", capability.describe());
+        }
+        if !self.granted.contains(&capability) {
+            self.wanted.set(Some(capability));
+            bail!("No permission for {} yet; press G to grant it", capability.describe());
+        }
+        Ok(())
+    }
+
+    // Shows the selected message full screen, with its conversation's timeline
+    fn open_selected(&mut self, token: &google_api::ApiToken) {
+        self.mode = AppMode::Viewing;
+        self.focus = Focus::Viewer;
+        self.timeline = None;
+        self.timeline_loading = None;
+        // A message split off its thread is a conversation of one, so it has no timeline
+        let Some(email) = self.emails.get(self.selected_index)
+            .filter(|e| !e.thread_id.is_empty() && !self.thread_overrides.is_split(&e.id))
+        else {
+            return;
+        };
+        let overrides = match (&email.account, email.foreign_token()) {
+            (Some(account), Some(_)) => ThreadOverrides::load_for(account.name.as_deref()),
+            _ => self.thread_overrides.clone(),
+        };
+        let token = email.foreign_token().unwrap_or(token).clone();
+        let thread_id = email.thread_id.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let points = timeline::load(&token, &thread_id, &overrides).await.ok().filter(|p| p.len() > 1);
+            let _ = tx.send(points);
+        });
+        self.timeline_loading = Some(rx);
+    }
+
+    // Acting on another account's message needs its state files, so only reading is allowed
+    fn ensure_local(&self) -> Result<()> {
+        if let Some(account) = self.emails.get(self.selected_index).and_then(|e| e.account.as_ref())
+            && account.name != store::account()
+        {
+            bail!("This message is in the {} account; switch to it with A first", account.label());
+        }
+        Ok(())
+    }
+
+    fn selected_message(&self) -> Option<plugins::SelectedMessage> {
+        self.emails.get(self.selected_index).map(|e| plugins::SelectedMessage {
+            id: e.id.clone(),
+            thread_id: e.thread_id.clone(),
+            from: e.from.clone(),
+            subject: e.subject.clone(),
+        })
+    }
+
+    // Panes on screen in the current mode, in Tab order
+    fn focusable(&self) -> Vec<Focus> {
+        match self.mode {
+            AppMode::Viewing if self.sender_panel.is_some() || self.show_privacy => vec![Focus::Viewer, Focus::Sidebar],
+            AppMode::Viewing => vec![Focus::Viewer],
+            _ if self.triage_layout => vec![Focus::List, Focus::Sidebar],
+            _ => vec![Focus::List, Focus::Preview],
+        }
+    }
+
+    // Falls back to the main pane when the focused one has gone, e.g. after a mode change
+    fn focused(&self) -> Focus {
+        let panes = self.focusable();
+        if panes.contains(&self.focus) { self.focus } else { panes[0] }
+    }
+
+    fn cycle_focus(&mut self) {
+        let panes = self.focusable();
+        let current = panes.iter().position(|p| *p == self.focused()).unwrap_or(0);
+        self.focus = panes[(current + 1) % panes.len()];
+    }
+
+    fn pane_block<'a>(&self, pane: Focus, title: impl Into<Line<'a>>) -> Block<'a> {
+        let block = Block::default().borders(Borders::ALL).title(title);
+        if self.focused() == pane { block.border_style(Style::default().fg(Color::Cyan)) } else { block }
+    }
+
+    fn suggest_label(&self, email: &mut EmailInfo) {
+        if self.view == View::Inbox
+            && let Some(classifier) = &self.classifier
+        {
+            email.suggested_label = classifier.suggest(&email.from, &email.subject, &email.snippet, &email.label_ids);
+        }
+    }
+
+    // Recomputed with the tracker count once the body has been analyzed
+    fn assess(&self, email: &EmailInfo, trackers: Option<usize>, config: &Config) -> Verdict {
+        if matches!(self.view, View::Sent | View::Outbox) {
+            return Verdict::default();
+        }
+        caution::assess(&email.id, &email.from, &email.reply_to, trackers, &self.known_senders, &config.caution)
+    }
+
+    fn label_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.label_names.get(id).map_or(id, String::as_str)
+    }
+
+    fn scroll_down(&mut self) {
+        self.scroll_offset = self.scroll_offset.saturating_add(1);
+    }
+
+    fn scroll_up(&mut self) {
+        self.scroll_offset = self.scroll_offset.saturating_sub(1);
+    }
+}
+
+// "Re: Fwd: Launch plan" -> "Launch plan", so a subject search matches the whole conversation
+fn strip_reply_prefixes(subject: &str) -> &str {
+    let mut rest = subject.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:"].iter().find(|p| lower.starts_with(**p)) else {
+            return rest;
+        };
+        rest = rest[prefix.len()..].trim_start();
+    }
+}
+
+// Accent colors for sender markers; red and yellow are left out as they already mean
+// "bounced" and "watched" in the list
+const SENDER_PALETTE: [u8; 12] = [33, 37, 41, 69, 99, 133, 166, 172, 30, 65, 139, 168];
+const SENDER_GLYPHS: [&str; 4] = ["●", "■", "▲", "◆"];
+
+// A per-sender identicon: color and shape both derive from the address, so the same person
+// is recognisable at a glance even where colors are hard to tell apart
+fn sender_marker(from: &str) -> Span<'static> {
+    let hash = address::stable_hash(&address::extract_address(from));
+    let color = SENDER_PALETTE[(hash % SENDER_PALETTE.len() as u64) as usize];
+    let glyph = SENDER_GLYPHS[((hash >> 32) % SENDER_GLYPHS.len() as u64) as usize];
+    Span::styled(format!("{} ", glyph), Style::default().fg(Color::Indexed(color)))
+}
+
+// Surfaces mail that has sat in the inbox past the configured thresholds
+fn age_badge(received_at: Option<i64>, now: i64, ui: &UiConfig) -> Option<Span<'static>> {
+    let days = (now - received_at?) / 86_400;
+    let reached = |threshold: u32| threshold > 0 && days >= threshold as i64;
+    let color = if reached(ui.age_alert_days) {
+        Color::Red
+    } else if reached(ui.age_warn_days) {
+        Color::Indexed(214)
+    } else {
+        return None;
+    };
+    Some(Span::styled(format!("{}d ", days), Style::default().fg(color).bold()))
+}
+
+// Builds a pane for message text; RTL-dominant text is pre-wrapped, bidi-reordered and
+// right-aligned since the terminal itself only lays text out left-to-right
+fn text_pane<'a>(content: &'a str, block: Block<'a>, area: Rect, trim: bool) -> Paragraph<'a> {
+    if text::is_rtl_dominant(content) {
+        let inner_width = area.width.saturating_sub(2) as usize;
+        let lines: Vec<Line> = text::bidi_visual_lines(content, inner_width)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        Paragraph::new(lines).block(block).alignment(Alignment::Right)
+    } else {
+        Paragraph::new(content).block(block).wrap(Wrap { trim })
+    }
+}
+
+type Term = Terminal<CrosstermBackend<Stdout>>;
+
+// Hands the real terminal to a child process (editor, plugin) until resume_terminal
+fn suspend_terminal(terminal: &mut Term) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    Ok(())
+}
+
+fn resume_terminal(terminal: &mut Term) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(())
+}
+
+// Raw mode turns off the terminal's own Ctrl-Z handling, so the key arrives as input. Hand
+// the terminal back, stop the process the way the shell expects, and take it over again once
+// resumed with `fg`.
+#[cfg(unix)]
+fn suspend_to_shell(terminal: &mut Term) -> Result<()> {
+    suspend_terminal(terminal)?;
+    // Execution continues here after SIGCONT
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+    resume_terminal(terminal)
+}
+
+#[cfg(not(unix))]
+fn suspend_to_shell(_terminal: &mut Term) -> Result<()> {
+    Ok(())
+}
+
+// Performs what a key-bound script queued and returns a status line summarizing it
+async fn apply_script_actions(
+    app: &mut App,
+    terminal: &mut Term,
+    token: &google_api::ApiToken,
+    config: &Config,
+    actions: Vec<ScriptAction>,
+) -> Result<String> {
+    let mut status = None;
+    let mut changes = 0;
+    let mut errors = Vec::new();
+    for action in actions {
+        let allowed = match action {
+            ScriptAction::ModifyLabels { .. } => app.ensure_writable().and_then(|_| app.ensure_local()),
+            ScriptAction::Compose { .. } => app.ensure_capability(Capability::Send),
+            ScriptAction::Status(_) => Ok(()),
+        };
+        if let Err(e) = allowed {
+            errors.push(e.to_string());
+            continue;
+        }
+        match action {
+            ScriptAction::ModifyLabels { message_id, add, remove } => {
+                let add: Vec<&str> = add.iter().map(String::as_str).collect();
+                let remove: Vec<&str> = remove.iter().map(String::as_str).collect();
+                match google_api::modify_labels(token, &message_id, &add, &remove).await {
+                    Ok(()) => {
+                        changes += 1;
+                        if let Some(email) = app.emails.iter_mut().find(|e| e.id == message_id) {
+                            if remove.contains(&"UNREAD") {
+                                email.is_unread = false;
+                            }
+                            if add.contains(&"UNREAD") {
+                                email.is_unread = true;
+                            }
+                        }
+                    }
+                    Err(e) => errors.push(e.to_string()),
+                }
+            }
+            ScriptAction::Compose { to, subject, body } => {
+                suspend_terminal(terminal)?;
+                let prefill = OutgoingMessage { to: address::split_list(&to), subject, body, ..Default::default() };
+                let edited = compose::edit_in_editor(&prefill, None, &config.compose);
+                resume_terminal(terminal)?;
+                match edited {
+                    Ok(Some(message)) => match outbox::send_or_queue(token, &message).await {
+                        Ok(report) => {
+                            changes += 1;
+                            if !report.warnings.is_empty() {
+                                status = Some(format!("Warning: {}", report.warnings.join("; ")));
+                            }
+                        }
+                        Err(e) => errors.push(e.to_string()),
+                    },
+                    Ok(None) => status = Some("Compose cancelled".to_string()),
+                    Err(e) => errors.push(format!("{:#}", e)),
+                }
+            }
+            ScriptAction::Status(text) => status = Some(text),
+        }
+    }
+    Ok(match (errors.first(), status) {
+        (Some(error), _) => format!("Script: {} error(s), first: {}", errors.len(), error),
+        (None, Some(status)) => status,
+        (None, None) => format!("Script: {} action(s) applied", changes),
+    })
+}
+
+// The status line for a send, carrying any send-as warnings about the From address
+fn send_status(report: outbox::SendReport, sent: &str, queued: &str, drafted: &str) -> String {
+    let status = match report.outcome {
+        outbox::SendOutcome::Sent(_) => sent,
+        outbox::SendOutcome::Queued(_) => queued,
+        outbox::SendOutcome::Drafted(_) => drafted,
+    };
+    if report.warnings.is_empty() {
+        status.to_string()
+    } else {
+        format!("{} (warning: {})", status, report.warnings.join("; "))
+    }
+}
+
+// Writes a reply to the selected message in the editor, quoted per `compose.reply_style`
+async fn reply_to_selected(
+    app: &App,
+    terminal: &mut Term,
+    token: &google_api::ApiToken,
+    config: &Config,
+    all: bool,
+) -> Result<String> {
+    app.ensure_capability(Capability::Send)?;
+    app.ensure_local()?;
+    let Some(email) = app.emails.get(app.selected_index).filter(|_| app.view != View::Outbox) else {
+        bail!("No message selected");
+    };
+    let detail = google_api::get_full_message(token, &email.id).await?;
+    // The profile and every send-as alias; if Gmail can't list them, my own address may stay
+    // among the recipients, which is still worth replying
+    let me: Vec<String> = needs_reply::account_addresses(token).await.into_iter().collect();
+    let (to, cc) = compose::reply_recipients(
+        &detail.get_header("From"),
+        &detail.get_header("Reply-To"),
+        &detail.get_header("To"),
+        &detail.get_header("Cc"),
+        all,
+        &me,
+    );
+    let subject = detail.get_header("Subject");
+    let subject = if subject.to_ascii_lowercase().starts_with("re:") { subject } else { format!("Re: {}", subject) };
+    let attribution = compose::attribution(
+        &detail.get_header("From"),
+        detail.received_at(),
+        &detail.get_header("Date"),
+        &config.compose,
+    )?;
+    let draft = compose::reply_draft(
+        attribution,
+        &google_api::decode_email_body(&detail),
+        config.compose.reply_style,
+    );
+
+    suspend_terminal(terminal)?;
+    let prefill = OutgoingMessage { to, cc, subject, body: draft.body, ..Default::default() };
+    let edited = compose::edit_in_editor(&prefill, Some(draft.cursor_line), &config.compose);
+    resume_terminal(terminal)?;
+    Ok(match edited? {
+        Some(mut message) => {
+            message
+                .extra_headers
+                .extend(compose::threading_headers(&detail.get_header("Message-ID"), &detail.get_header("References")));
+            message.thread_id = Some(detail.thread_id.clone());
+            send_status(
+                outbox::send_or_queue(token, &message).await?,
+                "Reply sent",
+                "Offline: reply queued in the outbox",
+                "Reply saved to Drafts",
+            )
+        }
+        None => "Reply cancelled".to_string(),
+    })
+}
+
+// Moves the selected message to Trash, or back out of it, and drops its row on success
+async fn trash_selected(app: &mut App, token: &google_api::ApiToken, body_request_tx: mpsc::Sender<BodyRequest>, restore: bool) -> String {
+    let email = &app.emails[app.selected_index];
+    // A conversation row stands for the whole thread, unless it was split off
+    let thread_id = (app.view.threaded() && !email.thread_id.is_empty() && !app.thread_overrides.is_split(&email.id))
+        .then(|| email.thread_id.clone());
+    let result = match (&thread_id, restore) {
+        (Some(thread_id), true) => google_api::untrash_thread(token, thread_id).await,
+        (Some(thread_id), false) => google_api::trash_thread(token, thread_id).await,
+        (None, true) => google_api::untrash_message(token, &email.id).await,
+        (None, false) => google_api::trash_message(token, &email.id).await,
+    };
+    if let Err(e) = result {
+        return format!("{} failed: {}", if restore { "Restore" } else { "Trash" }, e);
+    }
+    let email = app.remove_selected(body_request_tx);
+    let (message_id, subject) = (email.id, email.subject);
+    if restore {
+        let _ = app.trash.forget(&message_id);
+        app.action_log.record(Action::Untrashed { message_id, thread_id, subject: subject.clone() });
+        format!("Restored from Trash: {}", subject)
+    } else {
+        let _ = app.trash.record(&message_id);
+        app.action_log.record(Action::Trashed { message_id, thread_id, subject: subject.clone() });
+        format!("Moved to Trash: {} (deleted for good in {} days)", subject, trash::RETENTION_DAYS)
+    }
+}
+
+// Saves one of the open message's attachments to the download directory
+async fn save_attachment(app: &App, token: &google_api::ApiToken, index: usize) -> String {
+    let (Some(email), Some(attachment)) = (app.emails.get(app.selected_index), app.current_attachments.get(index)) else {
+        return "No attachment selected".to_string();
+    };
+    let token = email.foreign_token().unwrap_or(token);
+    match attachment.save(token, &email.id, &paths::download_dir()).await {
+        Ok(path) => format!("Saved {}", path.display()),
+        Err(e) => format!("Couldn't save {}: {:#}", attachment.filename, e),
+    }
+}
+
+// The original's attachments are listed in the editor, so unwanted ones can be dropped
+async fn forward_selected(
+    app: &App,
+    terminal: &mut Term,
+    token: &google_api::ApiToken,
+    config: &Config,
+) -> Result<String> {
+    app.ensure_capability(Capability::Send)?;
+    app.ensure_local()?;
+    let Some(email) = app.emails.get(app.selected_index).filter(|_| app.view != View::Outbox) else {
+        bail!("No message selected");
+    };
+    let detail = google_api::get_full_message(token, &email.id).await?;
+    let subject = detail.get_header("Subject");
+    let lower = subject.to_ascii_lowercase();
+    let subject = if lower.starts_with("fwd:") || lower.starts_with("fw:") { subject } else { format!("Fwd: {}", subject) };
+    let headers = ["From", "Date", "Subject", "To", "Cc"].map(|name| (name, detail.get_header(name)));
+    let body = compose::forward_body(&headers, &google_api::decode_email_body(&detail));
+
+    let copies = attachments::Copies::fetch(token, &detail).await?;
+
+    suspend_terminal(terminal)?;
+    let prefill = OutgoingMessage { subject, body, attachments: copies.paths.clone(), ..Default::default() };
+    let edited = compose::edit_in_editor(&prefill, Some(0), &config.compose);
+    resume_terminal(terminal)?;
+    Ok(match edited? {
+        Some(message) => send_status(
+            outbox::send_or_queue(token, &message).await?,
+            "Forwarded",
+            "Offline: forward queued in the outbox",
+            "Forward saved to Drafts",
+        ),
+        None => "Forward cancelled".to_string(),
+    })
+}
+
+// Opens the selected draft in the editor again; what comes back is sent, or saved over the
+// draft with `draft: true`
+async fn resume_draft(
+    app: &App,
+    terminal: &mut Term,
+    token: &google_api::ApiToken,
+    config: &Config,
+) -> Result<String> {
+    app.ensure_capability(Capability::Modify)?;
+    let Some(email) = app.emails.get(app.selected_index) else {
+        bail!("No draft selected");
+    };
+    let drafts = google_api::list_drafts(token, "", DRAFTS_LOOKUP_LIMIT).await?;
+    let Some(draft) = drafts.iter().find(|d| d.message.id == email.id) else {
+        bail!("This draft no longer exists");
+    };
+    let detail = google_api::get_draft(token, &draft.id).await?.message;
+    let copies = attachments::Copies::fetch(token, &detail).await?;
+    let recipients = |name: &str| address::split_list(&detail.get_header(name));
+    let prefill = OutgoingMessage {
+        to: recipients("To"),
+        cc: recipients("Cc"),
+        bcc: recipients("Bcc"),
+        subject: detail.get_header("Subject"),
+        body: google_api::decode_email_body(&detail),
+        attachments: copies.paths.clone(),
+        // Quitting the editor unchanged must not send it
+        save_as_draft: true,
+        ..Default::default()
+    };
+
+    suspend_terminal(terminal)?;
+    let edited = compose::edit_in_editor(&prefill, None, &config.compose);
+    resume_terminal(terminal)?;
+    let Some(mut message) = edited? else {
+        return Ok("Draft left unchanged".to_string());
+    };
+    message.draft_id = Some(draft.id.clone());
+    message.thread_id = Some(detail.thread_id.clone());
+    // Replies keep threading with the message they answer
+    for name in ["In-Reply-To", "References"] {
+        let value = detail.get_header(name);
+        if !value.is_empty() {
+            message.extra_headers.push((name.to_string(), value));
+        }
+    }
+    Ok(send_status(
+        outbox::send_or_queue(token, &message).await?,
+        "Draft sent",
+        "Offline: draft queued in the outbox",
+        "Draft saved",
+    ))
+}
+
+// For the selected message when its sender isn't in my cached history, as sender or recipient
+fn new_sender_badge(app: &App) -> Option<Span<'static>> {
+    let email = app.emails.get(app.selected_index)?;
+    if matches!(app.view, View::Sent | View::Outbox) || !app.known_senders.is_first_time(&email.from, &email.id) {
+        return None;
+    }
+    Some(Span::styled(" NEW SENDER ", Style::default().bg(Color::Yellow).fg(Color::Black).bold()))
+}
+
+// Dots along a time axis, one per message and colored like the sender's marker, with the
+// open message drawn larger; dates of the first and last message underneath
+fn timeline_pane(points: &[timeline::Point], current: Option<&str>, width: usize) -> Paragraph<'static> {
+    let mut cells: Vec<Span> = vec![Span::styled("─", Style::default().fg(Color::DarkGray)); width];
+    for (point, column) in points.iter().zip(timeline::columns(points, width)) {
+        let style = sender_marker(&point.from).style;
+        let glyph = if current == Some(point.id.as_str()) { Span::styled("◉", style.bold()) } else { Span::styled("●", style) };
+        if let Some(cell) = cells.get_mut(column) {
+            *cell = glyph;
+        }
+    }
+    let date = |at: i64| {
+        chrono::DateTime::from_timestamp(at, 0)
+            .map(|d| d.with_timezone(&chrono::Local).format("%b %-d %H:%M").to_string())
+            .unwrap_or_default()
+    };
+    let (first, last) = (points[0].at, points[points.len() - 1].at);
+    let (start, end) = (date(first), date(last));
+    let gap = width.saturating_sub(start.len() + end.len());
+    let axis = Line::from(Span::styled(format!("{}{}{}", start, " ".repeat(gap), end), Style::default().fg(Color::Gray)));
+    let title = format!("Timeline: {} messages over {}", points.len(), stats::describe_delay(last - first));
+    Paragraph::new(vec![Line::from(cells), axis]).block(Block::default().borders(Borders::ALL).title(title))
+}
+
+// One line listing the numbered views and saved searches with their unread badges
+fn folder_bar(app: &App, config: &Config) -> Line<'static> {
+    let badge = |key: &str, show_total: bool| match app.label_counts.get(key) {
+        Some(counts) if show_total => counts.total.filter(|t| *t > 0).map(|t| format!(" ({})", t)),
+        Some(counts) if counts.unread > 0 => Some(format!(" {}", counts.unread)),
+        _ => None,
+    };
+    let mut spans = Vec::new();
+    for (i, view) in NUMBERED_VIEWS.iter().enumerate() {
+        let style = if *view == app.view { Style::default().bg(Color::Blue).fg(Color::White) } else { Style::default() };
+        spans.push(Span::styled(format!(" {} {}", i + 1, view.title()), style));
+        let count = match view {
+            View::Inbox => badge("inbox", false),
+            View::Trash => badge("trash", true),
+            _ => None,
+        };
+        if let Some(count) = count {
+            spans.push(Span::styled(count, style.fg(Color::Yellow).bold()));
+        }
+        spans.push(Span::styled(" ", style));
+    }
+    // 0 flips between the two, so it shows whichever is open
+    let awaiting = if app.view == View::AwaitingOthers { View::AwaitingOthers } else { View::AwaitingMe };
+    let style = if awaiting == app.view { Style::default().bg(Color::Blue).fg(Color::White) } else { Style::default() };
+    spans.push(Span::styled(format!(" 0 {} ", awaiting.title()), style));
+    for name in config.searches.keys() {
+        spans.push(Span::styled(format!(" │ {}", name), Style::default().fg(Color::Gray)));
+        if let Some(count) = badge(&format!("search:{}", name), false) {
+            spans.push(Span::styled(count, Style::default().fg(Color::Yellow).bold()));
+        }
+    }
+    Line::from(spans)
+}
+
+fn address_action_label(action: AddressAction, address: &str, senders: &SenderLists) -> String {
+    match action {
+        AddressAction::Compose => format!("Compose to {}", address),
+        AddressAction::ShowMail => "Show all mail from them".to_string(),
+        AddressAction::Copy => "Copy address".to_string(),
+        AddressAction::Vip if senders.vips.contains(address) => "Remove from VIPs".to_string(),
+        AddressAction::Vip => "Add to VIPs".to_string(),
+        AddressAction::Block if senders.blocked.contains(address) => "Unblock".to_string(),
+        AddressAction::Block => "Block (hide their mail)".to_string(),
+    }
+}
+
+// OSC 52 asks the terminal itself to set the clipboard, which also works over SSH
+fn copy_to_clipboard(terminal: &mut Term, value: &str) -> Result<()> {
+    use std::io::Write;
+    let backend = terminal.backend_mut();
+    write!(backend, "\x1b]52;c;{}\x07", STANDARD.encode(value))?;
+    backend.flush()?;
+    Ok(())
+}
+
+// Reports in the status line; "show all mail" asks for the list to be reloaded as a search
+async fn run_address_action(
+    app: &mut App,
+    terminal: &mut Term,
+    token: &google_api::ApiToken,
+    config: &Config,
+    action: AddressAction,
+    address: &str,
+) -> Result<CommandEffect> {
+    let status = match action {
+        AddressAction::Compose => {
+            app.ensure_capability(Capability::Send)?;
+            suspend_terminal(terminal)?;
+            let prefill = OutgoingMessage { to: vec![address.to_string()], ..Default::default() };
+            let edited = compose::edit_in_editor(&prefill, None, &config.compose);
+            resume_terminal(terminal)?;
+            match edited? {
+                Some(message) => send_status(
+                    outbox::send_or_queue(token, &message).await?,
+                    "Message sent",
+                    "Offline: message queued in the outbox",
+                    "Saved to Drafts",
+                ),
+                None => "Compose cancelled".to_string(),
+            }
+        }
+        AddressAction::Copy => {
+            copy_to_clipboard(terminal, address)?;
+            format!("Copied {}", address)
+        }
+        AddressAction::Vip => match app.senders.toggle_vip(address)? {
+            true => format!("{} is now a VIP", address),
+            false => format!("{} removed from VIPs", address),
+        },
+        AddressAction::Block => match app.senders.toggle_blocked(address)? {
+            true => format!("Blocked {}; their mail is hidden from the inbox and folders", address),
+            false => format!("Unblocked {}", address),
+        },
+        AddressAction::ShowMail => {
+            app.search_query = format!("from:{}", address);
+            app.sender_panel = None;
+            app.mode = AppMode::List;
+            app.view = View::Search;
+            return Ok(CommandEffect::Reload);
+        }
+    };
+    app.status_message = Some(status);
+    Ok(CommandEffect::None)
+}
+
+fn header_block(detail: &google_api::MessageDetail) -> String {
+    PREVIEW_HEADERS
+        .iter()
+        .map(|name| (name, detail.get_header(name)))
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| format!("{}: {}\n", name, value))
+        .collect()
+}
+
+fn email_info(detail: &google_api::MessageDetail, view: View) -> EmailInfo {
+    let from = if matches!(view, View::Sent | View::Drafts | View::AwaitingOthers) {
+        let mut recipients = detail.get_header("To");
+        let cc = detail.get_header("Cc");
+        if !cc.is_empty() {
+            recipients = format!("{}, {}", recipients, cc);
+        }
+        recipients
+    } else {
+        detail.get_header("From")
+    };
+    EmailInfo {
+        id: detail.id.clone(),
+        thread_id: detail.thread_id.clone(),
+        from: text::single_line(&from),
+        subject: text::single_line(&detail.get_header("Subject")),
+        is_unread: detail.is_unread(),
+        snippet: text::sanitize_multiline(&detail.snippet),
+        delivery_failed: false,
+        received_at: detail.received_at(),
+        message_count: 1,
+        label_ids: detail.label_ids.clone().unwrap_or_default(),
+        suggested_label: None,
+        account: None,
+        reply_to: detail.get_header("Reply-To"),
+        caution: Verdict::default(),
+    }
+}
+
+async fn load_message_page(
+    token: &google_api::ApiToken,
+    view: View,
+    query: &str,
+    page_token: Option<&str>,
+) -> Result<(Vec<EmailInfo>, Option<String>)> {
+    let began = Instant::now();
+    let message_list = google_api::MessagesList::new()
+        .q(query)
+        .page_token(page_token)
+        .max_results(PAGE_SIZE)
+        .include_spam_trash(view == View::Trash)
+        .send(token)
+        .await?;
+    timings::record(timings::LIST, began);
+    let message_ids = message_list.messages.unwrap_or_default();
+    let began = Instant::now();
+    let header_futures = message_ids
+        .iter()
+        .map(|msg| google_api::get_message_headers(token, &msg.id, view.list_headers()));
+    let results = futures::future::join_all(header_futures).await;
+    timings::record(timings::HEADERS, began);
+
+    let emails = results.into_iter().flatten().map(|detail| email_info(&detail, view)).collect();
+    Ok((emails, message_list.next_page_token))
+}
+
+// One row per conversation: a reply no longer shows up as a second row, and the message
+// count comes with the thread metadata instead of needing extra requests
+async fn load_thread_page(
+    token: &google_api::ApiToken,
+    view: View,
+    query: &str,
+    page_token: Option<&str>,
+    overrides: &ThreadOverrides,
+) -> Result<(Vec<EmailInfo>, Option<String>)> {
+    let began = Instant::now();
+    let thread_list = google_api::ThreadsList::new()
+        .q(query)
+        .page_token(page_token)
+        .max_results(PAGE_SIZE)
+        .send(token)
+        .await?;
+    timings::record(timings::LIST, began);
+    let thread_refs = thread_list.threads.unwrap_or_default();
+    let began = Instant::now();
+    let thread_futures = thread_refs
+        .iter()
+        .map(|t| google_api::get_thread(token, &t.id, view.list_headers()));
+    let results = futures::future::join_all(thread_futures).await;
+    timings::record(timings::HEADERS, began);
+
+    let mut emails = Vec::new();
+    for thread in results.into_iter().flatten() {
+        // Messages split off locally get a row of their own; the rest stay one conversation
+        let (split, rest): (Vec<_>, Vec<_>) =
+            thread.messages.unwrap_or_default().into_iter().partition(|m| overrides.is_split(&m.id));
+        let thread = google_api::Thread { id: thread.id, messages: Some(rest) };
+        if let Some(latest) = thread.latest() {
+            emails.push(EmailInfo {
+                is_unread: thread.is_unread(),
+                message_count: thread.message_count(),
+                ..email_info(latest, view)
+            });
+        }
+        emails.extend(split.iter().map(|m| email_info(m, view)));
+    }
+    Ok((emails, thread_list.next_page_token))
+}
+
+fn spawn_header_loader(
+    token: google_api::ApiToken,
+    view: View,
+    query: Option<String>,
+    page_token: Option<String>,
+    sensitivity: Sensitivity,
+) -> mpsc::Receiver<HeaderEvent> {
+    let (header_tx, header_rx) = mpsc::channel::<HeaderEvent>(100);
+    tokio::spawn(async move {
+        if view == View::NeedsReply {
+            let cached = cache::cached_messages();
+            let known = needs_reply::account_addresses(&token).await;
+            for detail in needs_reply::candidates(&cached, &known, sensitivity) {
+                if header_tx.send(HeaderEvent::Email(Box::new(email_info(detail, view)))).await.is_err() { break; }
+            }
+            return;
+        }
+        if matches!(view, View::AwaitingMe | View::AwaitingOthers) {
+            let cached = cache::cached_messages();
+            for detail in needs_reply::awaiting(&cached, view == View::AwaitingMe) {
+                if header_tx.send(HeaderEvent::Email(Box::new(email_info(detail, view)))).await.is_err() { break; }
+            }
+            return;
+        }
+        if view == View::Unified {
+            load_unified(&token, &query.unwrap_or_default(), &header_tx).await;
+            return;
+        }
+        let Some(query) = query else {
+            for entry in Outbox::load().entries {
+                let status = match entry.status {
+                    OutboxStatus::Pending => format!("Pending ({} attempt(s))", entry.attempts),
+                    OutboxStatus::Failed => "Failed".to_string(),
+                };
+                let error = entry.last_error.as_deref().unwrap_or("none");
+                let email_info = EmailInfo {
+                    id: entry.id.clone(),
+                    thread_id: String::new(),
+                    from: text::single_line(&format!("To: {}", entry.to.join(", "))),
+                    subject: text::single_line(&entry.subject),
+                    is_unread: false,
+                    snippet: text::sanitize_multiline(&format!("Status: {}\nLast error: {}\n\n{}", status, error, entry.body)),
+                    delivery_failed: entry.status == OutboxStatus::Failed,
+                    received_at: None,
+                    message_count: 1,
+                    label_ids: Vec::new(),
+                    suggested_label: None,
+                    account: None,
+                    reply_to: String::new(),
+                    caution: Verdict::default(),
+                };
+                if header_tx.send(HeaderEvent::Email(Box::new(email_info))).await.is_err() { break; }
+            }
+            return;
+        };
+        let page = if view.threaded() {
+            load_thread_page(&token, view, &query, page_token.as_deref(), &ThreadOverrides::load()).await
+        } else {
+            load_message_page(&token, view, &query, page_token.as_deref()).await
+        };
+        match page {
+            Ok((emails, next_page_token)) => {
+                for email_info in emails {
+                    if header_tx.send(HeaderEvent::Email(Box::new(email_info))).await.is_err() { break; }
+                }
+                let _ = header_tx.send(HeaderEvent::PageEnd { next_page_token }).await;
+            }
+            Err(e) => {
+                let _ = header_tx.send(HeaderEvent::Notice(format!("Loading failed: {:#}", e))).await;
+            }
+        }
+    });
+    header_rx
+}
+
+// First page of every signed-in account's inbox, merged newest first. Accounts whose token
+// can't be loaded are left out and named in a notice.
+async fn load_unified(token: &google_api::ApiToken, query: &str, header_tx: &mpsc::Sender<HeaderEvent>) {
+    let current = store::account();
+    let mut accounts = Vec::new();
+    let mut skipped = Vec::new();
+    // Sealed tokens are left out rather than prompting over the full-screen interface
+    for name in std::iter::once(None).chain(store::accounts().into_iter().map(Some)) {
+        let loaded = if name == current {
+            Ok(Some(token.clone()))
+        } else {
+            google_api::saved_token_for(name.as_deref()).await
+        };
+        match loaded {
+            Ok(Some(token)) => accounts.push(Arc::new(RowAccount { name, token })),
+            // Account directories without a token are not signed in; nothing to report
+            Ok(None) => {}
+            Err(_) => skipped.push(name.unwrap_or_else(|| "default".to_string())),
+        }
+    }
+
+    let pages = futures::future::join_all(
+        accounts.iter().map(|account| {
+            // Overrides are kept per account, so each account's rows follow its own
+            let overrides = ThreadOverrides::load_for(account.name.as_deref());
+            async move { load_thread_page(&account.token, View::Unified, query, None, &overrides).await }
+        }),
+    )
+    .await;
+    let mut emails = Vec::new();
+    for (account, page) in accounts.iter().zip(pages) {
+        match page {
+            Ok((page, _)) => {
+                emails.extend(page.into_iter().map(|email| EmailInfo { account: Some(account.clone()), ..email }))
             }
+            Err(_) => skipped.push(account.label().to_string()),
+        }
+    }
+    emails.sort_by_key(|e| std::cmp::Reverse(e.received_at));
+    for email in emails {
+        if header_tx.send(HeaderEvent::Email(Box::new(email))).await.is_err() {
+            return;
         }
     }
+    // Only the first page of each account is merged
+    let _ = header_tx.send(HeaderEvent::PageEnd { next_page_token: None }).await;
+    if !skipped.is_empty() {
+        let _ = header_tx.send(HeaderEvent::Notice(format!("Left out: {}", skipped.join(", ")))).await;
+    }
+}
 
-    fn scroll_down(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_add(1);
+fn granted_capabilities(token: &google_api::ApiToken) -> Vec<Capability> {
+    Capability::ALL.into_iter().filter(|c| token.has(*c)).collect()
+}
+
+// A fresh token for the selected account after the saved one was rejected
+async fn sign_in_again() -> Result<google_api::ApiToken> {
+    if google_api::service_account().is_some() {
+        return google_api::saved_token().await?.context("The service account produced no token");
     }
+    login::sign_in().await
+}
 
-    fn scroll_up(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_sub(1);
+// Makes `name` the active account, taking its session lock and loading its token. On failure
+// the previous account stays active.
+async fn open_account(name: Option<&str>) -> Result<(google_api::ApiToken, store::Lock)> {
+    let previous = store::account();
+    store::set_account(name)?;
+    let opened = async {
+        let lock = store::lock(LOCK_FILE)?;
+        let flag = name.map(|n| format!(" --account {}", n)).unwrap_or_default();
+        let token = google_api::saved_token()
+            .await?
+            .with_context(|| format!("Not signed in there; run `gmail-cli{} auth login` first", flag))?;
+        Ok((token, lock))
+    }
+    .await;
+    if opened.is_err() {
+        store::set_account(previous.as_deref())?;
     }
+    opened
 }
 
-pub async fn run(token: google_api::ApiToken) -> Result<()> {
-    // --- Channel Setup ---
-    let (header_tx, mut header_rx) = mpsc::channel::<EmailInfo>(100);
-    let (body_request_tx, mut body_request_rx) = mpsc::channel::<String>(10);
-    let (body_result_tx, mut body_result_rx) = mpsc::channel::<String>(10);
+// Background tasks working for the signed-in account, replaced as a whole when switching
+// accounts; dropping them stops the tasks
+struct Workers {
+    body_request_tx: mpsc::Sender<BodyRequest>,
+    body_result_rx: mpsc::Receiver<BodyResult>,
+    counts_rx: mpsc::Receiver<(String, Counts)>,
+    classifier_rx: mpsc::Receiver<(Classifier, HashMap<String, String>)>,
+    sync_rx: mpsc::Receiver<offline::SyncReport>,
+    handles: Vec<tokio::task::JoinHandle<()>>,
+}
 
-    // --- Background Tasks ---
-    let token_clone_1 = token.clone();
-    tokio::spawn(async move {
-        if let Ok(message_list) = google_api::list_messages(&token_clone_1).await {
-            let message_ids = message_list.messages.unwrap_or_default();
-            let header_futures = message_ids
-                .iter()
-                .map(|msg| google_api::get_message_headers(&token_clone_1, &msg.id));
-            let mut results = futures::future::join_all(header_futures).await;
-            for detail_result in results.drain(..) {
-                if let Ok(detail) = detail_result {
-                    let email_info = EmailInfo {
-                        id: detail.id.clone(),
-                        from: detail.get_header("From"),
-                        subject: detail.get_header("Subject"),
-                        is_unread: detail.is_unread(),
-                        snippet: detail.snippet.clone(),
-                    };
-                    if header_tx.send(email_info).await.is_err() { break; }
-                }
+impl Workers {
+    fn start(token: &google_api::ApiToken, config: &Config) -> Self {
+        let (body_request_tx, mut body_request_rx) = mpsc::channel::<BodyRequest>(10);
+        let (body_result_tx, body_result_rx) = mpsc::channel::<BodyResult>(10);
+        let mut handles = vec![google_api::spawn_token_refresher(token.clone())];
+
+        let body_token = token.clone();
+        handles.push(tokio::spawn(async move {
+            while let Some(request) = body_request_rx.recv().await {
+                let token = request.token.as_ref().unwrap_or(&body_token);
+                let loaded = async {
+                    let detail = google_api::get_full_message(token, &request.id).await?;
+                    // The cache belongs to the selected account
+                    if request.token.is_none() {
+                        let _ = cache::store_message(&detail);
+                    }
+                    // Decoding may run a mailcap viewer and rendering an external HTML command, so
+                    // both happen off the async workers
+                    let (prefs, reader) = (request.prefs.clone(), request.reader);
+                    tokio::task::spawn_blocking(move || {
+                        let decoded = google_api::decode_email_body(&detail);
+                        let shown = google_api::render_body(&detail, &prefs, reader);
+                        (detail, decoded, shown)
+                    })
+                    .await
+                    .context("Rendering the message failed")
+                };
+                let result = match loaded.await {
+                    Ok((detail, decoded, shown)) => {
+                        BodyResult {
+                            id: detail.id.clone(),
+                            body: format!("{}\n{}", header_block(&detail), shown),
+                            preview: text::new_content(&decoded),
+                            privacy: privacy::analyze(&detail),
+                            attachments: attachments::Attachment::list(&detail),
+                            failed: false,
+                        }
+                    }
+                    Err(e) => {
+                        let message = format!("Couldn't load this message: {:#}", e);
+                        BodyResult {
+                            id: request.id,
+                            body: message.clone(),
+                            preview: message,
+                            privacy: PrivacyReport::default(),
+                            attachments: Vec::new(),
+                            failed: true,
+                        }
+                    }
+                };
+                if body_result_tx.send(result).await.is_err() { break; }
             }
+        }));
+
+        if !google_api::read_only_requested() && token.has(Capability::Send) {
+            let outbox_token = token.clone();
+            handles.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    let _ = outbox::retry_pending(&outbox_token).await;
+                }
+            }));
         }
-    });
 
-    let token_clone_2 = token.clone();
-    tokio::spawn(async move {
-        while let Some(email_id) = body_request_rx.recv().await {
-            if let Ok(detail) = google_api::get_full_message(&token_clone_2, &email_id).await {
-                let body = google_api::decode_email_body(&detail);
-                if body_result_tx.send(body).await.is_err() { break; }
-            }
+        // Label changes queued while offline go out once Gmail answers again
+        let (sync_tx, sync_rx) = mpsc::channel(1);
+        if !google_api::read_only_requested() {
+            let sync_token = token.clone();
+            handles.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    if let Ok(report) = offline::sync(&sync_token).await
+                        && (report.applied > 0 || report.new_conflicts > 0)
+                        && sync_tx.send(report).await.is_err()
+                    {
+                        break;
+                    }
+                }
+            }));
         }
-    });
+
+        let counts_rx = counts::spawn_refresher(token.clone(), count_sources(config));
+
+        let (classifier_tx, classifier_rx) = mpsc::channel(1);
+        let names_token = token.clone();
+        handles.push(tokio::spawn(async move {
+            let Ok(classifier) = tokio::task::spawn_blocking(|| Classifier::train(&cache::cached_messages())).await else {
+                return;
+            };
+            // Names are only for display; suggestions fall back to label ids offline
+            let names = google_api::list_labels(&names_token)
+                .await
+                .map(|labels| labels.into_iter().map(|l| (l.id, l.name)).collect())
+                .unwrap_or_default();
+            let _ = classifier_tx.send((classifier, names)).await;
+        }));
+
+        Workers { body_request_tx, body_result_rx, counts_rx, classifier_rx, sync_rx, handles }
+    }
+}
+
+// Badges for the inbox, trash and every saved search
+fn count_sources(config: &Config) -> BTreeMap<String, CountSource> {
+    let mut sources = BTreeMap::new();
+    sources.insert("inbox".to_string(), CountSource::Label("INBOX"));
+    sources.insert("trash".to_string(), CountSource::Label("TRASH"));
+    for (name, query) in &config.searches {
+        sources.insert(format!("search:{}", name), CountSource::Search(query.clone()));
+    }
+    sources
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+pub async fn run(
+    mut token: google_api::ApiToken,
+    // Replaced, releasing the old one, when switching accounts
+    mut _lock: store::Lock,
+    date_range: DateRange,
+    mut config: Config,
+    // A Gmail query for one message to show in the viewer right away, from `open`
+    open: Option<String>,
+) -> Result<()> {
+    let (startup_view, startup_query) = match &open {
+        Some(query) => (View::Search, query.clone()),
+        None => resolve_startup_view(&config.ui.startup, &config)?,
+    };
+    let mut open_on_load = open.is_some();
+
+    // --- Background Tasks ---
+    let mut workers = Workers::start(&token, &config);
 
     // --- App Initialization ---
     let mut app = App {
         mode: AppMode::List,
+        view: startup_view,
         is_loading: true,
         emails: Vec::new(),
+        next_page_token: None,
+        startup_refresh: None,
         selected_index: 0,
         current_email_body: "Loading email list...".to_string(),
+        current_preview: None,
+        untranslated_body: None,
+        translation: None,
+        speech: None,
+        conversation: None,
+        conversation_loading: None,
+        current_privacy: PrivacyReport::default(),
+        current_attachments: Vec::new(),
+        attachment_picker: None,
+        delete_confirm: None,
+        body_cache: LruCache::new(BODY_CACHE_SIZE),
+        show_privacy: false,
+        focus: Focus::List,
+        preview_scroll: 0,
+        sidebar_scroll: 0,
+        sender_panel: None,
+        show_debug: false,
+        heatmap: None,
+        read_only: google_api::read_only_requested(),
+        granted: granted_capabilities(&token),
+        wanted: std::cell::Cell::new(None),
+        timeline: None,
+        timeline_loading: None,
+        bounced: HashSet::new(),
+        bounces_loading: None,
+        label_counts: HashMap::new(),
+        classifier: None,
+        label_names: HashMap::new(),
+        known_senders: KnownSenders::from_cache(),
         scroll_offset: 0,
+        watched: WatchedThreads::load(),
+        thread_overrides: ThreadOverrides::load(),
+        merge_mark: None,
+        deadlines: Deadlines::load(),
+        trash: TrashLog::load(),
+        senders: SenderLists::load(),
+        address_menu: None,
+        account_picker: None,
+        conflict_picker: ConflictPicker::open(),
+        triage_layout: false,
+        density: config.ui.density,
+        needs_reply_sensitivity: config.needs_reply.sensitivity,
+        reader_mode: config.ui.reader_mode,
+        table_state: TableState::default(),
+        action_log: ActionLog::default(),
+        date_range: date_range.clone(),
+        inbox_query: config.inbox_query().to_string(),
+        search_query: startup_query,
+        command_input: String::new(),
+        status_message: None,
     };
+    app.load_bounces(&token);
+    let mut header_rx = spawn_header_loader(token.clone(), app.view, app.view_query(), None, app.needs_reply_sensitivity);
+    let mut initial_load_done = false;
+    if !open_on_load {
+        app.startup_refresh = Some(0);
+        if app.show_snapshot(&config) {
+            app.select(0, workers.body_request_tx.clone());
+            initial_load_done = true;
+        }
+    }
 
     // --- TUI Setup ---
     enable_raw_mode()?;
@@ -138,24 +2014,151 @@ pub async fn run(token: google_api::ApiToken) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // --- Main Loop ---
-    let mut initial_load_done = false;
+    let mut config_watcher = ConfigWatcher::new();
     loop {
         // --- Event & Data Handling ---
+        // Paths, network and HTML settings need a restart, and `ui.startup` only matters at
+        // launch; every other setting, whether copied into `app` or read from `config`, takes
+        // effect here
+        match config_watcher.poll() {
+            Some(Ok(reloaded)) => {
+                if reloaded.searches != config.searches {
+                    app.label_counts.retain(|key, _| !key.starts_with("search:"));
+                    workers.counts_rx = counts::spawn_refresher(token.clone(), count_sources(&reloaded));
+                }
+                app.density = reloaded.ui.density;
+                // Only an edit to the setting overrides what `v` chose
+                if reloaded.ui.reader_mode != config.ui.reader_mode {
+                    app.reader_mode = reloaded.ui.reader_mode;
+                    app.rerender(workers.body_request_tx.clone());
+                }
+                google_api::encrypt_saved_tokens(reloaded.security.encrypt_token);
+                app.needs_reply_sensitivity = reloaded.needs_reply.sensitivity;
+                let query_changed = app.inbox_query != reloaded.inbox_query();
+                app.inbox_query = reloaded.inbox_query().to_string();
+                if query_changed && matches!(app.view, View::Inbox | View::Unified) {
+                    header_rx = app.switch_view(app.view, &token);
+                    initial_load_done = false;
+                }
+                config = reloaded;
+                app.status_message = Some("Config reloaded".to_string());
+            }
+            // TOML errors span several lines, with a caret under the problem; the footer has one
+            Some(Err(e)) => app.status_message = Some(format!("Config not reloaded: {:#}", e).split_whitespace().collect::<Vec<_>>().join(" ")),
+            None => {}
+        }
+        if let Ok(report) = workers.sync_rx.try_recv() {
+            let mut status = format!("Applied {} offline change(s)", report.applied);
+            if report.new_conflicts > 0 {
+                status = format!("{}; {} need a decision", status, report.new_conflicts);
+                app.conflict_picker = ConflictPicker::open();
+            }
+            app.status_message = Some(status);
+        }
+        while let Ok((key, counts)) = workers.counts_rx.try_recv() {
+            app.label_counts.insert(key, counts);
+        }
+        if let Ok((classifier, names)) = workers.classifier_rx.try_recv() {
+            app.classifier = Some(classifier);
+            app.label_names = names;
+            let mut emails = std::mem::take(&mut app.emails);
+            for email in &mut emails {
+                app.suggest_label(email);
+            }
+            app.emails = emails;
+        }
+        if let Some(rx) = &mut app.bounces_loading
+            && let Ok(bounced) = rx.try_recv()
+        {
+            app.bounces_loading = None;
+            for email in &mut app.emails {
+                email.delivery_failed |= bounced.contains(&email.id);
+            }
+            app.bounced = bounced;
+        }
+        if let Some(rx) = &mut app.timeline_loading
+            && let Ok(points) = rx.try_recv()
+        {
+            app.timeline_loading = None;
+            app.timeline = points;
+        }
+        if let Some(rx) = &mut app.conversation_loading
+            && let Ok(result) = rx.try_recv()
+        {
+            app.conversation_loading = None;
+            match result {
+                Ok(conversation) => {
+                    app.scroll_offset = conversation.offset();
+                    app.conversation = Some(conversation);
+                    app.status_message = None;
+                }
+                Err(e) => app.status_message = Some(format!("Couldn't load the conversation: {:#}", e)),
+            }
+        }
+        if let Some(pending) = &mut app.translation
+            && let Ok(result) = pending.rx.try_recv()
+        {
+            let body = app.translation.take().map(|pending| pending.body).unwrap_or_default();
+            app.show_translation(body, result, &config.translate);
+        }
+        if app.speech.as_mut().is_some_and(|speech| !speech.is_playing()) {
+            app.speech = None;
+        }
         if !app.is_loading {
-             if let Ok(body) = body_result_rx.try_recv() {
-                app.current_email_body = body;
+             if let Ok(mut result) = workers.body_result_rx.try_recv() {
+                result.body = text::sanitize_multiline(&result.body);
+                result.preview = text::sanitize_multiline(&result.preview);
+                let trackers = result.privacy.tracking_pixels.len() + result.privacy.redirect_links.len();
+                if !result.failed {
+                    app.body_cache.insert(&result.id, result.clone());
+                }
+                if !result.failed
+                    && let Some(index) = app.emails.iter().position(|e| e.id == result.id)
+                {
+                    app.emails[index].caution = app.assess(&app.emails[index], Some(trackers), &config);
+                }
+                // Ignore bodies for messages the user has already moved away from
+                if app.emails.get(app.selected_index).is_some_and(|e| e.id == result.id) {
+                    app.current_email_body = result.body;
+                    app.current_preview = Some(result.preview);
+                    app.current_privacy = result.privacy;
+                    app.current_attachments = result.attachments;
+                }
             }
         } else {
             match header_rx.try_recv() {
-                Ok(email) => {
-                    app.emails.push(email);
+                Ok(HeaderEvent::Email(email)) if app.view.hides_blocked() && app.senders.is_blocked(&email.from) => {}
+                Ok(HeaderEvent::Email(email)) if app.absorb_merged(&email) => {}
+                Ok(HeaderEvent::Email(mut email)) => {
+                    email.delivery_failed |= app.bounced.contains(&email.id);
+                    app.suggest_label(&mut email);
+                    email.caution = app.assess(&email, None, &config);
+                    // Messages trashed elsewhere start their retention clock when first seen
+                    if app.view == View::Trash && app.trash.observe(&email.id) {
+                        let _ = app.trash.save();
+                    }
+                    match app.startup_refresh {
+                        Some(fresh) => app.reconcile(*email, fresh, workers.body_request_tx.clone()),
+                        None => app.emails.push(*email),
+                    }
                     if !initial_load_done {
-                        app.select(0, body_request_tx.clone());
+                        app.select(0, workers.body_request_tx.clone());
                         initial_load_done = true;
                     }
+                    if open_on_load {
+                        open_on_load = false;
+                        app.open_selected(&token);
+                    }
                 },
+                Ok(HeaderEvent::PageEnd { next_page_token }) => {
+                    app.next_page_token = next_page_token;
+                    app.finish_startup_refresh(workers.body_request_tx.clone());
+                },
+                Ok(HeaderEvent::Notice(notice)) => app.status_message = Some(notice),
                 Err(mpsc::error::TryRecvError::Disconnected) => {
                     app.is_loading = false;
+                    // The refresh failed; the snapshot stays up rather than an empty list
+                    app.startup_refresh = None;
                 },
                 _ => {}
             }
@@ -166,50 +2169,241 @@ pub async fn run(token: google_api::ApiToken) -> Result<()> {
             let main_area = f.area();
             
             match app.mode {
-                AppMode::List => {
+                AppMode::List | AppMode::Command => {
+                    let rows = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(1), Constraint::Min(0)])
+                        .split(main_area);
+                    f.render_widget(Paragraph::new(folder_bar(&app, &config)), rows[0]);
                     let main_chunks = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
-                        .split(main_area);
+                        .split(rows[1]);
                     
-                    let title = if app.is_loading { "Inbox (Loading...)" } else { "Primary Inbox" };
-                    let header_cells = ["From", "Subject"]
-                        .iter()
-                        .map(|h| Cell::from(*h).style(Style::default().bold().underlined()));
+                    let mut view_title = app.view.title().to_string();
+                    if let Some(account) = store::account() {
+                        view_title = format!("[{}] {}", account, view_title);
+                    }
+                    if app.read_only {
+                        view_title = format!("[read-only] {}", view_title);
+                    }
+                    if app.view == View::Search {
+                        view_title = format!("{}: {}", view_title, app.search_query);
+                    }
+                    if !app.date_range.is_empty() {
+                        view_title = format!("{} [{}]", view_title, app.date_range.describe());
+                    }
+                    let title = if app.is_loading {
+                        format!("{} (Loading...)", view_title)
+                    } else if app.next_page_token.is_some() {
+                        format!("{} ({} loaded, more at the end)", view_title, app.emails.len())
+                    } else {
+                        view_title
+                    };
+                    let unified = app.view == View::Unified;
+                    let account_width = if unified { ACCOUNT_COLUMN_WIDTH } else { 0 };
+                    let header_cells = unified
+                        .then_some("Account")
+                        .into_iter()
+                        .chain([app.view.correspondent_column(), "Subject"])
+                        .map(|h| Cell::from(h).style(Style::default().bold().underlined()));
                     let header = Row::new(header_cells).height(1);
 
+                    // Truncate ourselves (grapheme/width aware) so wide characters never spill
+                    // into the next column or get cut in half by the widget
+                    let inner_width = main_chunks[0].width.saturating_sub(3 + account_width) as usize;
+                    let from_width = inner_width * 40 / 100;
+                    let subject_width = inner_width - from_width;
+
+                    let local_now = chrono::Local::now();
+                    let now = local_now.timestamp();
                     let rows = app.emails.iter().enumerate().map(|(i, email)| {
                         let is_selected = i == app.selected_index;
+                        let deadline = app.deadlines.get(&email.id);
+                        let overdue = deadline.is_some_and(|d| d.is_overdue(local_now));
                         let style = if is_selected {
                             Style::default().bg(Color::Blue).fg(Color::White)
+                        } else if overdue {
+                            Style::default().bold().fg(Color::LightRed)
                         } else if email.is_unread {
                             Style::default().bold().bg(Color::DarkGray)
                         } else { Style::default() };
-                        Row::new(vec![
-                            Cell::from(email.from.clone()),
-                            Cell::from(email.subject.clone()),
-                        ]).style(style)
+                        let mut from_spans = vec![sender_marker(&email.from)];
+                        if app.senders.is_vip(&email.from) {
+                            from_spans.push(Span::styled("★ ", Style::default().fg(Color::Magenta)));
+                        }
+                        if email.caution.is_flagged(&config.caution) {
+                            from_spans.push(Span::styled(format!("⚠{} ", email.caution.score), Style::default().fg(Color::LightRed).bold()));
+                        }
+                        if email.delivery_failed {
+                            from_spans.push(Span::styled("✗ ", Style::default().fg(Color::Red).bold()));
+                        }
+                        let count = if email.message_count > 1 { format!(" ({})", email.message_count) } else { String::new() };
+                        let badge_width: usize = from_spans.iter().map(|s| s.width()).sum::<usize>() + count.len();
+                        from_spans.push(Span::raw(text::truncate_to_width(&email.from, from_width.saturating_sub(badge_width))));
+                        from_spans.push(Span::styled(count, Style::default().fg(Color::Gray)));
+                        let from_cell = Cell::from(Line::from(from_spans));
+                        let mut subject_spans = Vec::new();
+                        if app.watched.contains(&email.thread_id) {
+                            subject_spans.push(Span::styled("◉ ", Style::default().fg(Color::Yellow)));
+                        }
+                        if let Some(deadline) = deadline {
+                            let color = if overdue { Color::Red } else { Color::Indexed(214) };
+                            subject_spans.push(Span::styled(
+                                format!("⚑{} ", reminders::describe_remaining(deadline.due, local_now)),
+                                Style::default().fg(color).bold(),
+                            ));
+                        }
+                        if app.view == View::Inbox
+                            && let Some(badge) = age_badge(email.received_at, now, &config.ui)
+                        {
+                            subject_spans.push(badge);
+                        }
+                        if app.view == View::Trash
+                            && let Some((days, estimated)) = app.trash.days_left(&email.id, local_now)
+                        {
+                            // Only an upper bound when the message was trashed outside this app
+                            let bound = if estimated { "≤" } else { "" };
+                            let color = if days <= 3 { Color::Red } else { Color::Gray };
+                            subject_spans.push(Span::styled(format!("{}{}d left ", bound, days), Style::default().fg(color)));
+                        }
+                        let suggestion = email.suggested_label.as_deref().map(|id| {
+                            Span::styled(format!(" → {}", app.label_name(id)), Style::default().fg(Color::DarkGray).italic())
+                        });
+                        let badge_width: usize =
+                            subject_spans.iter().chain(suggestion.iter()).map(|s| s.width()).sum();
+                        subject_spans.push(Span::raw(text::truncate_to_width(&email.subject, subject_width.saturating_sub(badge_width))));
+                        subject_spans.extend(suggestion);
+                        let subject_line = Line::from(subject_spans);
+                        let (subject_cell, height) = match app.density {
+                            Density::Compact => (Cell::from(subject_line), 1),
+                            Density::Comfortable => {
+                                let snippet = text::truncate_to_width(&text::single_line(&email.snippet), subject_width);
+                                let snippet_line = Line::from(Span::styled(snippet, Style::default().fg(Color::Gray).not_bold()));
+                                (Cell::from(vec![subject_line, snippet_line]), 2)
+                            }
+                        };
+                        let account_cell = email.account.as_ref().map(|a| {
+                            Cell::from(text::truncate_to_width(a.label(), account_width.saturating_sub(1) as usize))
+                                .style(Style::default().fg(Color::Cyan))
+                        });
+                        Row::new(account_cell.into_iter().chain([from_cell, subject_cell])).style(style).height(height)
                     });
 
-                    let table = Table::new(rows, [Constraint::Percentage(40), Constraint::Percentage(60)])
+                    let widths = unified
+                        .then_some(Constraint::Length(account_width))
+                        .into_iter()
+                        .chain([Constraint::Fill(40), Constraint::Fill(60)]);
+                    let table = Table::new(rows, widths)
                         .header(header)
-                        .block(Block::default().borders(Borders::ALL).title(title));
-                    f.render_widget(table, main_chunks[0]);
+                        .block(app.pane_block(Focus::List, title));
+                    app.table_state.select(Some(app.selected_index));
+                    f.render_stateful_widget(table, main_chunks[0], &mut app.table_state);
 
-                    let selected_email_snippet = app.emails.get(app.selected_index)
-                        .map_or(String::new(), |email| email.snippet.clone());
+                    if app.triage_layout {
+                        let items: Vec<ListItem> = app.action_log.entries.iter().map(|entry| {
+                            let text = format!("{} {}", entry.time, entry.action.describe());
+                            if entry.undone {
+                                ListItem::new(format!("{} (undone)", text))
+                                    .style(Style::default().fg(Color::DarkGray).crossed_out())
+                            } else {
+                                ListItem::new(text)
+                            }
+                        }).collect();
+                        let log = List::new(items)
+                            .block(app.pane_block(Focus::Sidebar, "Actions this session"))
+                            .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+                        let mut log_state = ListState::default();
+                        if !app.action_log.entries.is_empty() {
+                            log_state.select(Some(app.action_log.selected));
+                        }
+                        f.render_stateful_widget(log, main_chunks[1], &mut log_state);
+                    } else {
+                        let selected_email_snippet = match &app.current_preview {
+                            Some(preview) => preview.clone(),
+                            None => app.emails.get(app.selected_index).map_or(String::new(), |email| email.snippet.clone()),
+                        };
 
-                    let preview = Paragraph::new(selected_email_snippet)
-                        .block(Block::default().borders(Borders::ALL).title("Preview"))
-                        .wrap(Wrap { trim: true });
-                    f.render_widget(preview, main_chunks[1]);
+                        let mut preview_title = vec![Span::raw("Preview ")];
+                        preview_title.extend(new_sender_badge(&app));
+                        let preview_block = app.pane_block(Focus::Preview, Line::from(preview_title));
+                        let preview = text_pane(&selected_email_snippet, preview_block, main_chunks[1], true)
+                            .scroll((app.preview_scroll, 0));
+                        f.render_widget(preview, main_chunks[1]);
+                    }
                 }
                 AppMode::Viewing => {
-                    let email_view = Paragraph::new(app.current_email_body.as_str())
-                        .block(Block::default().borders(Borders::ALL).title("Content"))
-                        .wrap(Wrap { trim: false })
-                        .scroll((app.scroll_offset, 0));
-                    f.render_widget(email_view, main_area);
+                    let tracker_count = app.current_privacy.tracking_pixels.len()
+                        + app.current_privacy.redirect_links.len();
+                    let mut title_spans = vec![match &app.conversation {
+                        Some(conversation) => Span::raw(format!("Conversation ({} messages) ", conversation.len())),
+                        None => Span::raw("Content "),
+                    }];
+                    title_spans.extend(new_sender_badge(&app));
+                    if app.reader_mode {
+                        title_spans.push(Span::styled(" READER ", Style::default().bg(Color::Cyan).fg(Color::Black)));
+                        title_spans.push(Span::raw(" "));
+                    }
+                    if !app.current_attachments.is_empty() {
+                        title_spans.push(Span::styled(format!("[{} attached] ", app.current_attachments.len()), Style::default().fg(Color::Yellow)));
+                    }
+                    if tracker_count > 0 {
+                        title_spans.push(Span::styled(format!("[{} trackers] ", tracker_count), Style::default().fg(Color::Red)));
+                    }
+                    if let Some(email) = app.emails.get(app.selected_index)
+                        && email.caution.is_flagged(&config.caution)
+                    {
+                        title_spans.push(Span::styled(
+                            format!("[caution: {}] ", email.caution.reasons.join(", ")),
+                            Style::default().fg(Color::LightRed).bold(),
+                        ));
+                    }
+                    let content_title = Line::from(title_spans);
+                    let content_block = app.pane_block(Focus::Viewer, content_title);
+
+                    let mut viewer_area = main_area;
+                    if let Some(points) = &app.timeline {
+                        let rows = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(4), Constraint::Min(0)])
+                            .split(main_area);
+                        let current = app.emails.get(app.selected_index).map(|e| e.id.as_str());
+                        f.render_widget(timeline_pane(points, current, rows[0].width.saturating_sub(2) as usize), rows[0]);
+                        viewer_area = rows[1];
+                    }
+
+                    let shown = app.conversation.as_ref().map_or(app.current_email_body.as_str(), Conversation::text);
+                    let mut side_panels = Vec::new();
+                    if let Some(sender) = &app.sender_panel {
+                        side_panels.push(("Sender", sender.clone()));
+                    }
+                    if app.show_privacy {
+                        side_panels.push(("Privacy", app.current_privacy.render()));
+                    }
+                    if !side_panels.is_empty() {
+                        let chunks = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+                            .split(viewer_area);
+                        let email_view = text_pane(shown, content_block, chunks[0], false)
+                            .scroll((app.scroll_offset, 0));
+                        f.render_widget(email_view, chunks[0]);
+                        let panel_areas = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints(vec![Constraint::Ratio(1, side_panels.len() as u32); side_panels.len()])
+                            .split(chunks[1]);
+                        for ((title, content), area) in side_panels.into_iter().zip(panel_areas.iter()) {
+                            let panel = Paragraph::new(content)
+                                .block(app.pane_block(Focus::Sidebar, title))
+                                .wrap(Wrap { trim: false })
+                                .scroll((app.sidebar_scroll, 0));
+                            f.render_widget(panel, *area);
+                        }
+                    } else {
+                        let email_view = text_pane(shown, content_block, viewer_area, false)
+                            .scroll((app.scroll_offset, 0));
+                        f.render_widget(email_view, viewer_area);
+                    }
                 }
             }
 
@@ -218,49 +2412,885 @@ pub async fn run(token: google_api::ApiToken) -> Result<()> {
                 .constraints([Constraint::Min(0), Constraint::Length(1)])
                 .split(main_area);
 
-            let footer_text = match app.mode {
-                AppMode::List => "↑/↓: Navigate | Enter: View Full Email | q: Quit",
-                AppMode::Viewing => "↑/↓: Scroll | q: Back to List",
+            let mut footer_text = match (&app.mode, &app.status_message) {
+                (AppMode::Command, _) => format!(":{}", app.command_input),
+                _ if token.is_rejected() => "Google rejected the saved authorization | L: Sign In Again | q: Quit".to_string(),
+                (_, Some(message)) => message.clone(),
+                (AppMode::List, None) if app.triage_layout => concat!(
+                    "↑/↓: Navigate | Tab: Focus | K/J: Select Action | u: Undo Action | t: Preview Layout",
+                    " | q: Quit",
+                )
+                .to_string(),
+                (AppMode::List, None) => concat!(
+                    "↑/↓: Navigate | Tab: Focus | Enter: View Full Email | R/g: Reply/Reply All | f: Forward",
+                    " | e: Archive | d: Trash/Restore | X: Delete Forever (Trash) | l: Apply Suggested Label",
+                    " | w: Watch Thread | t: Triage Layout | D: Density | H: Heatmap | A: Account",
+                    " | b: Respond By",
+                    " | 1-9: Inbox/Sent/Outbox/Archive/All/Trash/Needs Reply/All Inboxes/Drafts (Enter resumes)",
+                    " | 0: Awaiting Me/Others | r/x: Retry/Delete (Outbox) | :goto YYYY-MM | :trash",
+                    " | :conflicts | :merge/:unmerge/:split | :prefs | :run <plugin> | /: Search | q: Quit",
+                )
+                .to_string(),
+                (AppMode::Viewing, None) if app.conversation.is_some() => concat!(
+                    "↑/↓: Scroll | ]/[: Next/Previous Message | Space: Expand/Collapse | c: Just This Message",
+                    " | R/g: Reply/Reply All | f: Forward | q: Back to List",
+                )
+                .to_string(),
+                (AppMode::Viewing, None) => concat!(
+                    "↑/↓: Scroll | c: Conversation | Tab: Focus | Enter: Sender Actions | R/g: Reply/Reply All",
+                    " | f: Forward | a: Save Attachment | s: Mail From Sender | S: Same Subject | p: Privacy",
+                    " | i: Sender | v: Reader Mode | T: Translate | P: Read Aloud | q: Back to List",
+                )
+                .to_string(),
             };
+            if app.speech.is_some() && !matches!(app.mode, AppMode::Command) {
+                footer_text = format!("♪ Reading aloud (P: Stop) | {}", footer_text);
+            }
             let footer = Paragraph::new(footer_text)
                 .style(Style::default().fg(Color::White).bg(Color::DarkGray));
             f.render_widget(footer, footer_chunks[1]);
+
+            if let Some(heatmap) = &app.heatmap {
+                let width = heatmap.iter().map(|l| text::display_width(l)).max().unwrap_or(0) as u16 + 2;
+                let height = heatmap.len() as u16 + 2;
+                let area = Rect {
+                    x: 1,
+                    y: main_area.height.saturating_sub(height + 1),
+                    width: width.min(main_area.width.saturating_sub(1)),
+                    height: height.min(main_area.height),
+                };
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(heatmap.join("\n"))
+                        .style(Style::default().fg(Color::Green))
+                        .block(Block::default().borders(Borders::ALL).title("Mail per day (H)")),
+                    area,
+                );
+            }
+
+            if let Some(picker) = &app.account_picker {
+                let current = store::account();
+                let items: Vec<ListItem> = picker
+                    .accounts
+                    .iter()
+                    .map(|account| {
+                        let name = account.as_deref().unwrap_or("(default)");
+                        let marker = if *account == current { "● " } else { "  " };
+                        ListItem::new(format!("{}{}", marker, name))
+                    })
+                    .collect();
+                let height = (picker.accounts.len() as u16 + 2).min(main_area.height);
+                let width = 40.min(main_area.width);
+                let area = Rect {
+                    x: main_area.width.saturating_sub(width) / 2,
+                    y: main_area.height.saturating_sub(height) / 2,
+                    width,
+                    height,
+                };
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title("Switch account"))
+                    .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+                let mut state = ListState::default();
+                state.select(Some(picker.selected));
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut state);
+            }
+
+            if let Some(picker) = &app.conflict_picker {
+                let items: Vec<ListItem> = picker
+                    .changes
+                    .iter()
+                    .map(|change| {
+                        let conflict = change.conflict.as_ref().map(|c| c.describe()).unwrap_or_default();
+                        ListItem::new(format!("{} — {}", text::single_line(&change.description), conflict))
+                    })
+                    .collect();
+                let height = (picker.changes.len() as u16 + 2).min(main_area.height);
+                let width = 90.min(main_area.width);
+                let area = Rect {
+                    x: main_area.width.saturating_sub(width) / 2,
+                    y: main_area.height.saturating_sub(height) / 2,
+                    width,
+                    height,
+                };
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title("Offline changes in conflict (s: Skip, f: Force, a/Esc: Abort)"))
+                    .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+                let mut state = ListState::default();
+                state.select(Some(picker.selected));
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut state);
+            }
+
+            if let Some(confirm) = &app.delete_confirm {
+                let prompt = format!(
+                    "{}\n\nThis can't be undone. Type {} and press Enter:\n> {}",
+                    text::single_line(&confirm.subject),
+                    DELETE_CONFIRMATION,
+                    confirm.typed
+                );
+                let width = 60.min(main_area.width);
+                let height = 7.min(main_area.height);
+                let area = Rect {
+                    x: main_area.width.saturating_sub(width) / 2,
+                    y: main_area.height.saturating_sub(height) / 2,
+                    width,
+                    height,
+                };
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(prompt)
+                        .wrap(Wrap { trim: false })
+                        .style(Style::default().fg(Color::Red))
+                        .block(Block::default().borders(Borders::ALL).title("Delete permanently (Esc: Cancel)")),
+                    area,
+                );
+            }
+
+            if let Some(selected) = app.attachment_picker {
+                let items: Vec<ListItem> = app
+                    .current_attachments
+                    .iter()
+                    .map(|a| ListItem::new(format!("{}  ({})", a.filename, attachments::describe_size(a.size))))
+                    .collect();
+                let height = (items.len() as u16 + 2).min(main_area.height);
+                let width = 60.min(main_area.width);
+                let area = Rect {
+                    x: main_area.width.saturating_sub(width) / 2,
+                    y: main_area.height.saturating_sub(height) / 2,
+                    width,
+                    height,
+                };
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title("Save attachment (Enter)"))
+                    .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+                let mut state = ListState::default();
+                state.select(Some(selected));
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut state);
+            }
+
+            if let Some(menu) = &app.address_menu {
+                let items: Vec<ListItem> = ADDRESS_ACTIONS
+                    .iter()
+                    .map(|action| ListItem::new(address_action_label(*action, &menu.address, &app.senders)))
+                    .collect();
+                let width = (menu.address.len() as u16 + 16).clamp(36, main_area.width);
+                let height = (ADDRESS_ACTIONS.len() as u16 + 2).min(main_area.height);
+                let area = Rect {
+                    x: main_area.width.saturating_sub(width) / 2,
+                    y: main_area.height.saturating_sub(height) / 2,
+                    width,
+                    height,
+                };
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(menu.address.as_str()))
+                    .highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+                let mut state = ListState::default();
+                state.select(Some(menu.selected));
+                f.render_widget(Clear, area);
+                f.render_stateful_widget(list, area, &mut state);
+            }
+
+            if app.show_debug {
+                let cache = &app.body_cache;
+                let mut lines = vec![
+                    Line::from(format!("Body cache: {}/{} entries", cache.len(), cache.capacity())),
+                    Line::from(format!(
+                        "Hits: {}  Misses: {}  Hit rate: {:.0}%",
+                        cache.hits,
+                        cache.misses,
+                        cache.hit_rate() * 100.0
+                    )),
+                    Line::from(format!("Rows loaded: {}  More pages: {}", app.emails.len(), app.next_page_token.is_some())),
+                ];
+                lines.extend(timings::lines().into_iter().map(Line::from));
+                let area = Rect {
+                    x: main_area.width.saturating_sub(50),
+                    y: 1,
+                    width: 50.min(main_area.width),
+                    height: (lines.len() as u16 + 2).min(main_area.height),
+                };
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Debug (F12)")),
+                    area,
+                );
+            }
         })?;
+        if !app.emails.is_empty() {
+            timings::mark(timings::FIRST_ROW);
+        }
+        // The preview is set once the selected message's body has arrived
+        if app.current_preview.is_some() {
+            timings::mark(timings::FIRST_BODY);
+        }
 
         // --- User Input ---
-        if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                match app.mode {
-                    AppMode::List => match key.code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Down => app.next(body_request_tx.clone()),
-                        KeyCode::Up => app.previous(body_request_tx.clone()),
-                        KeyCode::Enter => {
-                            app.mode = AppMode::Viewing;
-                        }
-                        _ => {}
-                    },
-                    AppMode::Viewing => match key.code {
-                        KeyCode::Char('q') => {
-                            if let Some(email) = app.emails.get_mut(app.selected_index) {
-                                if email.is_unread {
-                                    if google_api::mark_as_read(&token, &email.id).await.is_ok() {
-                                        email.is_unread = false;
+        if event::poll(std::time::Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+        {
+            app.status_message = None;
+            if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                suspend_to_shell(&mut terminal)?;
+                continue;
+            }
+            if key.code == KeyCode::Char('G') && !matches!(app.mode, AppMode::Command) {
+                let Some(capability) = app.wanted.take() else {
+                    app.status_message = Some("Nothing to grant".to_string());
+                    continue;
+                };
+                suspend_terminal(&mut terminal)?;
+                let granted = login::grant(capability).await;
+                resume_terminal(&mut terminal)?;
+                match granted {
+                    Ok(new_token) => {
+                        token = new_token;
+                        app.granted = granted_capabilities(&token);
+                        workers = Workers::start(&token, &config);
+                        app.status_message = Some(format!("Granted permission for {}; try again", capability.describe()));
+                    }
+                    Err(e) => app.status_message = Some(format!("Not granted: {:#}", e)),
+                }
+                continue;
+            }
+            if key.code == KeyCode::Char('L') && token.is_rejected() && !matches!(app.mode, AppMode::Command) {
+                suspend_terminal(&mut terminal)?;
+                let signed_in = sign_in_again().await;
+                resume_terminal(&mut terminal)?;
+                match signed_in {
+                    Ok(new_token) => {
+                        token = new_token;
+                        app.granted = granted_capabilities(&token);
+                        workers = Workers::start(&token, &config);
+                        app.body_cache = LruCache::new(BODY_CACHE_SIZE);
+                        header_rx = app.switch_view(app.view, &token);
+                        initial_load_done = false;
+                        app.status_message = Some("Signed in again".to_string());
+                    }
+                    Err(e) => app.status_message = Some(format!("Sign-in failed: {:#}", e)),
+                }
+                continue;
+            }
+            if let Some(selected) = app.attachment_picker {
+                match key.code {
+                    KeyCode::Up => app.attachment_picker = Some(selected.saturating_sub(1)),
+                    KeyCode::Down => app.attachment_picker = Some((selected + 1).min(app.current_attachments.len().saturating_sub(1))),
+                    KeyCode::Esc | KeyCode::Char('q') => app.attachment_picker = None,
+                    KeyCode::Enter => {
+                        app.attachment_picker = None;
+                        app.status_message = Some(save_attachment(&app, &token, selected).await);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(confirm) = &mut app.delete_confirm {
+                match key.code {
+                    KeyCode::Esc => app.delete_confirm = None,
+                    KeyCode::Backspace => {
+                        confirm.typed.pop();
+                    }
+                    KeyCode::Char(c) => confirm.typed.push(c),
+                    KeyCode::Enter if confirm.typed != DELETE_CONFIRMATION => {
+                        app.status_message = Some(format!("Type {} to confirm, or Esc to cancel", DELETE_CONFIRMATION));
+                    }
+                    KeyCode::Enter => {
+                        let message_id = confirm.message_id.clone();
+                        app.delete_confirm = None;
+                        app.status_message = Some(match trash::delete_trashed(&token, &message_id).await {
+                            Ok(subject) => {
+                                if let Some(index) = app.emails.iter().position(|e| e.id == message_id) {
+                                    app.selected_index = index;
+                                    app.remove_selected(workers.body_request_tx.clone());
+                                }
+                                app.trash.items.remove(&message_id);
+                                format!("Deleted for good: {}", subject)
+                            }
+                            Err(e) => format!("Delete failed: {:#}", e),
+                        });
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(picker) = &mut app.conflict_picker {
+                match key.code {
+                    KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+                    KeyCode::Down => picker.selected = (picker.selected + 1).min(picker.changes.len() - 1),
+                    // Leaves the rest queued, for :conflicts later
+                    KeyCode::Esc | KeyCode::Char('a') => app.conflict_picker = None,
+                    KeyCode::Char(c @ ('s' | 'f')) => {
+                        let change = &picker.changes[picker.selected];
+                        let resolution = if c == 'f' { Resolution::Force } else { Resolution::Skip };
+                        match offline::resolve(&token, &change.id, resolution).await {
+                            Ok(()) => {
+                                let verb = if c == 'f' { "Forced" } else { "Skipped" };
+                                app.status_message = Some(format!("{}: {}", verb, change.description));
+                                picker.changes.remove(picker.selected);
+                                picker.selected = picker.selected.min(picker.changes.len().saturating_sub(1));
+                                if picker.changes.is_empty() {
+                                    app.conflict_picker = None;
+                                }
+                            }
+                            Err(e) => app.status_message = Some(format!("Couldn't apply it: {:#}", e)),
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(picker) = &mut app.account_picker {
+                match key.code {
+                    KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+                    KeyCode::Down => picker.selected = (picker.selected + 1).min(picker.accounts.len() - 1),
+                    KeyCode::Esc | KeyCode::Char('q') => app.account_picker = None,
+                    KeyCode::Enter => {
+                        let name = picker.accounts[picker.selected].clone();
+                        app.account_picker = None;
+                        if name == store::account() {
+                            continue;
+                        }
+                        // Saved under the account being left, for its next "last" startup
+                        let session = Session { view: Some(app.view), search_query: app.search_query.clone() };
+                        let _ = store::save(SESSION_FILE, &session);
+                        // Unlocking a sealed token asks on the plain terminal
+                        let prompts = google_api::token_prompts(name.as_deref());
+                        if prompts {
+                            suspend_terminal(&mut terminal)?;
+                        }
+                        let opened = open_account(name.as_deref()).await;
+                        if prompts {
+                            resume_terminal(&mut terminal)?;
+                        }
+                        match opened {
+                            Ok((new_token, new_lock)) => {
+                                token = new_token;
+                                _lock = new_lock;
+                                app.granted = granted_capabilities(&token);
+                                workers = Workers::start(&token, &config);
+                                app.watched = WatchedThreads::load();
+                                app.thread_overrides = ThreadOverrides::load();
+                                app.merge_mark = None;
+                                app.deadlines = Deadlines::load();
+                                app.trash = TrashLog::load();
+                                app.senders = SenderLists::load();
+                                app.body_cache = LruCache::new(BODY_CACHE_SIZE);
+                                app.action_log = ActionLog::default();
+                                app.label_counts.clear();
+                                app.classifier = None;
+                                app.label_names.clear();
+                                app.known_senders = KnownSenders::from_cache();
+                                header_rx = app.switch_view(View::Inbox, &token);
+                                initial_load_done = false;
+                                app.status_message =
+                                    Some(format!("Switched to {}", name.as_deref().unwrap_or("the default account")));
+                            }
+                            Err(e) => app.status_message = Some(format!("{:#}", e)),
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(menu) = &mut app.address_menu {
+                match key.code {
+                    KeyCode::Up => menu.selected = menu.selected.saturating_sub(1),
+                    KeyCode::Down => menu.selected = (menu.selected + 1).min(ADDRESS_ACTIONS.len() - 1),
+                    KeyCode::Esc | KeyCode::Char('q') => app.address_menu = None,
+                    KeyCode::Enter => {
+                        let action = ADDRESS_ACTIONS[menu.selected];
+                        let address = std::mem::take(&mut menu.address);
+                        app.address_menu = None;
+                        match run_address_action(&mut app, &mut terminal, &token, &config, action, &address).await {
+                            Ok(CommandEffect::Reload) => {
+                                header_rx = app.switch_view(app.view, &token);
+                                initial_load_done = false;
+                            }
+                            Ok(_) => {}
+                            Err(e) => app.status_message = Some(format!("{:#}", e)),
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match app.mode {
+                AppMode::Command => match key.code {
+                    KeyCode::Esc => {
+                        app.command_input.clear();
+                        app.mode = AppMode::List;
+                    }
+                    KeyCode::Enter => {
+                        let input = std::mem::take(&mut app.command_input);
+                        app.mode = AppMode::List;
+                        match app.execute_command(&input) {
+                            Ok(CommandEffect::Reload) => {
+                                header_rx = app.switch_view(app.view, &token);
+                                initial_load_done = false;
+                            }
+                            Ok(CommandEffect::RunPlugin { name, args }) => {
+                                let context = plugins::PluginContext::new(&token, app.selected_message(), args).await;
+                                // The plugin gets the real terminal until it exits
+                                suspend_terminal(&mut terminal)?;
+                                let result = plugins::run(&name, &context).await;
+                                println!("\nPress Enter to return to gmail-cli.");
+                                let mut line = String::new();
+                                std::io::stdin().read_line(&mut line).ok();
+                                resume_terminal(&mut terminal)?;
+                                app.status_message = Some(match result {
+                                    Ok(status) if status.success() => format!("{} finished", name),
+                                    Ok(status) => format!("{} exited with {}", name, status),
+                                    Err(e) => e.to_string(),
+                                });
+                            }
+                            Ok(CommandEffect::TrashSelected) => {
+                                app.status_message = Some(trash_selected(&mut app, &token, workers.body_request_tx.clone(), false).await);
+                            }
+                            Ok(CommandEffect::Rerender) => app.rerender(workers.body_request_tx.clone()),
+                            Ok(CommandEffect::None) => {}
+                            Err(e) => app.status_message = Some(e.to_string()),
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        app.command_input.pop();
+                    }
+                    KeyCode::Char(c) => app.command_input.push(c),
+                    _ => {}
+                },
+                AppMode::List => match key.code {
+                    KeyCode::Char('q') => break,
+                    // Ahead of the built-in keys, so a binding replaces the one on its key
+                    KeyCode::Char(c) if config.scripts.bindings.contains_key(&c) => {
+                        let selected = app.selected_message();
+                        app.status_message = Some(match scripting::run(&config.scripts.bindings[&c], selected.as_ref()) {
+                            Ok(actions) => apply_script_actions(&mut app, &mut terminal, &token, &config, actions).await?,
+                            Err(e) => format!("Script error: {:#}", e),
+                        });
+                    }
+                    KeyCode::Tab => app.cycle_focus(),
+                    KeyCode::Down | KeyCode::Up if app.focused() == Focus::Preview => {
+                        app.preview_scroll = match key.code {
+                            KeyCode::Down => app.preview_scroll.saturating_add(1),
+                            _ => app.preview_scroll.saturating_sub(1),
+                        };
+                    }
+                    KeyCode::Down if app.focused() == Focus::Sidebar => app.action_log.select_next(),
+                    KeyCode::Up if app.focused() == Focus::Sidebar => app.action_log.select_previous(),
+                    KeyCode::Down if app.at_end_with_more_pages() => {
+                        if let Some(rx) = app.load_next_page(&token) {
+                            header_rx = rx;
+                        }
+                    }
+                    KeyCode::Down => {
+                        app.next(workers.body_request_tx.clone());
+                        // Reaching the last loaded row starts on the next page right away
+                        if app.at_end_with_more_pages()
+                            && let Some(rx) = app.load_next_page(&token)
+                        {
+                            header_rx = rx;
+                        }
+                    }
+                    KeyCode::Up => app.previous(workers.body_request_tx.clone()),
+                    KeyCode::Enter if app.view == View::Drafts => {
+                        app.status_message = Some(match resume_draft(&app, &mut terminal, &token, &config).await {
+                            Ok(status) => status,
+                            Err(e) => format!("Draft failed: {:#}", e),
+                        });
+                        header_rx = app.switch_view(View::Drafts, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Enter => app.open_selected(&token),
+                    KeyCode::Char('w') if app.ensure_local().is_err() => {
+                        app.status_message = app.ensure_local().err().map(|e| e.to_string());
+                    }
+                    KeyCode::Char('w') => {
+                        if let Some(email) = app.emails.get(app.selected_index) {
+                            let thread_id = email.thread_id.clone();
+                            let subject = email.subject.clone();
+                            match app.watched.toggle(&token, &thread_id, &subject).await {
+                                Ok(now_watched) => app.action_log.record(if now_watched {
+                                    Action::Watched { thread_id, subject }
+                                } else {
+                                    Action::Unwatched { thread_id, subject }
+                                }),
+                                Err(e) => app.status_message = Some(format!("Couldn't change the watch list: {:#}", e)),
+                            }
+                        }
+                    }
+                    KeyCode::Char('t') => app.triage_layout = !app.triage_layout,
+                    KeyCode::Char('l') => {
+                        if let Err(e) = app.ensure_writable() {
+                            app.status_message = Some(e.to_string());
+                        } else if let Some(email) = app.emails.get(app.selected_index)
+                            && let Some(label_id) = email.suggested_label.clone()
+                        {
+                            let name = app.label_name(&label_id).to_string();
+                            let description = format!("Label {}: {}", name, email.subject);
+                            let target = Target::Message(email.id.clone());
+                            app.status_message = Some(match offline::apply_or_queue(&token, target, &[&label_id], &[], &description).await {
+                                Ok(applied) => {
+                                    let email = &mut app.emails[app.selected_index];
+                                    email.label_ids.push(label_id.clone());
+                                    email.suggested_label = None;
+                                    app.action_log.record(Action::Labeled {
+                                        message_id: email.id.clone(),
+                                        label_id,
+                                        label_name: name.clone(),
+                                        subject: email.subject.clone(),
+                                    });
+                                    if applied { format!("Labeled {}", name) } else { format!("Offline; will label {} when back online", name) }
+                                }
+                                Err(e) => format!("Labeling failed: {:#}", e),
+                            });
+                        }
+                    }
+                    KeyCode::Char('d') => {
+                        if let Err(e) = app.ensure_writable().and_then(|_| app.ensure_local()) {
+                            app.status_message = Some(e.to_string());
+                        } else if app.view != View::Outbox && app.emails.get(app.selected_index).is_some() {
+                            // In Trash, d takes the message back out
+                            let restore = app.view == View::Trash;
+                            app.status_message = Some(trash_selected(&mut app, &token, workers.body_request_tx.clone(), restore).await);
+                        }
+                    }
+                    KeyCode::Char('X') if app.view == View::Trash => {
+                        if let Err(e) = app.ensure_capability(Capability::Delete).and_then(|_| app.ensure_local()) {
+                            app.status_message = Some(e.to_string());
+                        } else if let Some(email) = app.emails.get(app.selected_index) {
+                            app.delete_confirm =
+                                Some(DeleteConfirm { message_id: email.id.clone(), subject: email.subject.clone(), typed: String::new() });
+                        }
+                    }
+                    KeyCode::Char('e') => {
+                        if let Err(e) = app.ensure_writable() {
+                            app.status_message = Some(e.to_string());
+                        } else if let Some(email) = app.emails.get(app.selected_index).filter(|_| app.view != View::Outbox) {
+                            // A conversation row stands for the whole thread, so all of it leaves the inbox
+                            let thread_id = (app.view.threaded() && !app.thread_overrides.is_split(&email.id)).then(|| email.thread_id.clone());
+                            let (message_id, subject) = (email.id.clone(), email.subject.clone());
+                            // Undo goes through the selected account's token
+                            let local = email.foreign_token().is_none();
+                            // Changes queued offline are synced with the selected account's token
+                            let result = match (email.foreign_token(), &thread_id) {
+                                (Some(foreign), Some(thread_id)) => google_api::archive_thread(foreign, thread_id).await.map(|_| true),
+                                (Some(foreign), None) => google_api::archive_message(foreign, &message_id).await.map(|_| true),
+                                (None, _) => {
+                                    let target = match &thread_id {
+                                        Some(thread_id) => Target::Thread(thread_id.clone()),
+                                        None => Target::Message(message_id.clone()),
+                                    };
+                                    offline::apply_or_queue(&token, target, &[], &["INBOX"], &format!("Archive: {}", subject)).await
+                                }
+                            };
+                            app.status_message = Some(match result {
+                                Ok(applied) => {
+                                    // Other views keep the row, which still belongs in them
+                                    if matches!(app.view, View::Inbox | View::Unified | View::NeedsReply) {
+                                        app.remove_selected(workers.body_request_tx.clone());
+                                    } else {
+                                        app.emails[app.selected_index].label_ids.retain(|l| l != "INBOX");
+                                    }
+                                    if local {
+                                        app.action_log.record(Action::Archived { message_id, thread_id, subject: subject.clone() });
                                     }
+                                    if applied { format!("Archived: {}", subject) } else { format!("Offline; will archive when back online: {}", subject) }
                                 }
+                                Err(e) => format!("Archive failed: {:#}", e),
+                            });
+                        }
+                    }
+                    KeyCode::Char('A') => {
+                        let current = store::account();
+                        let accounts: Vec<Option<String>> =
+                            std::iter::once(None).chain(store::accounts().into_iter().map(Some)).collect();
+                        let selected = accounts.iter().position(|a| *a == current).unwrap_or(0);
+                        app.account_picker = Some(AccountPicker { accounts, selected });
+                    }
+                    KeyCode::F(12) => app.show_debug = !app.show_debug,
+                    KeyCode::Char('H') => {
+                        app.heatmap = match app.heatmap {
+                            Some(_) => None,
+                            None => {
+                                let counts = heatmap::daily_counts(&cache::cached_messages());
+                                Some(heatmap::render(&counts, HEATMAP_WEEKS, chrono::Local::now().date_naive()))
+                            }
+                        };
+                    }
+                    KeyCode::Char('D') => {
+                        app.density = match app.density {
+                            Density::Compact => Density::Comfortable,
+                            Density::Comfortable => Density::Compact,
+                        };
+                    }
+                    KeyCode::Char(':') => app.mode = AppMode::Command,
+                    KeyCode::Char('/') => {
+                        app.command_input = "search ".to_string();
+                        app.mode = AppMode::Command;
+                    }
+                    KeyCode::Char('b') => {
+                        app.command_input = "due ".to_string();
+                        app.mode = AppMode::Command;
+                    }
+                    KeyCode::Char('K') => app.action_log.select_previous(),
+                    KeyCode::Char('J') => app.action_log.select_next(),
+                    KeyCode::Char('u') => {
+                        if let Some(entry) = app.action_log.selected_mut()
+                            && !entry.undone
+                            && entry.action.undo(&token, &mut app.watched).await.is_ok()
+                        {
+                            entry.undone = true;
+                            if let Action::MarkedRead { message_id, .. } = &entry.action
+                                && let Some(email) = app.emails.iter_mut().find(|e| &e.id == message_id)
+                            {
+                                email.is_unread = true;
+                            }
+                            if let Action::Labeled { message_id, label_id, .. } = &entry.action
+                                && let Some(email) = app.emails.iter_mut().find(|e| &e.id == message_id)
+                            {
+                                email.label_ids.retain(|l| l != label_id);
+                            }
+                        }
+                    }
+                    KeyCode::Char('1') => {
+                        header_rx = app.switch_view(View::Inbox, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('2') => {
+                        header_rx = app.switch_view(View::Sent, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('3') => {
+                        header_rx = app.switch_view(View::Outbox, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('4') => {
+                        header_rx = app.switch_view(View::Archive, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('5') => {
+                        header_rx = app.switch_view(View::AllMail, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('6') => {
+                        header_rx = app.switch_view(View::Trash, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('7') => {
+                        header_rx = app.switch_view(View::NeedsReply, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('0') => {
+                        let view = if app.view == View::AwaitingMe { View::AwaitingOthers } else { View::AwaitingMe };
+                        header_rx = app.switch_view(view, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('8') => {
+                        header_rx = app.switch_view(View::Unified, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('9') => {
+                        header_rx = app.switch_view(View::Drafts, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('r') if app.view == View::Outbox && app.ensure_capability(Capability::Send).is_err() => {
+                        app.status_message = app.ensure_capability(Capability::Send).err().map(|e| e.to_string());
+                    }
+                    KeyCode::Char('r') if app.view == View::Outbox => {
+                        if let Some(email) = app.emails.get(app.selected_index) {
+                            let _ = outbox::retry_one(&token, &email.id).await;
+                        }
+                        header_rx = app.switch_view(View::Outbox, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char('x') if app.view == View::Outbox => {
+                        if let Some(email) = app.emails.get(app.selected_index) {
+                            let _ = Outbox::discard(&email.id).await;
+                        }
+                        header_rx = app.switch_view(View::Outbox, &token);
+                        initial_load_done = false;
+                    }
+                    KeyCode::Char(c @ ('R' | 'g')) => {
+                        app.status_message = Some(match reply_to_selected(&app, &mut terminal, &token, &config, c == 'g').await {
+                            Ok(status) => status,
+                            Err(e) => format!("Reply failed: {:#}", e),
+                        });
+                    }
+                    KeyCode::Char('f') => {
+                        app.status_message = Some(match forward_selected(&app, &mut terminal, &token, &config).await {
+                            Ok(status) => status,
+                            Err(e) => format!("Forward failed: {:#}", e),
+                        });
+                    }
+                    _ => {}
+                },
+                AppMode::Viewing => match key.code {
+                    KeyCode::Char('q') => {
+                        let unread = app.emails.get(app.selected_index).is_some_and(|e| e.is_unread);
+                        // Without the modify permission the message stays unread on the server; say
+                        // so, with the way to grant it, rather than skipping it silently
+                        let can_mark = unread && !app.read_only && {
+                            let granted = app.ensure_capability(Capability::Modify);
+                            if let Err(e) = &granted {
+                                app.status_message = Some(format!("Not marked read on Gmail: {}", e));
+                            }
+                            granted.is_ok()
+                        };
+                        if let Some(email) = app.emails.get_mut(app.selected_index)
+                            && can_mark
+                            && google_api::mark_as_read(email.foreign_token().unwrap_or(&token), &email.id).await.is_ok()
+                        {
+                            email.is_unread = false;
+                            // Undo goes through the selected account's token
+                            if email.foreign_token().is_none() {
+                                app.action_log.record(Action::MarkedRead {
+                                    message_id: email.id.clone(),
+                                    subject: email.subject.clone(),
+                                });
+                            }
+                        }
+                        // The panel describes this message's sender only
+                        app.sender_panel = None;
+                        app.timeline = None;
+                        app.timeline_loading = None;
+                        app.conversation = None;
+                        app.conversation_loading = None;
+                        app.mode = AppMode::List;
+                        app.focus = Focus::List;
+                    }
+                    KeyCode::Tab => app.cycle_focus(),
+                    KeyCode::Down if app.focused() == Focus::Sidebar => app.sidebar_scroll = app.sidebar_scroll.saturating_add(1),
+                    KeyCode::Up if app.focused() == Focus::Sidebar => app.sidebar_scroll = app.sidebar_scroll.saturating_sub(1),
+                    KeyCode::Down => app.scroll_down(),
+                    KeyCode::Up => app.scroll_up(),
+                    KeyCode::Char('v') => {
+                        app.reader_mode = !app.reader_mode;
+                        app.rerender(workers.body_request_tx.clone());
+                    }
+                    KeyCode::Enter => {
+                        if let Some(email) = app.emails.get(app.selected_index).filter(|_| app.view != View::Outbox) {
+                            // Sent rows list the recipients; the menu acts on the first one
+                            let first = email.from.split(',').next().unwrap_or_default();
+                            app.address_menu = Some(AddressMenu { address: address::extract_address(first), selected: 0 });
+                        }
+                    }
+                    KeyCode::Char('p') => app.show_privacy = !app.show_privacy,
+                    KeyCode::Char('c') if app.conversation.take().is_some() => app.scroll_offset = 0,
+                    // Pressed again while loading, it stays on just this message
+                    KeyCode::Char('c') if app.conversation_loading.take().is_some() => app.status_message = None,
+                    KeyCode::Char('c') => {
+                        if let Some(email) = app.emails.get(app.selected_index).filter(|e| !e.thread_id.is_empty()) {
+                            let overrides = match (&email.account, email.foreign_token()) {
+                                (Some(account), Some(_)) => ThreadOverrides::load_for(account.name.as_deref()),
+                                _ => app.thread_overrides.clone(),
+                            };
+                            let token = email.foreign_token().unwrap_or(&token).clone();
+                            let (thread_id, open_id) = (email.thread_id.clone(), email.id.clone());
+                            let (senders, reader) = (app.senders.clone(), app.reader_mode);
+                            let (tx, rx) = tokio::sync::oneshot::channel();
+                            tokio::spawn(async move {
+                                let _ = tx.send(Conversation::load(&token, &thread_id, &open_id, &overrides, &senders, reader).await);
+                            });
+                            app.conversation_loading = Some(rx);
+                            app.status_message = Some("Loading the conversation...".to_string());
+                        }
+                    }
+                    KeyCode::Char(c @ (']' | '[')) if app.conversation.is_some() => {
+                        if let Some(conversation) = &mut app.conversation {
+                            conversation.move_cursor(c == ']');
+                            app.scroll_offset = conversation.offset();
+                        }
+                    }
+                    KeyCode::Char(' ') if app.conversation.is_some() => {
+                        if let Some(conversation) = &mut app.conversation {
+                            conversation.toggle();
+                            app.scroll_offset = conversation.offset();
+                        }
+                    }
+                    KeyCode::Char('a') => match app.current_attachments.len() {
+                        0 if app.current_preview.is_none() => app.status_message = Some("Still loading".to_string()),
+                        0 => app.status_message = Some("No attachments".to_string()),
+                        1 => app.status_message = Some(save_attachment(&app, &token, 0).await),
+                        _ => app.attachment_picker = Some(0),
+                    },
+                    KeyCode::Char('T') => {
+                        if let Some(original) = app.untranslated_body.take() {
+                            app.current_email_body = original;
+                        } else if app.current_preview.is_none() {
+                            app.status_message = Some("Nothing to translate yet".to_string());
+                        } else if app.translation.is_none() {
+                            let body = app.current_email_body.clone();
+                            // The header block ends at the first blank line; only the text is sent
+                            let text = body.split_once("\n\n").map_or(body.as_str(), |(_, text)| text).to_string();
+                            let (tx, rx) = tokio::sync::oneshot::channel();
+                            let translate_config = config.translate.clone();
+                            tokio::spawn(async move {
+                                let _ = tx.send(translate::translate(&text, &translate_config).await);
+                            });
+                            app.translation = Some(PendingTranslation { body, rx });
+                            app.status_message = Some("Translating...".to_string());
+                        }
+                    }
+                    KeyCode::Char('P') if app.speech.take().is_some() => {}
+                    KeyCode::Char('P') => {
+                        if let Some(email) = app.emails.get(app.selected_index)
+                            && app.current_preview.is_some()
+                        {
+                            // The header block is summed up rather than read out field by field
+                            let text = app.current_email_body.split_once("\n\n").map_or(app.current_email_body.as_str(), |(_, text)| text);
+                            let intro = format!("Message from {}. Subject: {}.\n\n", address::display_name(&email.from), email.subject);
+                            match Speech::start(&format!("{}{}", intro, text), &config.speech) {
+                                Ok(speech) => app.speech = Some(speech),
+                                Err(e) => app.status_message = Some(format!("Couldn't read aloud: {:#}", e)),
                             }
-                            app.mode = AppMode::List;
+                        } else {
+                            app.status_message = Some("Nothing to read yet".to_string());
+                        }
+                    }
+                    KeyCode::Char('i') => {
+                        app.sender_panel = match (&app.sender_panel, app.emails.get(app.selected_index)) {
+                            (None, Some(email)) => {
+                                let sender = address::extract_address(&email.from);
+                                let times = stats::response_times(&cache::cached_messages());
+                                Some(format!("{}\n\n{}", sender, stats::describe_correspondent(times.get(&sender))))
+                            }
+                            _ => None,
+                        };
+                    }
+                    KeyCode::Char(c @ ('R' | 'g')) => {
+                        app.status_message = Some(match reply_to_selected(&app, &mut terminal, &token, &config, c == 'g').await {
+                            Ok(status) => status,
+                            Err(e) => format!("Reply failed: {:#}", e),
+                        });
+                    }
+                    KeyCode::Char('f') => {
+                        app.status_message = Some(match forward_selected(&app, &mut terminal, &token, &config).await {
+                            Ok(status) => status,
+                            Err(e) => format!("Forward failed: {:#}", e),
+                        });
+                    }
+                    KeyCode::Char('s') => {
+                        if let Some(email) = app.emails.get(app.selected_index) {
+                            app.command_input = format!("search from:{}", address::extract_address(&email.from));
+                            app.mode = AppMode::Command;
+                        }
+                    }
+                    KeyCode::Char('S') => {
+                        if let Some(email) = app.emails.get(app.selected_index) {
+                            let subject = strip_reply_prefixes(&email.subject).replace('"', "");
+                            app.command_input = format!("search subject:\"{}\"", subject);
+                            app.mode = AppMode::Command;
                         }
-                        KeyCode::Down => app.scroll_down(),
-                        KeyCode::Up => app.scroll_up(),
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
         }
     }
 
     // --- Cleanup ---
+    let session = Session { view: Some(app.view), search_query: app.search_query.clone() };
+    let _ = store::save(SESSION_FILE, &session);
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     Ok(())
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/anishNagula/gmail-cli/releases/latest";
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+const USER_AGENT: &str = concat!("gmail-cli/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+// Release binaries are published as gmail-cli-<arch>-<os>[.exe]
+fn asset_name() -> String {
+    format!(
+        "gmail-cli-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['.', '-'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn is_cargo_install(exe: &Path) -> bool {
+    let cargo_bin = std::env::var_os("CARGO_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+        .map(|dir| dir.join("bin"));
+    cargo_bin.is_some_and(|bin| exe.starts_with(bin))
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let bytes = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(bytes.to_vec())
+}
+
+// SHA256SUMS lines are "<hex digest>  <file name>", as written by sha256sum
+fn expected_digest(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (digest, file) = line.split_once(char::is_whitespace)?;
+        (file.trim().trim_start_matches('*') == name).then(|| digest.to_ascii_lowercase())
+    })
+}
+
+// The running binary is renamed aside first, which also works on Windows where it can't be
+// overwritten while in use
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = exe.with_extension("new");
+    let previous = exe.with_extension("old");
+    fs::write(&staged, binary).with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    fs::rename(exe, &previous).with_context(|| format!("Failed to move {} aside", exe.display()))?;
+    if let Err(e) = fs::rename(&staged, exe) {
+        fs::rename(&previous, exe).ok();
+        fs::remove_file(&staged).ok();
+        return Err(e).context("Failed to install the new binary; the old one was restored");
+    }
+    // Windows keeps the old file locked until exit; it is replaced on the next update
+    fs::remove_file(&previous).ok();
+    Ok(())
+}
+
+/// Installs the latest GitHub release over the running binary after checking its SHA-256
+/// against the release's checksum list. With `check_only`, just reports whether one exists.
+pub async fn run(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    // GitHub is reached through the same proxy settings as Google
+    let client = crate::google_api::http_client();
+    let release: Release = client
+        .get(RELEASES_URL)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to query the latest release")?
+        .json()
+        .await?;
+
+    if parse_version(&release.tag_name) <= parse_version(current) {
+        println!("gmail-cli {} is up to date.", current);
+        return Ok(());
+    }
+    println!("Update available: {} -> {}", current, release.tag_name);
+    if check_only {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?.canonicalize()?;
+    if is_cargo_install(&exe) {
+        println!("This copy was installed with cargo; update it with `cargo install gmail-cli --force`.");
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let find = |wanted: &str| release.assets.iter().find(|a| a.name == wanted);
+    let Some(asset) = find(&name) else {
+        bail!("Release {} has no binary for this platform ({})", release.tag_name, name);
+    };
+    let Some(checksums) = find(CHECKSUMS_ASSET) else {
+        bail!("Release {} has no {}; refusing to install an unverified binary", release.tag_name, CHECKSUMS_ASSET);
+    };
+
+    let checksums = String::from_utf8(download(&client, &checksums.browser_download_url).await?)?;
+    let expected = expected_digest(&checksums, &name)
+        .with_context(|| format!("{} does not list {}", CHECKSUMS_ASSET, name))?;
+    println!("Downloading {}...", name);
+    let binary = download(&client, &asset.browser_download_url).await?;
+    let actual: String = Sha256::digest(&binary).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        bail!("Checksum mismatch for {} (expected {}, got {})", name, expected, actual);
+    }
+
+    replace_executable(&exe, &binary)?;
+    println!("Updated {} to {}.", exe.display(), release.tag_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(parse_version("v1.2.10") > parse_version("1.2.9"));
+        assert_eq!(parse_version("v0.3.0"), parse_version("0.3.0"));
+        // A pre-release suffix stops the parse, so it never counts as newer than its release
+        assert_eq!(parse_version("1.4.0-rc1"), vec![1, 4, 0]);
+    }
+
+    #[test]
+    fn digest_is_found_by_exact_file_name() {
+        let sums = "aaaa  gmail-cli-linux.tar.gz\nbbbb  gmail-cli-linux\n";
+        assert_eq!(expected_digest(sums, "gmail-cli-linux").as_deref(), Some("bbbb"));
+        assert_eq!(expected_digest(sums, "gmail-cli-macos"), None);
+    }
+
+    #[test]
+    fn binary_mode_names_and_mixed_case_digests_are_accepted() {
+        let sums = "AbCd01  *gmail-cli-windows.exe\r\n";
+        assert_eq!(expected_digest(sums, "gmail-cli-windows.exe").as_deref(), Some("abcd01"));
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Lets the daemon and cron jobs unlock the token without a terminal
+pub const PASSPHRASE_ENV: &str = "GMAIL_CLI_PASSPHRASE";
+const FORMAT: &str = "gmail-cli-sealed-v1";
+const SALT_LEN: usize = 16;
+
+/// A passphrase-encrypted blob: ChaCha20-Poly1305 under a key derived with Argon2id.
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    format: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// The key each file was last opened or sealed with, so a token refresh mid-session doesn't
+// prompt again. Accounts may use different passphrases, so keys never cross files.
+static UNLOCKED: Mutex<BTreeMap<PathBuf, ([u8; SALT_LEN], Key)>> = Mutex::new(BTreeMap::new());
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(prompt).context("Failed to read the passphrase")
+}
+
+fn new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("New passphrase for the saved token: ")?;
+    if passphrase.is_empty() {
+        bail!("The passphrase can't be empty");
+    }
+    if rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+        bail!("The passphrases don't match");
+    }
+    Ok(passphrase)
+}
+
+pub fn is_sealed(content: &str) -> bool {
+    serde_json::from_str::<Sealed>(content).is_ok_and(|sealed| sealed.format == FORMAT)
+}
+
+/// Encrypts `plaintext` for the file at `path` with the key it was unlocked with this run, or
+/// asks for a new passphrase.
+pub fn seal(path: &Path, plaintext: &[u8]) -> Result<String> {
+    let mut unlocked = UNLOCKED.lock().map_err(|_| anyhow!("Key cache poisoned"))?;
+    let (salt, key) = match unlocked.get(path) {
+        Some(pair) => *pair,
+        None => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(&new_passphrase()?, &salt)?;
+            unlocked.insert(path.to_path_buf(), (salt, key));
+            (salt, key)
+        }
+    };
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    Ok(serde_json::to_string_pretty(&Sealed {
+        format: FORMAT.to_string(),
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })?)
+}
+
+/// Decrypts what `seal` wrote to `path`, asking for the passphrase unless it is in the
+/// environment.
+pub fn open(path: &Path, content: &str) -> Result<Vec<u8>> {
+    let sealed: Sealed = serde_json::from_str(content)?;
+    let salt: [u8; SALT_LEN] = STANDARD
+        .decode(&sealed.salt)?
+        .try_into()
+        .map_err(|_| anyhow!("Corrupt salt in the sealed file"))?;
+    let nonce = STANDARD.decode(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        bail!("Corrupt nonce in the sealed file");
+    }
+    let ciphertext = STANDARD.decode(&sealed.ciphertext)?;
+
+    let key = derive_key(&read_passphrase("Passphrase for the saved token: ")?, &salt)?;
+    let plaintext = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("Wrong passphrase, or the token file was modified"))?;
+    if let Ok(mut unlocked) = UNLOCKED.lock() {
+        unlocked.insert(path.to_path_buf(), (salt, key));
+    }
+    Ok(plaintext)
+}
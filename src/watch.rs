@@ -0,0 +1,65 @@
+use crate::google_api::{self, ApiToken};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const WATCHED_THREADS_FILE: &str = "watched_threads.json";
+// Held while the file is loaded, changed and saved; the TUI and the daemon both write it
+const WATCHED_LOCK: &str = "watched_threads.lock";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchedThread {
+    pub subject: String,
+    pub message_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WatchedThreads {
+    pub threads: HashMap<String, WatchedThread>,
+}
+
+impl WatchedThreads {
+    pub fn load() -> Self {
+        crate::store::load(WATCHED_THREADS_FILE)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        crate::store::save(WATCHED_THREADS_FILE, self)
+    }
+
+    pub fn contains(&self, thread_id: &str) -> bool {
+        self.threads.contains_key(thread_id)
+    }
+
+    /// Starts or stops watching a thread, returning whether it is now watched. The current
+    /// message count is recorded so only messages arriving after this point trigger alerts.
+    pub async fn toggle(&mut self, token: &ApiToken, thread_id: &str, subject: &str) -> Result<bool> {
+        let entry = if self.contains(thread_id) {
+            None
+        } else {
+            let thread = google_api::get_thread(token, thread_id, &["Subject"]).await?;
+            Some(WatchedThread { subject: subject.to_string(), message_count: thread.messages.map_or(0, |m| m.len()) })
+        };
+        let watching = entry.is_some();
+        let _lock = crate::store::wait_for_lock(WATCHED_LOCK).await?;
+        *self = Self::load();
+        match entry {
+            Some(entry) => self.threads.insert(thread_id.to_string(), entry),
+            None => self.threads.remove(thread_id),
+        };
+        self.save()?;
+        Ok(watching)
+    }
+
+    /// Saves the message counts the daemon last saw, for threads that are still watched.
+    pub async fn record_counts(&mut self, counts: &HashMap<String, usize>) -> Result<()> {
+        let _lock = crate::store::wait_for_lock(WATCHED_LOCK).await?;
+        *self = Self::load();
+        for (thread_id, count) in counts {
+            if let Some(entry) = self.threads.get_mut(thread_id) {
+                entry.message_count = *count;
+            }
+        }
+        self.save()
+    }
+}